@@ -0,0 +1,214 @@
+use crate::{Point3, Ray, Scalar, Vector3};
+
+/// Axis aligned bounding box
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct Aabb {
+    pub min: Point3,
+    pub max: Point3,
+}
+
+impl Aabb {
+    /// Creates bounding box spanned between two corners
+    pub fn new(min: Point3, max: Point3) -> Self {
+        Self { min, max }
+    }
+
+    /// Creates bounding box, which does not contain anything.
+    /// It is a neutral element of the `union` operation.
+    pub fn empty() -> Self {
+        Self {
+            min: Point3::new(Scalar::INFINITY, Scalar::INFINITY, Scalar::INFINITY),
+            max: Point3::new(
+                Scalar::NEG_INFINITY,
+                Scalar::NEG_INFINITY,
+                Scalar::NEG_INFINITY,
+            ),
+        }
+    }
+
+    /// Creates the smallest bounding box containing all points
+    pub fn from_points<'a>(points: impl IntoIterator<Item = &'a Point3>) -> Self {
+        points
+            .into_iter()
+            .fold(Self::empty(), |aabb, point| aabb.grown(point))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.min.x > self.max.x || self.min.y > self.max.y || self.min.z > self.max.z
+    }
+
+    /// Returns bounding box enlarged to contain point
+    pub fn grown(&self, point: &Point3) -> Self {
+        Self {
+            min: self.min.inf(point),
+            max: self.max.sup(point),
+        }
+    }
+
+    /// Returns the smallest bounding box containing both boxes
+    pub fn union(&self, other: &Self) -> Self {
+        Self {
+            min: self.min.inf(&other.min),
+            max: self.max.sup(&other.max),
+        }
+    }
+
+    /// Returns bounding box enlarged on each side by `margin`
+    pub fn padded(&self, margin: Scalar) -> Self {
+        let margin = Vector3::new(margin, margin, margin);
+        Self {
+            min: self.min - margin,
+            max: self.max + margin,
+        }
+    }
+
+    pub fn extent(&self) -> Vector3 {
+        self.max - self.min
+    }
+
+    pub fn centroid(&self) -> Point3 {
+        nalgebra::center(&self.min, &self.max)
+    }
+
+    pub fn surface_area(&self) -> Scalar {
+        if self.is_empty() {
+            return 0.0;
+        }
+        let e = self.extent();
+        2.0 * (e.x * e.y + e.y * e.z + e.z * e.x)
+    }
+
+    /// Computes ray parameter at which ray enters the box.
+    /// If ray misses the box within `[ray.t_min, t_max]`, it returns None.
+    /// `inv_direction` has to be component-wise inverse of ray direction.
+    pub fn entry(&self, ray: &Ray, inv_direction: &Vector3, t_max: Scalar) -> Option<Scalar> {
+        let mut t_near = ray.t_min;
+        let mut t_far = t_max;
+        for axis in 0..3 {
+            let t0 = (self.min[axis] - ray.origin[axis]) * inv_direction[axis];
+            let t1 = (self.max[axis] - ray.origin[axis]) * inv_direction[axis];
+            let (t0, t1) = if inv_direction[axis] < 0.0 {
+                (t1, t0)
+            } else {
+                (t0, t1)
+            };
+            // `max` and `min` ignore NaNs, which appear when ray is
+            // parallel to the slab and starts exactly on its plane.
+            t_near = t_near.max(t0);
+            t_far = t_far.min(t1);
+            if t_near > t_far {
+                return None;
+            }
+        }
+        Some(t_near)
+    }
+}
+
+impl Default for Aabb {
+    fn default() -> Self {
+        Self::empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unit_box() -> Aabb {
+        Aabb::new(Point3::new(0.0, 0.0, 0.0), Point3::new(1.0, 1.0, 1.0))
+    }
+
+    fn inverse(v: &Vector3) -> Vector3 {
+        v.map(|c| 1.0 / c)
+    }
+
+    #[test]
+    fn empty_box_is_neutral_for_union() {
+        assert!(Aabb::empty().is_empty());
+        assert_eq!(Aabb::empty().union(&unit_box()), unit_box());
+        assert_eq!(Aabb::empty().surface_area(), 0.0);
+    }
+
+    #[test]
+    fn box_from_points_contains_them() {
+        let aabb = Aabb::from_points(&[
+            Point3::new(1.0, -1.0, 0.0),
+            Point3::new(0.0, 2.0, 0.5),
+            Point3::new(-1.0, 0.0, -3.0),
+        ]);
+        assert_eq!(
+            aabb,
+            Aabb::new(Point3::new(-1.0, -1.0, -3.0), Point3::new(1.0, 2.0, 0.5))
+        );
+        assert_eq!(aabb.centroid(), Point3::new(0.0, 0.5, -1.25));
+    }
+
+    #[test]
+    fn box_surface_area() {
+        let aabb = Aabb::new(Point3::new(0.0, 0.0, 0.0), Point3::new(1.0, 2.0, 3.0));
+        assert!((aabb.surface_area() - 22.0).abs() <= Scalar::EPSILON);
+    }
+
+    #[test]
+    fn ray_enters_box_in_front_of_it() {
+        let ray = Ray::new(Point3::new(0.5, 0.5, -1.0), Vector3::new(0.0, 0.0, 1.0));
+        let entry = unit_box().entry(&ray, &inverse(&ray.direction), Scalar::INFINITY);
+        assert_eq!(entry, Some(1.0));
+    }
+
+    #[test]
+    fn ray_starting_inside_box_enters_it_immediately() {
+        let ray = Ray::new(Point3::new(0.5, 0.5, 0.5), Vector3::new(1.0, 1.0, 0.0));
+        let entry = unit_box().entry(&ray, &inverse(&ray.direction), Scalar::INFINITY);
+        assert_eq!(entry, Some(0.0));
+    }
+
+    #[test]
+    fn ray_misses_box() {
+        let ray = Ray::new(Point3::new(1.5, 0.5, -1.0), Vector3::new(0.0, 0.0, 1.0));
+        assert_eq!(
+            unit_box().entry(&ray, &inverse(&ray.direction), Scalar::INFINITY),
+            None
+        );
+        let ray = Ray::new(Point3::new(0.5, 0.5, 2.0), Vector3::new(0.0, 0.0, 1.0));
+        assert_eq!(
+            unit_box().entry(&ray, &inverse(&ray.direction), Scalar::INFINITY),
+            None
+        );
+    }
+
+    #[test]
+    fn box_farther_than_limit_is_missed() {
+        let ray = Ray::new(Point3::new(0.5, 0.5, -1.0), Vector3::new(0.0, 0.0, 1.0));
+        assert_eq!(unit_box().entry(&ray, &inverse(&ray.direction), 0.5), None);
+    }
+
+    #[test]
+    fn box_behind_ray_bounds_is_missed() {
+        let ray = Ray::new_bounded(
+            Point3::new(0.5, 0.5, -1.0),
+            Vector3::new(0.0, 0.0, 1.0),
+            2.5,
+            Scalar::INFINITY,
+        );
+        assert_eq!(
+            unit_box().entry(&ray, &inverse(&ray.direction), ray.t_max),
+            None
+        );
+        let ray = Ray { t_min: 1.5, ..ray };
+        assert_eq!(
+            unit_box().entry(&ray, &inverse(&ray.direction), ray.t_max),
+            Some(1.5)
+        );
+    }
+
+    #[test]
+    fn flat_box_can_be_entered() {
+        let aabb = Aabb::new(Point3::new(0.0, 0.0, 1.0), Point3::new(1.0, 1.0, 1.0));
+        let ray = Ray::new(Point3::new(0.5, 0.5, 0.0), Vector3::new(0.0, 0.0, 1.0));
+        assert_eq!(
+            aabb.entry(&ray, &inverse(&ray.direction), Scalar::INFINITY),
+            Some(1.0)
+        );
+    }
+}
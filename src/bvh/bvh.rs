@@ -0,0 +1,309 @@
+use crate::{bvh::Aabb, Point3, Ray, Scalar};
+
+/// Number of buckets used to evaluate surface area heuristic along an axis
+const BINS_COUNT: usize = 16;
+/// Nodes with at most this many primitives may become leaves
+const MAX_LEAF_SIZE: usize = 4;
+/// Cost of visiting a node relative to the cost of intersecting a primitive
+const TRAVERSAL_COST: Scalar = 0.125;
+/// Relative margin added to primitive bounds and closest distance, so that
+/// rounding errors never make traversal skip a primitive brute force would hit.
+const MARGIN: Scalar = 128.0 * Scalar::EPSILON;
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+enum NodeKind {
+    /// Leaf referencing `count` primitive indices starting at `first`
+    Leaf { first: usize, count: usize },
+    /// Interior node. First child is stored right after the node.
+    Interior { second_child: usize, axis: usize },
+}
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+struct Node {
+    bounds: Aabb,
+    kind: NodeKind,
+}
+
+/// Bounding volume hierarchy built with surface area heuristic.
+/// It only stores primitive indices, so it can accelerate queries over
+/// any indexable collection of primitives.
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct Bvh {
+    nodes: Vec<Node>,
+    indices: Vec<usize>,
+}
+
+/// Helper struct describing primitives of one node during construction
+struct BuildItem {
+    bounds: Aabb,
+    centroid: Point3,
+}
+
+impl Bvh {
+    /// Builds hierarchy over primitives with given bounding boxes.
+    /// Primitives are identified by their index in `bounds`.
+    pub fn new(bounds: &[Aabb]) -> Self {
+        let items: Vec<BuildItem> = bounds
+            .iter()
+            .map(|b| {
+                let magnitude = b.min.coords.amax().max(b.max.coords.amax());
+                BuildItem {
+                    bounds: b.padded(MARGIN * magnitude),
+                    centroid: b.centroid(),
+                }
+            })
+            .collect();
+        let mut bvh = Self {
+            nodes: Vec::new(),
+            indices: (0..bounds.len()).collect(),
+        };
+        if !items.is_empty() {
+            bvh.build_node(&items, 0, items.len());
+        }
+        bvh
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    /// Finds primitive closest to ray's origin.
+    /// `intersect` is called with primitive index and distance to the closest
+    /// hit found so far. It returns distance to the hit together with any
+    /// hit data. Ties are resolved in favour of the primitive with the lower index.
+    pub fn closest_hit<H, F>(&self, ray: &Ray, mut intersect: F) -> Option<(usize, H)>
+    where
+        F: FnMut(usize, Scalar) -> Option<(Scalar, H)>,
+    {
+        if self.is_empty() {
+            return None;
+        }
+        let inv_direction = ray.direction.map(|c| 1.0 / c);
+        let origin_magnitude = ray.origin.coords.amax();
+        let mut closest: Option<(Scalar, usize, H)> = None;
+        let mut stack = vec![0];
+        while let Some(node_index) = stack.pop() {
+            let node = &self.nodes[node_index];
+            let limit = closest.as_ref().map_or(ray.t_max, |(d, _, _)| *d);
+            let padded_limit = limit + MARGIN * (limit + origin_magnitude);
+            if node
+                .bounds
+                .entry(ray, &inv_direction, padded_limit)
+                .is_none()
+            {
+                continue;
+            }
+            match node.kind {
+                NodeKind::Leaf { first, count } => {
+                    for &index in &self.indices[first..first + count] {
+                        let limit = closest.as_ref().map_or(ray.t_max, |(d, _, _)| *d);
+                        if let Some((distance, hit)) = intersect(index, limit) {
+                            let is_closer = match &closest {
+                                None => true,
+                                Some((d, i, _)) => distance < *d || (distance == *d && index < *i),
+                            };
+                            if is_closer {
+                                closest = Some((distance, index, hit));
+                            }
+                        }
+                    }
+                }
+                NodeKind::Interior { second_child, axis } => {
+                    // Push farther child first, so that nearer one is visited first
+                    if ray.direction[axis] < 0.0 {
+                        stack.push(node_index + 1);
+                        stack.push(second_child);
+                    } else {
+                        stack.push(second_child);
+                        stack.push(node_index + 1);
+                    }
+                }
+            }
+        }
+        closest.map(|(_, index, hit)| (index, hit))
+    }
+
+    fn build_node(&mut self, items: &[BuildItem], first: usize, last: usize) -> usize {
+        let node_index = self.nodes.len();
+        let range = &self.indices[first..last];
+        let bounds = range
+            .iter()
+            .fold(Aabb::empty(), |b, &i| b.union(&items[i].bounds));
+        self.nodes.push(Node {
+            bounds,
+            kind: NodeKind::Leaf {
+                first,
+                count: last - first,
+            },
+        });
+
+        let split = match self.find_split(items, first, last, &bounds) {
+            Some(split) => split,
+            None => return node_index,
+        };
+        let (axis, bin, centroid_bounds) = split;
+        let mid = self.partition(first, last, |i| {
+            Self::bin_of(&items[i].centroid, &centroid_bounds, axis) <= bin
+        });
+
+        self.build_node(items, first, mid);
+        let second_child = self.build_node(items, mid, last);
+        self.nodes[node_index].kind = NodeKind::Interior { second_child, axis };
+        node_index
+    }
+
+    /// Finds the cheapest split according to surface area heuristic.
+    /// Returns axis, last bin of the first child and bounds of centroids
+    /// or None, if it is better to keep primitives in one leaf.
+    fn find_split(
+        &self,
+        items: &[BuildItem],
+        first: usize,
+        last: usize,
+        bounds: &Aabb,
+    ) -> Option<(usize, usize, Aabb)> {
+        let count = last - first;
+        if count <= 1 {
+            return None;
+        }
+        let range = &self.indices[first..last];
+        let centroid_bounds = Aabb::from_points(range.iter().map(|&i| &items[i].centroid));
+        let extent = centroid_bounds.extent();
+
+        let mut best: Option<(Scalar, usize, usize)> = None;
+        for axis in 0..3 {
+            if extent[axis] <= 0.0 {
+                continue;
+            }
+            let mut bins = [(Aabb::empty(), 0usize); BINS_COUNT];
+            for &i in range {
+                let bin = &mut bins[Self::bin_of(&items[i].centroid, &centroid_bounds, axis)];
+                bin.0 = bin.0.union(&items[i].bounds);
+                bin.1 += 1;
+            }
+            // Sweep from the right to get costs of all right halves
+            let mut right_costs = [0.0; BINS_COUNT];
+            let mut accumulated = (Aabb::empty(), 0usize);
+            for bin in (1..BINS_COUNT).rev() {
+                accumulated = (
+                    accumulated.0.union(&bins[bin].0),
+                    accumulated.1 + bins[bin].1,
+                );
+                right_costs[bin - 1] = accumulated.0.surface_area() * accumulated.1 as Scalar;
+            }
+            let mut accumulated = (Aabb::empty(), 0usize);
+            for bin in 0..BINS_COUNT - 1 {
+                accumulated = (
+                    accumulated.0.union(&bins[bin].0),
+                    accumulated.1 + bins[bin].1,
+                );
+                if accumulated.1 == 0 || accumulated.1 == count {
+                    continue;
+                }
+                let cost =
+                    accumulated.0.surface_area() * accumulated.1 as Scalar + right_costs[bin];
+                let better = match best {
+                    Some((best_cost, _, _)) => cost < best_cost,
+                    None => true,
+                };
+                if better {
+                    best = Some((cost, axis, bin));
+                }
+            }
+        }
+
+        let (cost, axis, bin) = best?;
+        let cost = TRAVERSAL_COST + cost / bounds.surface_area().max(Scalar::MIN_POSITIVE);
+        if count <= MAX_LEAF_SIZE && cost >= count as Scalar {
+            return None;
+        }
+        Some((axis, bin, centroid_bounds))
+    }
+
+    fn bin_of(centroid: &Point3, centroid_bounds: &Aabb, axis: usize) -> usize {
+        let extent = centroid_bounds.max[axis] - centroid_bounds.min[axis];
+        let relative = (centroid[axis] - centroid_bounds.min[axis]) / extent;
+        ((relative * BINS_COUNT as Scalar) as usize).min(BINS_COUNT - 1)
+    }
+
+    /// Moves indices satisfying predicate to the front of the range.
+    /// Returns index of the first element not satisfying predicate.
+    fn partition<P: Fn(usize) -> bool>(
+        &mut self,
+        first: usize,
+        last: usize,
+        predicate: P,
+    ) -> usize {
+        let mut mid = first;
+        for i in first..last {
+            if predicate(self.indices[i]) {
+                self.indices.swap(i, mid);
+                mid += 1;
+            }
+        }
+        mid
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Vector3;
+
+    fn unit_box_at(x: Scalar) -> Aabb {
+        Aabb::new(Point3::new(x, 0.0, 0.0), Point3::new(x + 1.0, 1.0, 1.0))
+    }
+
+    #[test]
+    fn empty_hierarchy_has_no_hits() {
+        let bvh = Bvh::new(&[]);
+        let ray = Ray::new(Point3::new(0.0, 0.0, -1.0), Vector3::new(0.0, 0.0, 1.0));
+        assert!(bvh.is_empty());
+        assert_eq!(bvh.closest_hit(&ray, |i, _| Some((0.0, i))), None);
+    }
+
+    #[test]
+    fn hierarchy_bounds_contain_all_primitives() {
+        let boxes: Vec<Aabb> = (0..10).map(|i| unit_box_at(2.0 * i as Scalar)).collect();
+        let bvh = Bvh::new(&boxes);
+        let bounds = bvh.nodes[0].bounds;
+        for b in boxes {
+            assert_eq!(bounds.union(&b), bounds);
+        }
+    }
+
+    #[test]
+    fn every_primitive_is_referenced_once() {
+        let boxes: Vec<Aabb> = (0..100).map(|i| unit_box_at(1.5 * i as Scalar)).collect();
+        let bvh = Bvh::new(&boxes);
+        let mut indices = bvh.indices.clone();
+        indices.sort_unstable();
+        assert_eq!(indices, (0..100).collect::<Vec<_>>());
+        assert!(bvh.nodes.len() > 1, "primitives should be split");
+    }
+
+    #[test]
+    fn closest_hit_resolves_ties_by_index() {
+        let boxes = vec![unit_box_at(0.0); 10];
+        let bvh = Bvh::new(&boxes);
+        let ray = Ray::new(Point3::new(0.5, 0.5, -1.0), Vector3::new(0.0, 0.0, 1.0));
+        assert_eq!(
+            bvh.closest_hit(&ray, |i, _| if i >= 3 { Some((1.0, i)) } else { None }),
+            Some((3, 3))
+        );
+    }
+
+    #[test]
+    fn closest_hit_skips_primitives_not_on_ray() {
+        let boxes: Vec<Aabb> = (0..50).map(|i| unit_box_at(2.0 * i as Scalar)).collect();
+        let bvh = Bvh::new(&boxes);
+        let ray = Ray::new(Point3::new(20.5, 0.5, -1.0), Vector3::new(0.0, 0.0, 1.0));
+        let mut visited = Vec::new();
+        let hit = bvh.closest_hit(&ray, |i, _| {
+            visited.push(i);
+            Some((1.0, i))
+        });
+        assert_eq!(hit, Some((10, 10)));
+        assert!(visited.len() < boxes.len());
+    }
+}
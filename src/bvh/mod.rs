@@ -0,0 +1,6 @@
+mod aabb;
+pub use aabb::Aabb;
+
+#[allow(clippy::module_inception)]
+mod bvh;
+pub use bvh::Bvh;
@@ -0,0 +1,432 @@
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+
+use image::{hdr::HdrDecoder, ImageResult};
+use nalgebra::Unit;
+
+use crate::{environment::EnvironmentSample, Colour, Point2, Rotation3, Scalar, Vector3};
+
+/// Radiance arriving from infinitely far away, stored as equirectangular image.
+/// Columns map to azimuth around the y axis with the center of the image looking
+/// along -z, and rows map to polar angle with the top row looking along +y.
+/// Directions are sampled with density proportional to luminance of pixels.
+#[derive(Debug, PartialEq, Clone)]
+pub struct EnvironmentMap {
+    width: u32,
+    height: u32,
+    pixels: Vec<Colour>,
+    rotation: Rotation3,
+    intensity: Scalar,
+    /// Distribution of rows and of columns in every row
+    rows: Distribution,
+    columns: Vec<Distribution>,
+}
+
+/// Piecewise constant distribution over `[0, 1)`
+#[derive(Debug, PartialEq, Clone)]
+struct Distribution {
+    weights: Vec<Scalar>,
+    /// Cumulative weights normalized to end with one
+    cdf: Vec<Scalar>,
+    /// Mean of weights
+    mean: Scalar,
+}
+
+impl EnvironmentMap {
+    /// Creates map from pixels given row by row
+    pub fn new(width: u32, height: u32, pixels: Vec<Colour>) -> Self {
+        assert!(
+            width > 0 && height > 0,
+            "Environment map has to be non-empty"
+        );
+        assert_eq!(
+            pixels.len(),
+            width as usize * height as usize,
+            "Pixels do not match environment map size"
+        );
+        // Rows near the poles cover smaller solid angle
+        let columns: Vec<Distribution> = pixels
+            .chunks(width as usize)
+            .enumerate()
+            .map(|(row, pixels)| {
+                let theta = std::f32::consts::PI * (row as Scalar + 0.5) / height as Scalar;
+                Distribution::new(
+                    pixels
+                        .iter()
+                        .map(|p| p.luminance().max(0.0) * theta.sin())
+                        .collect(),
+                )
+            })
+            .collect();
+        let rows = Distribution::new(columns.iter().map(|c| c.mean).collect());
+        Self {
+            width,
+            height,
+            pixels,
+            rotation: Rotation3::identity(),
+            intensity: 1.0,
+            rows,
+            columns,
+        }
+    }
+
+    /// Creates map from radiance arriving from the centers of its pixels
+    pub fn from_radiance(width: u32, height: u32, radiance: impl Fn(&Vector3) -> Colour) -> Self {
+        let pixels = (0..width * height)
+            .map(|i| {
+                let uv = Point2::new(
+                    ((i % width) as Scalar + 0.5) / width as Scalar,
+                    ((i / width) as Scalar + 0.5) / height as Scalar,
+                );
+                radiance(&equirectangular_direction(&uv))
+            })
+            .collect();
+        Self::new(width, height, pixels)
+    }
+
+    /// Reads map from Radiance HDR file
+    pub fn load(path: impl AsRef<Path>) -> ImageResult<Self> {
+        let decoder = HdrDecoder::new(BufReader::new(File::open(path)?))?;
+        let metadata = decoder.metadata();
+        let pixels = decoder
+            .read_image_hdr()?
+            .into_iter()
+            .map(|p| Colour {
+                red: p.0[0],
+                green: p.0[1],
+                blue: p.0[2],
+            })
+            .collect();
+        Ok(Self::new(metadata.width, metadata.height, pixels))
+    }
+
+    /// Sets rotation from map space to world space
+    pub fn with_rotation(self, rotation: Rotation3) -> Self {
+        Self { rotation, ..self }
+    }
+
+    /// Sets factor scaling radiance of all pixels
+    pub fn with_intensity(self, intensity: Scalar) -> Self {
+        assert!(intensity >= 0.0, "Intensity has to be non-negative");
+        Self { intensity, ..self }
+    }
+
+    pub fn get_width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn get_height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn get_rotation(&self) -> &Rotation3 {
+        &self.rotation
+    }
+
+    pub fn get_intensity(&self) -> Scalar {
+        self.intensity
+    }
+
+    /// Returns radiance arriving from direction
+    pub fn get_radiance(&self, direction: &Vector3) -> Colour {
+        let uv = self.get_uv(direction);
+        let column = ((uv.x * self.width as Scalar) as usize).min(self.width as usize - 1);
+        let row = ((uv.y * self.height as Scalar) as usize).min(self.height as usize - 1);
+        self.pixels[row * self.width as usize + column] * self.intensity
+    }
+
+    /// Returns luminance integrated over all directions. Pixels of negative
+    /// luminance are counted as black like when sampling.
+    pub fn get_power(&self) -> Scalar {
+        let pi = std::f32::consts::PI;
+        let rows = self.pixels.chunks(self.width as usize).enumerate();
+        let power: Scalar = rows
+            .map(|(row, pixels)| {
+                let top = pi * row as Scalar / self.height as Scalar;
+                let bottom = pi * (row + 1) as Scalar / self.height as Scalar;
+                let luminance: Scalar = pixels.iter().map(|p| p.luminance().max(0.0)).sum();
+                luminance * (top.cos() - bottom.cos())
+            })
+            .sum();
+        power * 2.0 * pi / self.width as Scalar * self.intensity
+    }
+
+    /// Maps point of the unit square to direction. Bright parts of the map are chosen more often.
+    /// Returns `None` for black map.
+    pub fn sample(&self, u: &Point2) -> Option<EnvironmentSample> {
+        let (y, row) = self.rows.sample(u.y)?;
+        let (x, _) = self.columns[row].sample(u.x)?;
+        let direction = self.get_direction(&Point2::new(x, y));
+        let pdf = self.get_pdf(&direction);
+        if pdf <= 0.0 {
+            return None;
+        }
+        Some(EnvironmentSample {
+            direction: Unit::new_normalize(direction),
+            radiance: self.get_radiance(&direction),
+            pdf,
+        })
+    }
+
+    /// Returns density with respect to solid angle, with which `sample` generates direction
+    pub fn get_pdf(&self, direction: &Vector3) -> Scalar {
+        let uv = self.get_uv(direction);
+        let sin_theta = (std::f32::consts::PI * uv.y).sin();
+        if sin_theta <= 0.0 || self.rows.mean <= 0.0 {
+            return 0.0;
+        }
+        let column = ((uv.x * self.width as Scalar) as usize).min(self.width as usize - 1);
+        let row = ((uv.y * self.height as Scalar) as usize).min(self.height as usize - 1);
+        let columns = &self.columns[row];
+        // Density on the unit square is the product of marginal and conditional ones
+        let pdf = self.rows.get_pdf(row) * columns.get_pdf(column);
+        // Square maps to sphere by φ = 2π u and θ = π v, so dω = 2π² sin θ du dv
+        pdf / (2.0 * std::f32::consts::PI * std::f32::consts::PI * sin_theta)
+    }
+
+    /// Returns equirectangular image coordinates in `[0, 1]²` of world direction
+    fn get_uv(&self, direction: &Vector3) -> Point2 {
+        let d = self
+            .rotation
+            .inverse_transform_vector(direction)
+            .normalize();
+        let phi = d.x.atan2(-d.z);
+        let theta = d.y.clamp(-1.0, 1.0).acos();
+        Point2::new(
+            (phi + std::f32::consts::PI) / (2.0 * std::f32::consts::PI),
+            theta / std::f32::consts::PI,
+        )
+    }
+
+    /// Returns world direction of equirectangular image coordinates
+    fn get_direction(&self, uv: &Point2) -> Vector3 {
+        self.rotation * equirectangular_direction(uv)
+    }
+}
+
+/// Returns direction of equirectangular image coordinates in map space
+fn equirectangular_direction(uv: &Point2) -> Vector3 {
+    let phi = 2.0 * std::f32::consts::PI * uv.x - std::f32::consts::PI;
+    let theta = std::f32::consts::PI * uv.y;
+    Vector3::new(
+        theta.sin() * phi.sin(),
+        theta.cos(),
+        -theta.sin() * phi.cos(),
+    )
+}
+
+impl Distribution {
+    fn new(weights: Vec<Scalar>) -> Self {
+        let total: Scalar = weights.iter().sum();
+        let cdf = weights
+            .iter()
+            .scan(0.0, |sum, w| {
+                *sum += w;
+                Some(*sum / total)
+            })
+            .collect();
+        Self {
+            mean: total / weights.len() as Scalar,
+            weights,
+            cdf,
+        }
+    }
+
+    /// Maps `u` in `[0, 1)` to point in `[0, 1)` with density proportional to weights.
+    /// Returns the point and index of its piece, or `None` if all weights are zero.
+    fn sample(&self, u: Scalar) -> Option<(Scalar, usize)> {
+        if self.mean <= 0.0 {
+            return None;
+        }
+        let index = self
+            .cdf
+            .partition_point(|&c| c <= u)
+            .min(self.weights.len() - 1);
+        let start = if index == 0 { 0.0 } else { self.cdf[index - 1] };
+        let offset = ((u - start) / (self.cdf[index] - start)).clamp(0.0, 1.0);
+        let x = (index as Scalar + offset) / self.weights.len() as Scalar;
+        Some((x.min(1.0 - Scalar::EPSILON / 2.0), index))
+    }
+
+    /// Returns density of points in piece with given index
+    fn get_pdf(&self, index: usize) -> Scalar {
+        if self.mean <= 0.0 {
+            return 0.0;
+        }
+        self.weights[index] / self.mean
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{hdr::HDREncoder, Rgb};
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    /// Map with dark upper half and bright spot in the lower half
+    fn spot_map() -> EnvironmentMap {
+        let (width, height) = (16, 8);
+        let pixels = (0..width * height)
+            .map(|i| match (i % width, i / width) {
+                (5, 6) => Colour::grey(100.0),
+                (_, row) if row < 4 => Colour::grey(0.5),
+                _ => Colour::grey(0.0),
+            })
+            .collect();
+        EnvironmentMap::new(width, height, pixels)
+    }
+
+    fn random_direction(rng: &mut StdRng) -> Vector3 {
+        let z: Scalar = rng.gen_range(-1.0..1.0);
+        let phi: Scalar = rng.gen_range(0.0..2.0 * std::f32::consts::PI);
+        let r = (1.0 - z * z).sqrt();
+        Vector3::new(r * phi.cos(), r * phi.sin(), z)
+    }
+
+    #[test]
+    fn directions_map_to_pixels() {
+        let map = spot_map();
+        assert_eq!(map.get_radiance(&Vector3::y()), Colour::grey(0.5));
+        assert_eq!(map.get_radiance(&-Vector3::y()), Colour::grey(0.0));
+        // Column 5 of 16 lies at azimuth (5.5 / 16 - 1 / 2) 2π from -z
+        let phi = (5.5 / 16.0 - 0.5) * 2.0 * std::f32::consts::PI;
+        let theta = 6.5 / 8.0 * std::f32::consts::PI;
+        let spot = Vector3::new(
+            theta.sin() * phi.sin(),
+            theta.cos(),
+            -theta.sin() * phi.cos(),
+        );
+        assert_eq!(map.get_radiance(&spot), Colour::grey(100.0));
+        assert_eq!(map.get_radiance(&(spot * 3.0)), Colour::grey(100.0));
+
+        let rotation = Rotation3::from_axis_angle(&Vector3::y_axis(), 1.0);
+        let rotated = map.clone().with_rotation(rotation).with_intensity(2.0);
+        assert_eq!(
+            rotated.get_radiance(&(rotation * spot)),
+            Colour::grey(200.0)
+        );
+        assert_eq!(rotated.get_radiance(&spot), Colour::grey(0.0));
+    }
+
+    #[test]
+    fn uv_coordinates_round_trip() {
+        let map = spot_map().with_rotation(Rotation3::new(Vector3::new(0.3, -0.2, 0.5)));
+        let mut rng = StdRng::seed_from_u64(0);
+        for _ in 0..100 {
+            let direction = random_direction(&mut rng);
+            let back = map.get_direction(&map.get_uv(&direction));
+            assert!((back - direction).norm() < 1e-4, "{} {}", back, direction);
+        }
+    }
+
+    #[test]
+    fn samples_follow_their_pdf() {
+        let map = spot_map().with_rotation(Rotation3::new(Vector3::new(0.3, -0.2, 0.5)));
+        let mut rng = StdRng::seed_from_u64(1);
+        let count = 10_000;
+        let mut spot = 0;
+        for _ in 0..count {
+            let sample = map.sample(&Point2::new(rng.gen(), rng.gen())).unwrap();
+            let pdf = map.get_pdf(&sample.direction);
+            assert!(
+                (sample.pdf - pdf).abs() < 1e-3 * pdf,
+                "{} {}",
+                sample.pdf,
+                pdf
+            );
+            assert_eq!(sample.radiance, map.get_radiance(&sample.direction));
+            assert_ne!(sample.radiance, Colour::grey(0.0));
+            if sample.radiance == Colour::grey(100.0) {
+                spot += 1;
+            }
+        }
+        // Spot and upper half weighted by luminance and solid angle of their pixels
+        let solid_angle = |row: Scalar| (std::f32::consts::PI * (row + 0.5) / 8.0).sin();
+        let spot_weight = 100.0 * solid_angle(6.0);
+        let sky_weight: Scalar = (0..4)
+            .map(|row| 16.0 * 0.5 * solid_angle(row as Scalar))
+            .sum();
+        let expected = spot_weight / (spot_weight + sky_weight);
+        let fraction = spot as Scalar / count as Scalar;
+        assert!(
+            (fraction - expected).abs() < 0.02,
+            "{} {}",
+            fraction,
+            expected
+        );
+    }
+
+    #[test]
+    fn pdf_integrates_to_one() {
+        let pixels = (0..32)
+            .map(|i| Colour::grey((i % 8 + i / 8) as Scalar))
+            .collect();
+        let map = EnvironmentMap::new(8, 4, pixels);
+        let mut rng = StdRng::seed_from_u64(2);
+        let count = 200_000;
+        let sum: Scalar = (0..count)
+            .map(|_| map.get_pdf(&random_direction(&mut rng)))
+            .sum();
+        let integral = sum / count as Scalar * 4.0 * std::f32::consts::PI;
+        assert!((integral - 1.0).abs() < 0.03, "{}", integral);
+    }
+
+    #[test]
+    fn map_is_created_from_radiance_of_pixel_centers() {
+        let radiance = |d: &Vector3| Colour::grey(d.y.max(0.0) + d.x.abs());
+        let map = EnvironmentMap::from_radiance(16, 8, radiance);
+        let mut rng = StdRng::seed_from_u64(3);
+        for _ in 0..100 {
+            let uv = Point2::new(rng.gen(), rng.gen());
+            let center = Point2::new(
+                ((uv.x * 16.0).floor() + 0.5) / 16.0,
+                ((uv.y * 8.0).floor() + 0.5) / 8.0,
+            );
+            let direction = map.get_direction(&uv);
+            assert_eq!(
+                map.get_radiance(&direction),
+                radiance(&map.get_direction(&center))
+            );
+        }
+        // Uniform map of unit luminance has power of the whole sphere
+        let uniform = EnvironmentMap::new(3, 5, vec![Colour::grey(1.0); 15]).with_intensity(2.0);
+        assert!((uniform.get_power() - 8.0 * std::f32::consts::PI).abs() < 1e-4);
+        // Negative pixels do not cancel out bright ones
+        let mut pixels = vec![Colour::grey(1.0); 15];
+        pixels[7] = Colour::grey(-1e3);
+        let darkened = EnvironmentMap::new(3, 5, pixels);
+        pixels = vec![Colour::grey(1.0); 15];
+        pixels[7] = Colour::grey(0.0);
+        let blackened = EnvironmentMap::new(3, 5, pixels);
+        assert_eq!(darkened.get_power(), blackened.get_power());
+    }
+
+    #[test]
+    fn black_map_gives_no_samples() {
+        let map = EnvironmentMap::new(4, 2, vec![Colour::grey(0.0); 8]);
+        assert_eq!(map.sample(&Point2::new(0.5, 0.5)), None);
+        assert_eq!(map.get_pdf(&Vector3::x()), 0.0);
+    }
+
+    #[test]
+    fn map_is_loaded_from_hdr_file() {
+        let path = std::env::temp_dir().join(format!("rustracer-env-{}.hdr", std::process::id()));
+        let data: Vec<Rgb<f32>> = (0..8).map(|i| Rgb([i as f32, 0.5, 0.25])).collect();
+        HDREncoder::new(File::create(&path).unwrap())
+            .encode(&data, 4, 2)
+            .unwrap();
+        let map = EnvironmentMap::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!((map.get_width(), map.get_height()), (4, 2));
+        assert_eq!(
+            map.pixels[5],
+            Colour {
+                red: 5.0,
+                green: 0.5,
+                blue: 0.25
+            }
+        );
+        assert!(EnvironmentMap::load(&path).is_err());
+    }
+}
@@ -0,0 +1,65 @@
+use nalgebra::Unit;
+
+use crate::{Colour, Point2, Scalar, Vector3};
+
+mod map;
+pub use map::EnvironmentMap;
+
+mod sky;
+pub use sky::Sky;
+
+/// Direction sampled from the environment
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct EnvironmentSample {
+    /// Direction, from which light arrives
+    pub direction: Unit<Vector3>,
+    pub radiance: Colour,
+    /// Density of sampling the direction with respect to solid angle
+    pub pdf: Scalar,
+}
+
+/// Light arriving from infinitely far away, which is seen by rays leaving the scene
+#[derive(Debug, PartialEq, Clone)]
+pub enum Environment {
+    Map(EnvironmentMap),
+    Sky(Sky),
+}
+
+impl Environment {
+    /// Returns radiance arriving from direction
+    pub fn get_radiance(&self, direction: &Vector3) -> Colour {
+        match self {
+            Environment::Map(map) => map.get_radiance(direction),
+            Environment::Sky(sky) => sky.get_radiance(direction),
+        }
+    }
+
+    /// Maps point of the unit square to direction, from which bright light arrives
+    /// more often. Returns `None` for black environment.
+    pub fn sample(&self, u: &Point2) -> Option<EnvironmentSample> {
+        match self {
+            Environment::Map(map) => map.sample(u),
+            Environment::Sky(sky) => sky.sample(u),
+        }
+    }
+
+    /// Returns density with respect to solid angle, with which `sample` generates direction
+    pub fn get_pdf(&self, direction: &Vector3) -> Scalar {
+        match self {
+            Environment::Map(map) => map.get_pdf(direction),
+            Environment::Sky(sky) => sky.get_pdf(direction),
+        }
+    }
+}
+
+impl From<EnvironmentMap> for Environment {
+    fn from(map: EnvironmentMap) -> Self {
+        Environment::Map(map)
+    }
+}
+
+impl From<Sky> for Environment {
+    fn from(sky: Sky) -> Self {
+        Environment::Sky(sky)
+    }
+}
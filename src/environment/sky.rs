@@ -0,0 +1,369 @@
+use nalgebra::Unit;
+
+use crate::{
+    environment::{EnvironmentMap, EnvironmentSample},
+    Colour, Frame, Point2, Scalar, Vector3,
+};
+
+/// Angular radius of the sun disk
+const SUN_RADIUS: Scalar = 0.00465;
+/// Illuminance from the sun outside the atmosphere in klx
+const SOLAR_ILLUMINANCE: Scalar = 128.0;
+/// Wavelengths in micrometers, at which red, green and blue are attenuated by the atmosphere
+const WAVELENGTHS: [Scalar; 3] = [0.68, 0.55, 0.44];
+/// Resolution of the map used to sample directions of the sky
+const SAMPLING_WIDTH: u32 = 128;
+const SAMPLING_HEIGHT: u32 = 64;
+
+/// Daylight sky with the sun disk (Preetham et al., A Practical Analytic Model
+/// for Daylight, 1999). The sun is placed at `elevation` above the horizon and
+/// `azimuth` measured from -z towards +x, both in radians. `turbidity` describes
+/// haze of the atmosphere, from 2 for clear to 10 for hazy sky.
+/// Radiance is luminance in kcd/m² scaled by intensity. The ground below
+/// the horizon is black.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Sky {
+    elevation: Scalar,
+    azimuth: Scalar,
+    turbidity: Scalar,
+    intensity: Scalar,
+    sun_direction: Unit<Vector3>,
+    sun_radiance: Colour,
+    /// Luminance and chromaticity x, y at the zenith
+    zenith: [Scalar; 3],
+    /// Perez coefficients A to E of luminance and chromaticity x, y
+    perez: [[Scalar; 5]; 3],
+    /// Sky without the sun rasterized for importance sampling
+    sampling_map: EnvironmentMap,
+    /// Probability of sampling the sun disk instead of the sky
+    sun_probability: Scalar,
+}
+
+impl Sky {
+    pub fn new(elevation: Scalar, azimuth: Scalar, turbidity: Scalar) -> Self {
+        assert!(
+            (0.0..=std::f32::consts::FRAC_PI_2).contains(&elevation),
+            "Sun elevation has to be between 0 and π/2"
+        );
+        assert!(
+            (1.7..=10.0).contains(&turbidity),
+            "Turbidity has to be between 1.7 and 10"
+        );
+        let sun_direction = Unit::new_normalize(Vector3::new(
+            elevation.cos() * azimuth.sin(),
+            elevation.sin(),
+            -elevation.cos() * azimuth.cos(),
+        ));
+        let t = turbidity;
+        let theta = std::f32::consts::FRAC_PI_2 - elevation;
+        let chi = (4.0 / 9.0 - t / 120.0) * (std::f32::consts::PI - 2.0 * theta);
+        let chromaticity = |coefficients: [[Scalar; 4]; 3]| {
+            let [t2, t1, t0] =
+                coefficients.map(|[a, b, c, d]| ((a * theta + b) * theta + c) * theta + d);
+            (t2 * t + t1) * t + t0
+        };
+        let zenith = [
+            (4.0453 * t - 4.9710) * chi.tan() - 0.2155 * t + 2.4192,
+            chromaticity([
+                [0.00166, -0.00375, 0.00209, 0.0],
+                [-0.02903, 0.06377, -0.03202, 0.00394],
+                [0.11693, -0.21196, 0.06052, 0.25886],
+            ]),
+            chromaticity([
+                [0.00275, -0.00610, 0.00317, 0.0],
+                [-0.04214, 0.08970, -0.04153, 0.00516],
+                [0.15346, -0.26756, 0.06670, 0.26688],
+            ]),
+        ];
+        let perez = [
+            [
+                0.1787 * t - 1.4630,
+                -0.3554 * t + 0.4275,
+                -0.0227 * t + 5.3251,
+                0.1206 * t - 2.5771,
+                -0.0670 * t + 0.3703,
+            ],
+            [
+                -0.0193 * t - 0.2592,
+                -0.0665 * t + 0.0008,
+                -0.0004 * t + 0.2125,
+                -0.0641 * t - 0.8989,
+                -0.0033 * t + 0.0452,
+            ],
+            [
+                -0.0167 * t - 0.2608,
+                -0.0950 * t + 0.0092,
+                -0.0079 * t + 0.2102,
+                -0.0441 * t - 1.6537,
+                -0.0109 * t + 0.0529,
+            ],
+        ];
+        let mut sky = Self {
+            elevation,
+            azimuth,
+            turbidity,
+            intensity: 1.0,
+            sun_direction,
+            sun_radiance: get_sun_radiance(theta, turbidity),
+            zenith,
+            perez,
+            sampling_map: EnvironmentMap::new(1, 1, vec![Colour::default()]),
+            sun_probability: 0.0,
+        };
+        sky.sampling_map = EnvironmentMap::from_radiance(SAMPLING_WIDTH, SAMPLING_HEIGHT, |d| {
+            sky.get_sky_radiance(d)
+        });
+        // Sun and sky are sampled in proportion to their power, but neither is starved
+        let sky_power = sky.sampling_map.get_power();
+        let sun_power = sky.sun_radiance.luminance() * get_sun_solid_angle();
+        sky.sun_probability = (sun_power / (sun_power + sky_power)).clamp(0.1, 0.9);
+        sky
+    }
+
+    /// Sets factor scaling radiance of the sky and the sun
+    pub fn with_intensity(self, intensity: Scalar) -> Self {
+        assert!(intensity >= 0.0, "Intensity has to be non-negative");
+        Self { intensity, ..self }
+    }
+
+    pub fn get_elevation(&self) -> Scalar {
+        self.elevation
+    }
+
+    pub fn get_azimuth(&self) -> Scalar {
+        self.azimuth
+    }
+
+    pub fn get_turbidity(&self) -> Scalar {
+        self.turbidity
+    }
+
+    pub fn get_intensity(&self) -> Scalar {
+        self.intensity
+    }
+
+    /// Returns direction towards the center of the sun disk
+    pub fn get_sun_direction(&self) -> &Unit<Vector3> {
+        &self.sun_direction
+    }
+
+    /// Returns radiance of the sun disk, which is reddened by the atmosphere
+    pub fn get_sun_radiance(&self) -> Colour {
+        self.sun_radiance * self.intensity
+    }
+
+    /// Returns radiance arriving from direction
+    pub fn get_radiance(&self, direction: &Vector3) -> Colour {
+        let mut radiance = self.get_sky_radiance(direction);
+        if self.is_in_sun(direction) && direction.y > 0.0 {
+            radiance += self.sun_radiance;
+        }
+        radiance * self.intensity
+    }
+
+    /// Maps point of the unit square to direction.
+    /// The sun disk and bright parts of the sky are chosen more often.
+    pub fn sample(&self, u: &Point2) -> Option<EnvironmentSample> {
+        let direction = if u.x < self.sun_probability {
+            let u = Point2::new(u.x / self.sun_probability, u.y);
+            self.sample_sun(&u)
+        } else {
+            let u = Point2::new(
+                (u.x - self.sun_probability) / (1.0 - self.sun_probability),
+                u.y,
+            );
+            self.sampling_map.sample(&u)?.direction.into_inner()
+        };
+        let direction = Unit::new_normalize(direction);
+        let pdf = self.get_pdf(&direction);
+        if pdf <= 0.0 {
+            return None;
+        }
+        Some(EnvironmentSample {
+            direction,
+            radiance: self.get_radiance(&direction),
+            pdf,
+        })
+    }
+
+    /// Returns density with respect to solid angle, with which `sample` generates direction
+    pub fn get_pdf(&self, direction: &Vector3) -> Scalar {
+        let sun_pdf = if self.is_in_sun(direction) {
+            1.0 / get_sun_solid_angle()
+        } else {
+            0.0
+        };
+        self.sun_probability * sun_pdf
+            + (1.0 - self.sun_probability) * self.sampling_map.get_pdf(direction)
+    }
+
+    /// Returns radiance of the sky without the sun and intensity
+    fn get_sky_radiance(&self, direction: &Vector3) -> Colour {
+        let direction = direction.normalize();
+        if direction.y <= 0.0 {
+            return Colour::default();
+        }
+        let cos_gamma = direction.dot(&self.sun_direction).clamp(-1.0, 1.0);
+        let cos_sun_theta = self.sun_direction.y;
+        let [luminance, x, y] = [0, 1, 2].map(|i| {
+            let perez = |cos_theta: Scalar, cos_gamma: Scalar| {
+                let [a, b, c, d, e] = self.perez[i];
+                (1.0 + a * (b / cos_theta.max(1e-3)).exp())
+                    * (1.0 + c * (d * cos_gamma.acos()).exp() + e * cos_gamma * cos_gamma)
+            };
+            self.zenith[i] * perez(direction.y, cos_gamma) / perez(1.0, cos_sun_theta)
+        });
+        // CIE xyY to linear sRGB
+        let (big_x, big_z) = (x / y * luminance, (1.0 - x - y) / y * luminance);
+        Colour {
+            red: (3.2406 * big_x - 1.5372 * luminance - 0.4986 * big_z).max(0.0),
+            green: (-0.9689 * big_x + 1.8758 * luminance + 0.0415 * big_z).max(0.0),
+            blue: (0.0557 * big_x - 0.2040 * luminance + 1.0570 * big_z).max(0.0),
+        }
+    }
+
+    fn is_in_sun(&self, direction: &Vector3) -> bool {
+        direction.normalize().dot(&self.sun_direction) >= SUN_RADIUS.cos()
+    }
+
+    /// Maps point of the unit square uniformly to the cone of the sun disk
+    fn sample_sun(&self, u: &Point2) -> Vector3 {
+        // 1 - cos θ is computed without cancellation for the tiny disk
+        let one_minus_cos = u.x * 2.0 * (SUN_RADIUS / 2.0).sin().powi(2);
+        let sin_theta = (one_minus_cos * (2.0 - one_minus_cos)).sqrt();
+        let phi = 2.0 * std::f32::consts::PI * u.y;
+        let local = Vector3::new(
+            sin_theta * phi.cos(),
+            sin_theta * phi.sin(),
+            1.0 - one_minus_cos,
+        );
+        Frame::from_normal(&self.sun_direction).to_world(&local)
+    }
+}
+
+/// Solid angle of the sun disk
+fn get_sun_solid_angle() -> Scalar {
+    4.0 * std::f32::consts::PI * (SUN_RADIUS / 2.0).sin().powi(2)
+}
+
+/// Returns radiance of the sun at zenith angle `theta` attenuated by Rayleigh
+/// scattering and by aerosols according to Ångström's formula
+fn get_sun_radiance(theta: Scalar, turbidity: Scalar) -> Colour {
+    // Relative optical mass of the atmosphere (Kasten and Young, 1989)
+    let mass = 1.0 / (theta.cos() + 0.50572 * (96.07995 - theta.to_degrees()).powf(-1.6364));
+    let beta = 0.04608 * turbidity - 0.04586;
+    let [red, green, blue] = WAVELENGTHS.map(|lambda| {
+        let rayleigh = 0.008735 * lambda.powf(-4.08);
+        let aerosol = beta * lambda.powf(-1.3);
+        (-(rayleigh + aerosol) * mass).exp()
+    });
+    Colour { red, green, blue } * (SOLAR_ILLUMINANCE / get_sun_solid_angle())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    fn direction(elevation: Scalar, azimuth: Scalar) -> Vector3 {
+        Vector3::new(
+            elevation.cos() * azimuth.sin(),
+            elevation.sin(),
+            -elevation.cos() * azimuth.cos(),
+        )
+    }
+
+    #[test]
+    fn sky_is_bright_near_the_sun_and_black_below_horizon() {
+        let sky = Sky::new(0.5, 1.0, 3.0).with_intensity(0.5);
+        assert!((sky.get_sun_direction().into_inner() - direction(0.5, 1.0)).norm() < 1e-6);
+        assert_eq!(sky.get_radiance(&direction(-0.1, 1.0)), Colour::default());
+        let near_sun = sky.get_radiance(&direction(0.6, 1.0)).luminance();
+        let opposite = sky.get_radiance(&direction(0.6, 1.0 + std::f32::consts::PI));
+        assert!(near_sun > 2.0 * opposite.luminance());
+        // Clear sky is blue away from the sun
+        assert!(opposite.blue > opposite.red);
+        // Zenith luminance follows Preetham's formula scaled by intensity
+        // Sun zenith angle θ = π/2 - 0.5 gives π - 2θ = 1
+        let chi: Scalar = 4.0 / 9.0 - 3.0 / 120.0;
+        let zenith = 0.5 * ((4.0453 * 3.0 - 4.9710) * chi.tan() - 0.2155 * 3.0 + 2.4192);
+        let luminance = sky.get_radiance(&Vector3::y()).luminance();
+        assert!(
+            (luminance - zenith).abs() < 0.02 * zenith,
+            "{} {}",
+            luminance,
+            zenith
+        );
+    }
+
+    #[test]
+    fn sun_disk_is_seen_only_within_its_radius() {
+        let sky = Sky::new(0.3, -0.5, 2.5);
+        let sun = sky.get_radiance(sky.get_sun_direction());
+        assert_eq!(
+            sun,
+            sky.get_sun_radiance() + sky.get_sky_radiance(sky.get_sun_direction())
+        );
+        assert!(sun.luminance() > 1e4 * sky.get_radiance(&Vector3::y()).luminance());
+        let beside = direction(0.3 + 2.0 * SUN_RADIUS, -0.5);
+        assert!(sky.get_radiance(&beside).luminance() < 1e-3 * sun.luminance());
+        let inside = direction(0.3 + 0.5 * SUN_RADIUS, -0.5);
+        assert!(sky.get_radiance(&inside).luminance() > 0.5 * sun.luminance());
+    }
+
+    #[test]
+    fn sun_is_reddened_and_dimmed_by_atmosphere() {
+        let noon = Sky::new(1.4, 0.0, 2.0).get_sun_radiance();
+        let sunset = Sky::new(0.05, 0.0, 2.0).get_sun_radiance();
+        let hazy = Sky::new(1.4, 0.0, 8.0).get_sun_radiance();
+        assert!(sunset.red / sunset.blue > noon.red / noon.blue);
+        assert!(sunset.luminance() < noon.luminance());
+        assert!(hazy.luminance() < noon.luminance());
+        // Clear noon sun gives about 100 klx
+        let illuminance = noon.luminance() * get_sun_solid_angle();
+        assert!((80.0..120.0).contains(&illuminance), "{}", illuminance);
+    }
+
+    #[test]
+    fn samples_estimate_illuminance_of_the_ground() {
+        let sky = Sky::new(0.4, 2.0, 4.0);
+        // Sky by midpoint rule over the upper hemisphere and the sun as a point
+        let (columns, rows) = (400, 200);
+        let mut expected =
+            sky.get_sun_radiance().luminance() * get_sun_solid_angle() * sky.get_sun_direction().y;
+        for i in 0..columns * rows {
+            let elevation =
+                std::f32::consts::FRAC_PI_2 * ((i / columns) as Scalar + 0.5) / rows as Scalar;
+            let azimuth =
+                2.0 * std::f32::consts::PI * ((i % columns) as Scalar + 0.5) / columns as Scalar;
+            let solid_angle = elevation.cos() * std::f32::consts::FRAC_PI_2 / rows as Scalar
+                * 2.0
+                * std::f32::consts::PI
+                / columns as Scalar;
+            let direction = direction(elevation, azimuth);
+            expected += sky.get_sky_radiance(&direction).luminance() * direction.y * solid_angle;
+        }
+        let mut rng = StdRng::seed_from_u64(0);
+        let count = 100_000;
+        let mut estimate = 0.0;
+        for _ in 0..count {
+            let sample = sky.sample(&Point2::new(rng.gen(), rng.gen())).unwrap();
+            let pdf = sky.get_pdf(&sample.direction);
+            assert!(
+                (sample.pdf - pdf).abs() <= 1e-3 * pdf,
+                "{} {}",
+                sample.pdf,
+                pdf
+            );
+            assert_eq!(sample.radiance, sky.get_radiance(&sample.direction));
+            estimate += sample.radiance.luminance() * sample.direction.y.max(0.0) / sample.pdf;
+        }
+        estimate /= count as Scalar;
+        assert!(
+            (estimate - expected).abs() < 0.02 * expected,
+            "{} {}",
+            estimate,
+            expected
+        );
+    }
+}
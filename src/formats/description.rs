@@ -0,0 +1,1591 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::marker::PhantomData;
+use std::path::{Path, PathBuf};
+
+use serde::{
+    de::{
+        value::{MapAccessDeserializer, SeqAccessDeserializer},
+        Error as _, MapAccess, SeqAccess, Visitor,
+    },
+    Deserialize, Deserializer, Serialize, Serializer,
+};
+
+use crate::{
+    formats::{load_obj, ObjError},
+    primitives::{Mesh, Primitive, Sphere, Triangle},
+    Colour, Conductor, Dielectric, DirectionalLight, Environment, EnvironmentMap, HaltonSampler,
+    Isometry3, Lambertian, Light, Lobe, Material, Point3, PointLight, Principled, RayTraceable,
+    Rotation3, Scalar, Scene, Similarity3, Sky, SobolSampler, SpotLight, StratifiedSampler,
+    Transform3, Translation3, UniformSampler, Vector3, Viewport,
+};
+
+/// Error raised while reading, validating or writing scene description
+#[derive(Debug)]
+pub enum SceneFileError {
+    /// File could not be read or written
+    Io {
+        path: PathBuf,
+        error: std::io::Error,
+    },
+    /// File is not a valid TOML or does not match description structure
+    Parse(toml::de::Error),
+    /// Description could not be written as TOML
+    Write(toml::ser::Error),
+    /// Entry of the description has invalid value
+    Invalid { entry: String, message: String },
+    /// OBJ file referred by description could not be loaded
+    Obj { entry: String, error: ObjError },
+    /// Image file referred by description could not be loaded
+    Image {
+        entry: String,
+        error: image::ImageError,
+    },
+}
+
+/// Colour written as `[red, green, blue]`
+pub type ColourDescription = [Scalar; 3];
+
+/// Material written as table with `diffuse` and `emission` colours
+/// and optional `dielectric`, `conductor` and `principled` lobes.
+/// Each kind of lobe is written as a table or as an array of tables.
+#[derive(Debug, PartialEq, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MaterialDescription {
+    pub diffuse: ColourDescription,
+    pub emission: ColourDescription,
+    #[serde(
+        skip_serializing_if = "Vec::is_empty",
+        serialize_with = "serialize_one_or_many",
+        deserialize_with = "deserialize_one_or_many"
+    )]
+    pub dielectric: Vec<DielectricDescription>,
+    #[serde(
+        skip_serializing_if = "Vec::is_empty",
+        serialize_with = "serialize_one_or_many",
+        deserialize_with = "deserialize_one_or_many"
+    )]
+    pub conductor: Vec<ConductorDescription>,
+    #[serde(
+        skip_serializing_if = "Vec::is_empty",
+        serialize_with = "serialize_one_or_many",
+        deserialize_with = "deserialize_one_or_many"
+    )]
+    pub principled: Vec<PrincipledDescription>,
+}
+
+/// Transparent surface with index of refraction `ior`, which is colourless
+/// and smooth unless `tint` and `roughness` are given
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DielectricDescription {
+    pub ior: Scalar,
+    #[serde(default = "DielectricDescription::default_tint")]
+    pub tint: ColourDescription,
+    #[serde(default)]
+    pub roughness: Scalar,
+    #[serde(default)]
+    pub anisotropy: Scalar,
+}
+
+/// Metal given by name of `metal` (gold, silver, copper or aluminium) or by complex index
+/// of refraction `eta + i k`, whose surface is smooth unless `roughness` is given.
+/// `anisotropy` stretches reflections like on brushed metal.
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ConductorDescription {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metal: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub eta: Option<ColourDescription>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub k: Option<ColourDescription>,
+    #[serde(default)]
+    pub roughness: Scalar,
+    #[serde(default)]
+    pub anisotropy: Scalar,
+}
+
+/// Principled material, whose parameters other than colours are between 0 and 1.
+/// Omitted parameters give rough grey plastic. `emission` multiplied by
+/// `emission_strength` is added to emission of the material, not to the lobe.
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PrincipledDescription {
+    pub base_colour: ColourDescription,
+    pub metallic: Scalar,
+    pub roughness: Scalar,
+    pub specular: Scalar,
+    pub specular_tint: Scalar,
+    pub clearcoat: Scalar,
+    pub sheen: Scalar,
+    pub transmission: Scalar,
+    pub emission: ColourDescription,
+    pub emission_strength: Scalar,
+}
+
+/// Camera placed at `eye` looking at `target`.
+/// Vertical field of view is given in degrees.
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CameraDescription {
+    pub eye: [Scalar; 3],
+    pub target: [Scalar; 3],
+    #[serde(default = "CameraDescription::default_up")]
+    pub up: [Scalar; 3],
+    pub fov: Scalar,
+    pub near: Scalar,
+    pub far: Scalar,
+}
+
+/// Image resolution and number of rays cast through every pixel
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ViewportDescription {
+    pub width: u32,
+    pub height: u32,
+    pub samples: usize,
+    #[serde(default)]
+    pub sampler: SamplerDescription,
+}
+
+/// Sampler spreading rays over pixels
+#[derive(Debug, PartialEq, Eq, Copy, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SamplerDescription {
+    Uniform,
+    /// Jittered stratified sampling
+    #[default]
+    Stratified,
+    Halton,
+    Sobol,
+}
+
+/// Transform applied to primitive: scaling, then rotation by Euler angles
+/// (roll, pitch, yaw) in degrees and finally translation
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TransformDescription {
+    pub translation: [Scalar; 3],
+    pub rotation: [Scalar; 3],
+    pub scale: Scalar,
+}
+
+/// Geometry of the primitive, distinguished by its `type`
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase", deny_unknown_fields)]
+pub enum ShapeDescription {
+    Triangle {
+        vertices: [[Scalar; 3]; 3],
+    },
+    Sphere {
+        center: [Scalar; 3],
+        radius: Scalar,
+    },
+    Mesh {
+        vertices: Vec<[Scalar; 3]>,
+        faces: Vec<[u32; 3]>,
+    },
+    /// Wavefront OBJ file with path relative to the scene description file
+    Obj {
+        path: PathBuf,
+    },
+}
+
+/// Primitive with optional name of material and transform
+#[derive(Debug, PartialEq, Clone, Serialize)]
+pub struct PrimitiveDescription {
+    #[serde(flatten)]
+    pub shape: ShapeDescription,
+    /// Name of material from `materials`. Default material is used if it is not given.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub material: Option<String>,
+    #[serde(default, skip_serializing_if = "TransformDescription::is_identity")]
+    pub transform: TransformDescription,
+}
+
+/// Punctual light source distinguished by its `type`.
+/// Cone angles of spot lights are given in degrees.
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase", deny_unknown_fields)]
+pub enum LightSourceDescription {
+    Point {
+        position: [Scalar; 3],
+    },
+    Spot {
+        position: [Scalar; 3],
+        direction: [Scalar; 3],
+        inner_angle: Scalar,
+        outer_angle: Scalar,
+    },
+    Directional {
+        direction: [Scalar; 3],
+    },
+}
+
+/// Punctual light with colour, white by default, and intensity
+#[derive(Debug, PartialEq, Clone, Serialize)]
+pub struct LightDescription {
+    #[serde(flatten)]
+    pub source: LightSourceDescription,
+    pub colour: ColourDescription,
+    pub intensity: Scalar,
+}
+
+/// Light arriving from infinity distinguished by its `type`. Angles are given in degrees.
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum EnvironmentSourceDescription {
+    /// Equirectangular Radiance HDR image with path relative to the scene
+    /// description file, rotated by Euler angles (roll, pitch, yaw)
+    Map {
+        path: PathBuf,
+        #[serde(default)]
+        rotation: [Scalar; 3],
+    },
+    /// Daylight sky with the sun at given elevation and azimuth
+    Sky {
+        elevation: Scalar,
+        #[serde(default)]
+        azimuth: Scalar,
+        turbidity: Scalar,
+    },
+}
+
+/// Environment with intensity, which is one by default
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct EnvironmentDescription {
+    #[serde(flatten)]
+    pub source: EnvironmentSourceDescription,
+    #[serde(default = "EnvironmentDescription::default_intensity")]
+    pub intensity: Scalar,
+}
+
+/// Description of the whole rendering setup, which can be read from and written to TOML
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SceneDescription {
+    /// Seed of random number generators used while rendering
+    #[serde(default)]
+    pub seed: u64,
+    pub recursion_depth: usize,
+    pub beam_rays_count: usize,
+    pub camera: CameraDescription,
+    pub viewport: ViewportDescription,
+    #[serde(default)]
+    pub default_material: MaterialDescription,
+    /// Environment map or sky replacing emission of the default material
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub environment: Option<EnvironmentDescription>,
+    #[serde(default)]
+    pub materials: BTreeMap<String, MaterialDescription>,
+    #[serde(default)]
+    pub primitives: Vec<PrimitiveDescription>,
+    #[serde(default)]
+    pub lights: Vec<LightDescription>,
+}
+
+/// Scene with viewport and camera transform created from description
+#[derive(Debug, Clone)]
+pub struct LoadedScene {
+    pub scene: Scene,
+    pub viewport: Viewport,
+    /// Transform from camera space to world space
+    pub camera: Isometry3,
+    pub seed: u64,
+}
+
+impl<'de> Deserialize<'de> for PrimitiveDescription {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        // Shape shares the table with material and transform. They are taken out first,
+        // so that the shape can reject the remaining unknown keys.
+        let mut table = toml::value::Table::deserialize(deserializer)?;
+        let material = match table.remove("material") {
+            Some(material) => Some(material.try_into().map_err(D::Error::custom)?),
+            None => None,
+        };
+        let transform = match table.remove("transform") {
+            Some(transform) => transform.try_into().map_err(D::Error::custom)?,
+            None => TransformDescription::default(),
+        };
+        let shape = toml::Value::Table(table)
+            .try_into()
+            .map_err(D::Error::custom)?;
+        Ok(Self {
+            shape,
+            material,
+            transform,
+        })
+    }
+}
+
+impl<'de> Deserialize<'de> for LightDescription {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        // Light source shares the table with colour and intensity, which are taken
+        // out first like in `PrimitiveDescription`
+        let mut table = toml::value::Table::deserialize(deserializer)?;
+        let colour = match table.remove("colour") {
+            Some(colour) => colour.try_into().map_err(D::Error::custom)?,
+            None => Self::default_colour(),
+        };
+        let intensity = table
+            .remove("intensity")
+            .ok_or_else(|| D::Error::missing_field("intensity"))?
+            .try_into()
+            .map_err(D::Error::custom)?;
+        let source = toml::Value::Table(table)
+            .try_into()
+            .map_err(D::Error::custom)?;
+        Ok(Self {
+            source,
+            colour,
+            intensity,
+        })
+    }
+}
+
+impl SceneFileError {
+    fn invalid(entry: impl Into<String>, message: impl Into<String>) -> Self {
+        SceneFileError::Invalid {
+            entry: entry.into(),
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for SceneFileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SceneFileError::Io { path, error } => write!(f, "{}: {}", path.display(), error),
+            SceneFileError::Parse(error) => write!(f, "{}", error),
+            SceneFileError::Write(error) => write!(f, "{}", error),
+            SceneFileError::Invalid { entry, message } => write!(f, "{}: {}", entry, message),
+            SceneFileError::Obj { entry, error } => write!(f, "{}: {}", entry, error),
+            SceneFileError::Image { entry, error } => write!(f, "{}: {}", entry, error),
+        }
+    }
+}
+
+impl std::error::Error for SceneFileError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            SceneFileError::Io { error, .. } => Some(error),
+            SceneFileError::Parse(error) => Some(error),
+            SceneFileError::Write(error) => Some(error),
+            SceneFileError::Invalid { .. } => None,
+            SceneFileError::Obj { error, .. } => Some(error),
+            SceneFileError::Image { error, .. } => Some(error),
+        }
+    }
+}
+
+impl MaterialDescription {
+    fn build(&self, entry: &str) -> Result<Material, SceneFileError> {
+        let diffuse = build_colour(&self.diffuse, &format!("{}.diffuse", entry))?;
+        let emission = build_colour(&self.emission, &format!("{}.emission", entry))?;
+        let mut material = Material::default().with_emission(emission);
+        if diffuse != Colour::default() {
+            material = material.with_lobe(Lambertian::new(diffuse));
+        }
+        for (entry, dielectric) in lobe_entries(entry, "dielectric", &self.dielectric) {
+            material = material.with_lobe(dielectric.build(&entry)?);
+        }
+        for (entry, conductor) in lobe_entries(entry, "conductor", &self.conductor) {
+            material = material.with_lobe(conductor.build(&entry)?);
+        }
+        for (entry, principled) in lobe_entries(entry, "principled", &self.principled) {
+            material.emission += principled.build_emission(&entry)?;
+            material = material.with_lobe(principled.build(&entry)?);
+        }
+        Ok(material)
+    }
+}
+
+/// Pairs lobes of one kind with their entries, which are indexed only if there are more of them
+fn lobe_entries<'a, T>(
+    entry: &str,
+    kind: &str,
+    lobes: &'a [T],
+) -> impl Iterator<Item = (String, &'a T)> {
+    let entry = format!("{}.{}", entry, kind);
+    let indexed = lobes.len() > 1;
+    lobes.iter().enumerate().map(move |(index, lobe)| {
+        if indexed {
+            (format!("{}[{}]", entry, index), lobe)
+        } else {
+            (entry.clone(), lobe)
+        }
+    })
+}
+
+impl From<&Material> for MaterialDescription {
+    fn from(material: &Material) -> Self {
+        let colour = |c: &Colour| [c.red, c.green, c.blue];
+        let mut diffuse = Colour::default();
+        let mut dielectric = Vec::new();
+        let mut conductor = Vec::new();
+        let mut principled = Vec::new();
+        for lobe in &material.lobes {
+            match lobe {
+                Lobe::Lambertian(lambertian) => diffuse += lambertian.albedo,
+                Lobe::Dielectric(d) => dielectric.push(DielectricDescription {
+                    ior: d.ior,
+                    tint: colour(&d.tint),
+                    roughness: d.roughness,
+                    anisotropy: d.anisotropy,
+                }),
+                Lobe::Conductor(c) => conductor.push(ConductorDescription {
+                    metal: None,
+                    eta: Some(colour(&c.eta)),
+                    k: Some(colour(&c.k)),
+                    roughness: c.roughness,
+                    anisotropy: c.anisotropy,
+                }),
+                // Emission is written once, as emission of the material
+                Lobe::Principled(p) => principled.push(PrincipledDescription {
+                    base_colour: colour(&p.base_colour),
+                    metallic: p.metallic,
+                    roughness: p.roughness,
+                    specular: p.specular,
+                    specular_tint: p.specular_tint,
+                    clearcoat: p.clearcoat,
+                    sheen: p.sheen,
+                    transmission: p.transmission,
+                    ..Default::default()
+                }),
+            }
+        }
+        Self {
+            diffuse: colour(&diffuse),
+            emission: colour(&material.emission),
+            dielectric,
+            conductor,
+            principled,
+        }
+    }
+}
+
+impl DielectricDescription {
+    fn default_tint() -> ColourDescription {
+        [1.0, 1.0, 1.0]
+    }
+
+    fn build(&self, entry: &str) -> Result<Dielectric, SceneFileError> {
+        if !(self.ior > 0.0 && self.ior.is_finite()) {
+            return Err(SceneFileError::invalid(
+                format!("{}.ior", entry),
+                "index of refraction has to be positive",
+            ));
+        }
+        let tint = build_colour(&self.tint, &format!("{}.tint", entry))?;
+        validate_microfacets(self.roughness, self.anisotropy, entry)?;
+        Ok(Dielectric::new(self.ior)
+            .with_tint(tint)
+            .with_roughness(self.roughness)
+            .with_anisotropy(self.anisotropy))
+    }
+}
+
+impl ConductorDescription {
+    fn build(&self, entry: &str) -> Result<Conductor, SceneFileError> {
+        let conductor = match (&self.metal, self.eta, self.k) {
+            (Some(metal), None, None) => match metal.as_str() {
+                "gold" => Conductor::gold(),
+                "silver" => Conductor::silver(),
+                "copper" => Conductor::copper(),
+                "aluminium" => Conductor::aluminium(),
+                _ => {
+                    return Err(SceneFileError::invalid(
+                        format!("{}.metal", entry),
+                        "metal has to be gold, silver, copper or aluminium",
+                    ))
+                }
+            },
+            (None, Some(eta), Some(k)) => Conductor::new(
+                build_colour(&eta, &format!("{}.eta", entry))?,
+                build_colour(&k, &format!("{}.k", entry))?,
+            ),
+            _ => {
+                return Err(SceneFileError::invalid(
+                    entry,
+                    "conductor needs either metal or both eta and k",
+                ))
+            }
+        };
+        validate_microfacets(self.roughness, self.anisotropy, entry)?;
+        Ok(conductor
+            .with_roughness(self.roughness)
+            .with_anisotropy(self.anisotropy))
+    }
+}
+
+impl Default for PrincipledDescription {
+    fn default() -> Self {
+        let principled = Principled::default();
+        let colour = |c: &Colour| [c.red, c.green, c.blue];
+        Self {
+            base_colour: colour(&principled.base_colour),
+            metallic: principled.metallic,
+            roughness: principled.roughness,
+            specular: principled.specular,
+            specular_tint: principled.specular_tint,
+            clearcoat: principled.clearcoat,
+            sheen: principled.sheen,
+            transmission: principled.transmission,
+            emission: [0.0, 0.0, 0.0],
+            emission_strength: 1.0,
+        }
+    }
+}
+
+impl PrincipledDescription {
+    fn build(&self, entry: &str) -> Result<Principled, SceneFileError> {
+        let base_colour = build_colour(&self.base_colour, &format!("{}.base_colour", entry))?;
+        let parameters = [
+            ("metallic", self.metallic),
+            ("roughness", self.roughness),
+            ("specular", self.specular),
+            ("specular_tint", self.specular_tint),
+            ("clearcoat", self.clearcoat),
+            ("sheen", self.sheen),
+            ("transmission", self.transmission),
+        ];
+        for (name, value) in &parameters {
+            if !(0.0..=1.0).contains(value) {
+                return Err(SceneFileError::invalid(
+                    format!("{}.{}", entry, name),
+                    format!("{} has to be between 0 and 1", name),
+                ));
+            }
+        }
+        Ok(Principled::new(base_colour)
+            .with_metallic(self.metallic)
+            .with_roughness(self.roughness)
+            .with_specular(self.specular)
+            .with_specular_tint(self.specular_tint)
+            .with_clearcoat(self.clearcoat)
+            .with_sheen(self.sheen)
+            .with_transmission(self.transmission))
+    }
+
+    /// Returns emission scaled by its strength
+    fn build_emission(&self, entry: &str) -> Result<Colour, SceneFileError> {
+        let emission = build_colour(&self.emission, &format!("{}.emission", entry))?;
+        if !(self.emission_strength >= 0.0 && self.emission_strength.is_finite()) {
+            return Err(SceneFileError::invalid(
+                format!("{}.emission_strength", entry),
+                "emission strength has to be non-negative",
+            ));
+        }
+        Ok(emission * self.emission_strength)
+    }
+}
+
+impl CameraDescription {
+    fn default_up() -> [Scalar; 3] {
+        [0.0, 1.0, 0.0]
+    }
+
+    fn validate(&self) -> Result<(), SceneFileError> {
+        let eye = Point3::from(self.eye);
+        let target = Point3::from(self.target);
+        let up = Vector3::from(self.up);
+        if !(self.fov > 0.0 && self.fov < 180.0) {
+            return Err(SceneFileError::invalid(
+                "camera.fov",
+                "field of view has to be between 0 and 180 degrees",
+            ));
+        }
+        if !(self.near > 0.0 && self.near.is_finite()) {
+            return Err(SceneFileError::invalid(
+                "camera.near",
+                "near plane has to be positive",
+            ));
+        }
+        if !(self.far > self.near && self.far.is_finite()) {
+            return Err(SceneFileError::invalid(
+                "camera.far",
+                "far plane has to be farther than near plane",
+            ));
+        }
+        if !(eye - target).iter().all(|c| c.is_finite()) || eye == target {
+            return Err(SceneFileError::invalid(
+                "camera.target",
+                "target has to differ from eye",
+            ));
+        }
+        if up.cross(&(target - eye)).norm() <= Scalar::EPSILON {
+            return Err(SceneFileError::invalid(
+                "camera.up",
+                "up has to be non-zero and not parallel to view direction",
+            ));
+        }
+        Ok(())
+    }
+
+    /// Returns transform from camera space to world space
+    pub fn get_transform(&self) -> Isometry3 {
+        Isometry3::look_at_rh(
+            &Point3::from(self.eye),
+            &Point3::from(self.target),
+            &Vector3::from(self.up),
+        )
+        .inverse()
+    }
+}
+
+impl SamplerDescription {
+    /// Sets described sampler in viewport
+    fn apply_to(self, viewport: Viewport) -> Viewport {
+        match self {
+            SamplerDescription::Uniform => viewport.with_sampler(UniformSampler),
+            SamplerDescription::Stratified => viewport.with_sampler(StratifiedSampler::new(true)),
+            SamplerDescription::Halton => viewport.with_sampler(HaltonSampler),
+            SamplerDescription::Sobol => viewport.with_sampler(SobolSampler),
+        }
+    }
+}
+
+impl ViewportDescription {
+    fn validate(&self) -> Result<(), SceneFileError> {
+        if self.width == 0 {
+            return Err(SceneFileError::invalid(
+                "viewport.width",
+                "width has to be positive",
+            ));
+        }
+        if self.height == 0 {
+            return Err(SceneFileError::invalid(
+                "viewport.height",
+                "height has to be positive",
+            ));
+        }
+        if self.samples == 0 {
+            return Err(SceneFileError::invalid(
+                "viewport.samples",
+                "at least one sample per pixel is required",
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl TransformDescription {
+    pub fn is_identity(&self) -> bool {
+        *self == Self::default()
+    }
+
+    fn build(&self, entry: &str) -> Result<Similarity3, SceneFileError> {
+        let finite = |v: &[Scalar]| v.iter().all(|c| c.is_finite());
+        if !finite(&self.translation) || !finite(&self.rotation) {
+            return Err(SceneFileError::invalid(
+                format!("{}.transform", entry),
+                "translation and rotation have to be finite",
+            ));
+        }
+        if !(self.scale > 0.0 && self.scale.is_finite()) {
+            return Err(SceneFileError::invalid(
+                format!("{}.transform.scale", entry),
+                "scale has to be positive",
+            ));
+        }
+        let [roll, pitch, yaw] = self.rotation;
+        let rotation =
+            Rotation3::from_euler_angles(roll.to_radians(), pitch.to_radians(), yaw.to_radians());
+        Ok(Similarity3::from_parts(
+            Translation3::from(Vector3::from(self.translation)),
+            rotation.into(),
+            self.scale,
+        ))
+    }
+}
+
+impl Default for TransformDescription {
+    fn default() -> Self {
+        Self {
+            translation: [0.0; 3],
+            rotation: [0.0; 3],
+            scale: 1.0,
+        }
+    }
+}
+
+impl ShapeDescription {
+    /// Creates primitives of the shape. OBJ files may contain many meshes.
+    fn build(
+        &self,
+        entry: &str,
+        base: &Path,
+        material: Material,
+        transform: &Similarity3,
+    ) -> Result<Vec<Primitive>, SceneFileError> {
+        match self {
+            ShapeDescription::Triangle { vertices } => {
+                let [a, b, c] = vertices;
+                let triangle =
+                    Triangle::new([Point3::from(*a), Point3::from(*b), Point3::from(*c)]);
+                if triangle.get_size() <= 0.0 || !triangle.get_size().is_finite() {
+                    return Err(SceneFileError::invalid(
+                        format!("{}.vertices", entry),
+                        "triangle is degenerate",
+                    ));
+                }
+                Ok(vec![(transform * triangle).into()])
+            }
+            ShapeDescription::Sphere { center, radius } => {
+                if !(*radius > 0.0 && radius.is_finite()) {
+                    return Err(SceneFileError::invalid(
+                        format!("{}.radius", entry),
+                        "radius has to be positive",
+                    ));
+                }
+                let transform = Transform3::from_matrix_unchecked(transform.to_homogeneous());
+                Sphere::new(Point3::from(*center), *radius)
+                    .try_transform(&transform)
+                    .map(|sphere| vec![sphere.into()])
+                    .ok_or_else(|| {
+                        SceneFileError::invalid(
+                            format!("{}.transform", entry),
+                            "sphere can be scaled only uniformly",
+                        )
+                    })
+            }
+            ShapeDescription::Mesh { vertices, faces } => {
+                if faces.is_empty() {
+                    return Err(SceneFileError::invalid(
+                        format!("{}.faces", entry),
+                        "mesh needs at least one face",
+                    ));
+                }
+                if let Some((index, _)) = faces
+                    .iter()
+                    .enumerate()
+                    .find(|(_, face)| face.iter().any(|&i| i as usize >= vertices.len()))
+                {
+                    return Err(SceneFileError::invalid(
+                        format!("{}.faces[{}]", entry, index),
+                        format!("vertex index out of range 0..{}", vertices.len()),
+                    ));
+                }
+                let vertices = vertices.iter().map(|v| Point3::from(*v)).collect();
+                Ok(vec![(transform * Mesh::new(vertices, faces.clone())).into()])
+            }
+            ShapeDescription::Obj { path } => load_obj(base.join(path), material)
+                .map(|groups| {
+                    groups
+                        .into_iter()
+                        .map(|group| (transform * group.mesh).into())
+                        .collect()
+                })
+                .map_err(|error| SceneFileError::Obj {
+                    entry: format!("{}.path", entry),
+                    error,
+                }),
+        }
+    }
+}
+
+impl LightDescription {
+    fn default_colour() -> ColourDescription {
+        [1.0; 3]
+    }
+
+    fn build(&self, entry: &str) -> Result<Light, SceneFileError> {
+        let colour = build_colour(&self.colour, &format!("{}.colour", entry))?;
+        if !(self.intensity >= 0.0 && self.intensity.is_finite()) {
+            return Err(SceneFileError::invalid(
+                format!("{}.intensity", entry),
+                "intensity has to be non-negative",
+            ));
+        }
+        let position = |position: &[Scalar; 3]| {
+            if position.iter().all(|c| c.is_finite()) {
+                Ok(Point3::from(*position))
+            } else {
+                Err(SceneFileError::invalid(
+                    format!("{}.position", entry),
+                    "position has to be finite",
+                ))
+            }
+        };
+        let direction = |direction: &[Scalar; 3]| {
+            let direction = Vector3::from(*direction);
+            if direction.norm() > 0.0 && direction.norm().is_finite() {
+                Ok(direction)
+            } else {
+                Err(SceneFileError::invalid(
+                    format!("{}.direction", entry),
+                    "direction has to be non-zero",
+                ))
+            }
+        };
+        Ok(match &self.source {
+            LightSourceDescription::Point { position: p } => {
+                PointLight::new(position(p)?, colour, self.intensity).into()
+            }
+            LightSourceDescription::Spot {
+                position: p,
+                direction: d,
+                inner_angle,
+                outer_angle,
+            } => {
+                if !(0.0 <= *inner_angle && inner_angle <= outer_angle && *outer_angle <= 180.0) {
+                    return Err(SceneFileError::invalid(
+                        format!("{}.outer_angle", entry),
+                        "cone angles have to satisfy 0 <= inner_angle <= outer_angle <= 180",
+                    ));
+                }
+                SpotLight::new(
+                    position(p)?,
+                    direction(d)?,
+                    inner_angle.to_radians(),
+                    outer_angle.to_radians(),
+                    colour,
+                    self.intensity,
+                )
+                .into()
+            }
+            LightSourceDescription::Directional { direction: d } => {
+                DirectionalLight::new(direction(d)?, colour, self.intensity).into()
+            }
+        })
+    }
+}
+
+impl EnvironmentDescription {
+    fn default_intensity() -> Scalar {
+        1.0
+    }
+
+    fn build(&self, base: &Path) -> Result<Environment, SceneFileError> {
+        if !(self.intensity >= 0.0 && self.intensity.is_finite()) {
+            return Err(SceneFileError::invalid(
+                "environment.intensity",
+                "intensity has to be non-negative",
+            ));
+        }
+        Ok(match &self.source {
+            EnvironmentSourceDescription::Map { path, rotation } => {
+                if !rotation.iter().all(|c| c.is_finite()) {
+                    return Err(SceneFileError::invalid(
+                        "environment.rotation",
+                        "rotation has to be finite",
+                    ));
+                }
+                let [roll, pitch, yaw] = *rotation;
+                let rotation = Rotation3::from_euler_angles(
+                    roll.to_radians(),
+                    pitch.to_radians(),
+                    yaw.to_radians(),
+                );
+                EnvironmentMap::load(base.join(path))
+                    .map_err(|error| SceneFileError::Image {
+                        entry: "environment.path".to_string(),
+                        error,
+                    })?
+                    .with_rotation(rotation)
+                    .with_intensity(self.intensity)
+                    .into()
+            }
+            EnvironmentSourceDescription::Sky {
+                elevation,
+                azimuth,
+                turbidity,
+            } => {
+                if !(0.0..=90.0).contains(elevation) {
+                    return Err(SceneFileError::invalid(
+                        "environment.elevation",
+                        "sun elevation has to be between 0 and 90",
+                    ));
+                }
+                if !azimuth.is_finite() {
+                    return Err(SceneFileError::invalid(
+                        "environment.azimuth",
+                        "sun azimuth has to be finite",
+                    ));
+                }
+                if !(1.7..=10.0).contains(turbidity) {
+                    return Err(SceneFileError::invalid(
+                        "environment.turbidity",
+                        "turbidity has to be between 1.7 and 10",
+                    ));
+                }
+                Sky::new(elevation.to_radians(), azimuth.to_radians(), *turbidity)
+                    .with_intensity(self.intensity)
+                    .into()
+            }
+        })
+    }
+}
+
+impl SceneDescription {
+    /// Parses description from TOML
+    pub fn from_toml(content: &str) -> Result<Self, SceneFileError> {
+        toml::from_str(content).map_err(SceneFileError::Parse)
+    }
+
+    /// Writes description as TOML
+    pub fn to_toml(&self) -> Result<String, SceneFileError> {
+        toml::to_string_pretty(self).map_err(SceneFileError::Write)
+    }
+
+    /// Reads description from TOML file
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, SceneFileError> {
+        let path = path.as_ref();
+        let content = std::fs::read_to_string(path).map_err(|error| SceneFileError::Io {
+            path: path.to_path_buf(),
+            error,
+        })?;
+        Self::from_toml(&content)
+    }
+
+    /// Writes description to TOML file
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), SceneFileError> {
+        let path = path.as_ref();
+        std::fs::write(path, self.to_toml()?).map_err(|error| SceneFileError::Io {
+            path: path.to_path_buf(),
+            error,
+        })
+    }
+
+    /// Validates description and creates scene, viewport and camera.
+    /// Paths of OBJ and environment files are resolved relative to `base` directory.
+    pub fn build(&self, base: impl AsRef<Path>) -> Result<LoadedScene, SceneFileError> {
+        let base = base.as_ref();
+        self.camera.validate()?;
+        self.viewport.validate()?;
+        let default_material = self.default_material.build("default_material")?;
+        let materials = self
+            .materials
+            .iter()
+            .map(|(name, material)| {
+                material
+                    .build(&format!("materials.{}", name))
+                    .map(|m| (name.as_str(), m))
+            })
+            .collect::<Result<BTreeMap<_, _>, _>>()?;
+
+        let mut scene = Scene::new(
+            default_material.clone(),
+            self.recursion_depth,
+            self.beam_rays_count,
+        );
+        if let Some(environment) = &self.environment {
+            scene.set_environment(environment.build(base)?);
+        }
+        for (index, primitive) in self.primitives.iter().enumerate() {
+            let entry = format!("primitives[{}]", index);
+            let material = match &primitive.material {
+                None => &default_material,
+                Some(name) => materials.get(name.as_str()).ok_or_else(|| {
+                    SceneFileError::invalid(
+                        format!("{}.material", entry),
+                        format!("unknown material '{}'", name),
+                    )
+                })?,
+            };
+            let transform = primitive.transform.build(&entry)?;
+            for shape in primitive
+                .shape
+                .build(&entry, base, material.clone(), &transform)?
+            {
+                scene.add(shape, material.clone());
+            }
+        }
+        for (index, light) in self.lights.iter().enumerate() {
+            scene.add_light(light.build(&format!("lights[{}]", index))?);
+        }
+
+        Ok(LoadedScene {
+            scene,
+            viewport: self.viewport.sampler.apply_to(Viewport::new(
+                self.viewport.width,
+                self.viewport.height,
+                self.camera.fov.to_radians(),
+                self.camera.near,
+                self.camera.far,
+                self.viewport.samples,
+            )),
+            camera: self.camera.get_transform(),
+            seed: self.seed,
+        })
+    }
+}
+
+/// Reads scene description file and creates scene, viewport and camera from it.
+/// Paths of OBJ and environment files are resolved relative to the description file.
+pub fn load_scene(path: impl AsRef<Path>) -> Result<LoadedScene, SceneFileError> {
+    let path = path.as_ref();
+    let base = path.parent().unwrap_or_else(|| Path::new(""));
+    SceneDescription::load(path)?.build(base)
+}
+
+fn serialize_one_or_many<T: Serialize, S: Serializer>(
+    values: &[T],
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    match values {
+        [value] => value.serialize(serializer),
+        _ => values.serialize(serializer),
+    }
+}
+
+/// Reads single table or array of tables. Errors inside the tables are reported
+/// as they are, instead of only saying that neither form matched.
+fn deserialize_one_or_many<'de, T: Deserialize<'de>, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Vec<T>, D::Error> {
+    struct OneOrMany<T>(PhantomData<T>);
+
+    impl<'de, T: Deserialize<'de>> Visitor<'de> for OneOrMany<T> {
+        type Value = Vec<T>;
+
+        fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+            formatter.write_str("a table or an array of tables")
+        }
+
+        fn visit_map<A: MapAccess<'de>>(self, map: A) -> Result<Self::Value, A::Error> {
+            T::deserialize(MapAccessDeserializer::new(map)).map(|value| vec![value])
+        }
+
+        fn visit_seq<A: SeqAccess<'de>>(self, seq: A) -> Result<Self::Value, A::Error> {
+            Vec::deserialize(SeqAccessDeserializer::new(seq))
+        }
+    }
+
+    deserializer.deserialize_any(OneOrMany(PhantomData))
+}
+
+fn validate_microfacets(
+    roughness: Scalar,
+    anisotropy: Scalar,
+    entry: &str,
+) -> Result<(), SceneFileError> {
+    if !(0.0..=1.0).contains(&roughness) {
+        return Err(SceneFileError::invalid(
+            format!("{}.roughness", entry),
+            "roughness has to be between 0 and 1",
+        ));
+    }
+    if !(0.0..=1.0).contains(&anisotropy) {
+        return Err(SceneFileError::invalid(
+            format!("{}.anisotropy", entry),
+            "anisotropy has to be between 0 and 1",
+        ));
+    }
+    Ok(())
+}
+
+fn build_colour(colour: &ColourDescription, entry: &str) -> Result<Colour, SceneFileError> {
+    if !colour.iter().all(|c| *c >= 0.0 && c.is_finite()) {
+        return Err(SceneFileError::invalid(
+            entry,
+            "colour components have to be non-negative",
+        ));
+    }
+    let [red, green, blue] = *colour;
+    Ok(Colour { red, green, blue })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Ray;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    const SCENE: &str = r#"
+        seed = 11
+        recursion_depth = 2
+        beam_rays_count = 3
+
+        [camera]
+        eye = [0, 0, 5]
+        target = [0, 0, 0]
+        fov = 90
+        near = 1
+        far = 1000
+
+        [viewport]
+        width = 80
+        height = 60
+        samples = 4
+        sampler = "sobol"
+
+        [default_material]
+        diffuse = [0.5, 0.5, 0.5]
+
+        [materials.lamp]
+        diffuse = [1, 1, 0]
+        emission = [1, 1, 0]
+
+        [materials.red]
+        diffuse = [1, 0, 0]
+
+        [[primitives]]
+        type = "triangle"
+        vertices = [[1, 0, 0], [0, 1, 0], [-1, 0, 0]]
+        material = "lamp"
+
+        [[primitives]]
+        type = "sphere"
+        center = [0, 0, 0]
+        radius = 1
+        material = "red"
+        transform = { translation = [0, 0, -10], scale = 2 }
+
+        [[primitives]]
+        type = "mesh"
+        vertices = [[0, 0, 0], [1, 0, 0], [1, 1, 0], [0, 1, 0]]
+        faces = [[0, 1, 2], [0, 2, 3]]
+        transform = { rotation = [0, 90, 0] }
+
+        [[lights]]
+        type = "point"
+        position = [0, 5, 0]
+        intensity = 10
+
+        [[lights]]
+        type = "spot"
+        position = [0, 5, 0]
+        direction = [0, -1, 0]
+        inner_angle = 15
+        outer_angle = 30
+        colour = [1, 0.5, 0]
+        intensity = 20
+
+        [[lights]]
+        type = "directional"
+        direction = [1, -1, 0]
+        intensity = 2
+    "#;
+
+    fn invalid_entry(content: &str) -> String {
+        match SceneDescription::from_toml(content).and_then(|d| d.build("")) {
+            Err(SceneFileError::Invalid { entry, .. }) => entry,
+            other => panic!("expected validation error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn description_is_parsed() {
+        let description = SceneDescription::from_toml(SCENE).unwrap();
+        assert_eq!(description.seed, 11);
+        assert_eq!(description.recursion_depth, 2);
+        assert_eq!(description.camera.up, [0.0, 1.0, 0.0]);
+        assert_eq!(description.viewport.samples, 4);
+        assert_eq!(description.viewport.sampler, SamplerDescription::Sobol);
+        assert_eq!(description.materials.len(), 2);
+        assert_eq!(description.primitives.len(), 3);
+        assert_eq!(
+            description.primitives[1].shape,
+            ShapeDescription::Sphere {
+                center: [0.0, 0.0, 0.0],
+                radius: 1.0
+            }
+        );
+        assert_eq!(description.primitives[1].transform.scale, 2.0);
+        assert_eq!(description.primitives[2].material, None);
+        assert_eq!(
+            description.primitives[2].transform.rotation,
+            [0.0, 90.0, 0.0]
+        );
+        assert_eq!(description.lights.len(), 3);
+        assert_eq!(description.lights[0].colour, [1.0, 1.0, 1.0]);
+        assert_eq!(
+            description.lights[1].source,
+            LightSourceDescription::Spot {
+                position: [0.0, 5.0, 0.0],
+                direction: [0.0, -1.0, 0.0],
+                inner_angle: 15.0,
+                outer_angle: 30.0,
+            }
+        );
+    }
+
+    #[test]
+    fn example_scenes_are_valid() {
+        let description =
+            SceneDescription::from_toml(include_str!("../../scenes/triangles.toml")).unwrap();
+        assert_eq!(description.primitives.len(), 4);
+        assert!(description.build("").is_ok());
+        let description =
+            SceneDescription::from_toml(include_str!("../../scenes/outdoor.toml")).unwrap();
+        assert!(description.build("").is_ok());
+    }
+
+    #[test]
+    fn description_round_trips_through_toml() {
+        let description = SceneDescription::from_toml(SCENE).unwrap();
+        let written = description.to_toml().unwrap();
+        assert_eq!(SceneDescription::from_toml(&written).unwrap(), description);
+    }
+
+    #[test]
+    fn description_builds_scene() {
+        let content = SCENE.replace("recursion_depth = 2", "recursion_depth = 0");
+        let loaded = SceneDescription::from_toml(&content)
+            .unwrap()
+            .build("")
+            .unwrap();
+        assert_eq!(loaded.viewport.get_width(), 80.0);
+        assert_eq!(loaded.viewport.get_rays_count(), 4);
+        assert_eq!(loaded.camera * Point3::origin(), Point3::new(0.0, 0.0, 5.0));
+
+        let lamp = Ray::new(Point3::new(0.0, 0.5, 5.0), -Vector3::z());
+        #[rustfmt::skip]
+        assert_eq!(loaded.scene.trace(&lamp, &mut StdRng::seed_from_u64(0)), Colour {red: 1.0, green: 1.0, blue: 0.0});
+        let sky = Ray::new(Point3::new(0.0, 0.5, 5.0), Vector3::z());
+        #[rustfmt::skip]
+        assert_eq!(loaded.scene.trace(&sky, &mut StdRng::seed_from_u64(0)), Colour {red: 0.5, green: 0.5, blue: 0.5});
+        // Transformed sphere has radius 2 and is centered at z = -10,
+        // its red material reflects only red part of light from the lamps
+        let sphere = Ray::new(Point3::new(1.9, 0.0, 0.0), -Vector3::z());
+        let colour = loaded.scene.trace(&sphere, &mut StdRng::seed_from_u64(0));
+        assert!(colour.red > 0.0 && colour.green == 0.0 && colour.blue == 0.0);
+        let beside_sphere = Ray::new(Point3::new(2.1, 0.0, 0.0), -Vector3::z());
+        #[rustfmt::skip]
+        assert_eq!(loaded.scene.trace(&beside_sphere, &mut StdRng::seed_from_u64(0)), Colour {red: 0.5, green: 0.5, blue: 0.5});
+
+        let lights = loaded.scene.get_lights();
+        assert_eq!(lights.len(), 3);
+        assert_eq!(
+            lights[0],
+            PointLight::new(
+                Point3::new(0.0, 5.0, 0.0),
+                Colour {
+                    red: 1.0,
+                    green: 1.0,
+                    blue: 1.0
+                },
+                10.0
+            )
+            .into()
+        );
+        match lights[1] {
+            Light::Spot(spot) => {
+                assert!((spot.outer_angle - std::f32::consts::FRAC_PI_6).abs() < 1e-6);
+                assert_eq!(spot.colour.green, 0.5);
+            }
+            other => panic!("expected spot light, got {:?}", other),
+        }
+        assert!(matches!(lights[2], Light::Directional(_)));
+    }
+
+    #[test]
+    fn parse_errors_contain_line() {
+        let content = SCENE.replace("radius = 1", "radius = \"big\"");
+        let error = SceneDescription::from_toml(&content).unwrap_err();
+        assert!(matches!(error, SceneFileError::Parse(_)));
+        let error =
+            SceneDescription::from_toml("recursion_depth = 1\nbeam_rays_count = \n").unwrap_err();
+        assert!(error.to_string().contains("line 2"), "{}", error);
+    }
+
+    #[test]
+    fn unknown_primitive_keys_are_rejected() {
+        for (key, misspelled) in [
+            ("radius = 1", "radius = 1\n        radus = 2"),
+            ("material = \"red\"", "materail = \"red\""),
+            ("scale = 2", "scael = 2"),
+        ] {
+            let content = SCENE.replace(key, misspelled);
+            let error = SceneDescription::from_toml(&content).unwrap_err();
+            assert!(matches!(error, SceneFileError::Parse(_)), "{:?}", error);
+            assert!(error.to_string().contains("unknown field"), "{}", error);
+        }
+    }
+
+    #[test]
+    fn unknown_light_keys_are_rejected() {
+        for (key, misspelled) in [
+            (
+                "intensity = 10",
+                "intensity = 10\n        colur = [1, 0, 0]",
+            ),
+            ("inner_angle = 15", "iner_angle = 15"),
+        ] {
+            let content = SCENE.replace(key, misspelled);
+            let error = SceneDescription::from_toml(&content).unwrap_err();
+            assert!(matches!(error, SceneFileError::Parse(_)), "{:?}", error);
+            assert!(error.to_string().contains("unknown field"), "{}", error);
+        }
+        let content = SCENE.replace("intensity = 2\n", "");
+        let error = SceneDescription::from_toml(&content).unwrap_err();
+        assert!(error.to_string().contains("intensity"), "{}", error);
+    }
+
+    #[test]
+    fn validation_errors_point_at_entry() {
+        assert_eq!(
+            invalid_entry(&SCENE.replace("material = \"red\"", "material = \"blue\"")),
+            "primitives[1].material"
+        );
+        assert_eq!(
+            invalid_entry(&SCENE.replace("radius = 1", "radius = -1")),
+            "primitives[1].radius"
+        );
+        assert_eq!(
+            invalid_entry(&SCENE.replace("[0, 2, 3]]", "[0, 2, 4]]")),
+            "primitives[2].faces[1]"
+        );
+        assert_eq!(
+            invalid_entry(&SCENE.replace("scale = 2", "scale = 0")),
+            "primitives[1].transform.scale"
+        );
+        assert_eq!(
+            invalid_entry(&SCENE.replace("diffuse = [1, 0, 0]", "diffuse = [-1, 0, 0]")),
+            "materials.red.diffuse"
+        );
+        assert_eq!(
+            invalid_entry(&SCENE.replace("fov = 90", "fov = 180")),
+            "camera.fov"
+        );
+        assert_eq!(
+            invalid_entry(&SCENE.replace("far = 1000", "far = 1")),
+            "camera.far"
+        );
+        assert_eq!(
+            invalid_entry(&SCENE.replace("target = [0, 0, 0]", "target = [0, 0, 5]")),
+            "camera.target"
+        );
+        assert_eq!(
+            invalid_entry(&SCENE.replace("samples = 4", "samples = 0")),
+            "viewport.samples"
+        );
+        assert_eq!(
+            invalid_entry(&SCENE.replace("intensity = 10", "intensity = -10")),
+            "lights[0].intensity"
+        );
+        assert_eq!(
+            invalid_entry(&SCENE.replace("outer_angle = 30", "outer_angle = 10")),
+            "lights[1].outer_angle"
+        );
+        assert_eq!(
+            invalid_entry(&SCENE.replace("direction = [1, -1, 0]", "direction = [0, 0, 0]")),
+            "lights[2].direction"
+        );
+    }
+
+    #[test]
+    fn environment_is_loaded_relative_to_base() {
+        let base = std::env::temp_dir().join(format!("rustracer-scene-{}", std::process::id()));
+        std::fs::create_dir_all(&base).unwrap();
+        let pixels = vec![image::Rgb([0.5f32, 1.0, 2.0]); 8];
+        image::hdr::HDREncoder::new(std::fs::File::create(base.join("sky.hdr")).unwrap())
+            .encode(&pixels, 4, 2)
+            .unwrap();
+        let content = SCENE.replace(
+            "[default_material]",
+            "[environment]\ntype = \"map\"\npath = \"sky.hdr\"\nrotation = [0, 90, 0]\nintensity = 2\n\n[default_material]",
+        );
+        let description = SceneDescription::from_toml(&content).unwrap();
+        let written = description.to_toml().unwrap();
+        assert_eq!(SceneDescription::from_toml(&written).unwrap(), description);
+        let loaded = description.build(&base);
+        std::fs::remove_dir_all(&base).unwrap();
+        let environment = loaded.unwrap().scene.get_environment().unwrap().clone();
+        #[rustfmt::skip]
+        assert_eq!(environment.get_radiance(&Vector3::z()), Colour {red: 1.0, green: 2.0, blue: 4.0});
+
+        let error = description.build("").unwrap_err();
+        assert!(
+            matches!(&error, SceneFileError::Image { entry, .. } if entry == "environment.path")
+        );
+        assert_eq!(
+            invalid_entry(&content.replace("intensity = 2\n", "intensity = -2\n")),
+            "environment.intensity"
+        );
+    }
+
+    #[test]
+    fn sky_is_built_from_sun_position() {
+        let sky = "[environment]\ntype = \"sky\"\nelevation = 30\nazimuth = 90\nturbidity = 3\n\n";
+        let content = SCENE.replace("[default_material]", &format!("{}[default_material]", sky));
+        let description = SceneDescription::from_toml(&content).unwrap();
+        let written = description.to_toml().unwrap();
+        assert_eq!(SceneDescription::from_toml(&written).unwrap(), description);
+        let loaded = description.build("").unwrap();
+        match loaded.scene.get_environment() {
+            Some(Environment::Sky(sky)) => {
+                let expected = Vector3::new(0.75_f32.sqrt(), 0.5, 0.0);
+                assert!((sky.get_sun_direction().into_inner() - expected).norm() < 1e-6);
+                assert_eq!(sky.get_turbidity(), 3.0);
+                assert_eq!(sky.get_intensity(), 1.0);
+            }
+            other => panic!("expected sky, got {:?}", other),
+        }
+        assert_eq!(
+            invalid_entry(&content.replace("elevation = 30", "elevation = 100")),
+            "environment.elevation"
+        );
+        assert_eq!(
+            invalid_entry(&content.replace("turbidity = 3", "turbidity = 1")),
+            "environment.turbidity"
+        );
+    }
+
+    #[test]
+    fn dielectric_materials_are_built() {
+        let glass = "[materials.glass]\ndielectric = { ior = 1.5, tint = [1, 0.5, 1], roughness = 0.2 }\n\n";
+        let content = SCENE.replace("[materials.red]", &format!("{}[materials.red]", glass));
+        let description = SceneDescription::from_toml(&content).unwrap();
+        let written = description.to_toml().unwrap();
+        assert_eq!(SceneDescription::from_toml(&written).unwrap(), description);
+        let material = description.materials["glass"]
+            .build("materials.glass")
+            .unwrap();
+        #[rustfmt::skip]
+        let tint = Colour {red: 1.0, green: 0.5, blue: 1.0};
+        let expected = Dielectric::new(1.5).with_tint(tint).with_roughness(0.2);
+        assert_eq!(material.lobes, vec![expected.into()]);
+        assert_eq!(
+            MaterialDescription::from(&material),
+            description.materials["glass"]
+        );
+        assert_eq!(
+            invalid_entry(&content.replace("ior = 1.5", "ior = 0")),
+            "materials.glass.dielectric.ior"
+        );
+        assert_eq!(
+            invalid_entry(&content.replace("tint = [1, 0.5, 1]", "tint = [1, -0.5, 1]")),
+            "materials.glass.dielectric.tint"
+        );
+    }
+
+    #[test]
+    fn conductor_materials_are_built() {
+        let gold = "[materials.gold]\nconductor = { metal = \"gold\", roughness = 0.3, anisotropy = 0.5 }\n\n";
+        let content = SCENE.replace("[materials.red]", &format!("{}[materials.red]", gold));
+        let description = SceneDescription::from_toml(&content).unwrap();
+        let written = description.to_toml().unwrap();
+        assert_eq!(SceneDescription::from_toml(&written).unwrap(), description);
+        let material = description.materials["gold"]
+            .build("materials.gold")
+            .unwrap();
+        let expected = Conductor::gold().with_roughness(0.3).with_anisotropy(0.5);
+        assert_eq!(material.lobes, vec![expected.into()]);
+        // Metals are written by their index of refraction
+        let written = MaterialDescription::from(&material).conductor.remove(0);
+        assert_eq!(written.build("").unwrap(), expected);
+        assert_eq!(
+            invalid_entry(&content.replace("\"gold\"", "\"brass\"")),
+            "materials.gold.conductor.metal"
+        );
+        assert_eq!(
+            invalid_entry(&content.replace("metal = \"gold\"", "eta = [1, 1, 1]")),
+            "materials.gold.conductor"
+        );
+        assert_eq!(
+            invalid_entry(&content.replace("0.3", "1.5")),
+            "materials.gold.conductor.roughness"
+        );
+        assert_eq!(
+            invalid_entry(&content.replace("0.5 }", "-0.5 }")),
+            "materials.gold.conductor.anisotropy"
+        );
+    }
+
+    #[test]
+    fn principled_materials_are_built() {
+        let paint = "[materials.paint]\nprincipled = { base_colour = [0.9, 0.2, 0.1], metallic = 0.5, clearcoat = 0.7, emission = [1, 1, 0.5], emission_strength = 4 }\n\n";
+        let content = SCENE.replace("[materials.red]", &format!("{}[materials.red]", paint));
+        let description = SceneDescription::from_toml(&content).unwrap();
+        let written = description.to_toml().unwrap();
+        assert_eq!(SceneDescription::from_toml(&written).unwrap(), description);
+        let material = description.materials["paint"]
+            .build("materials.paint")
+            .unwrap();
+        let colour = |red, green, blue| Colour { red, green, blue };
+        let expected = Material::default()
+            .with_emission(colour(4.0, 4.0, 2.0))
+            .with_lobe(
+                Principled::new(colour(0.9, 0.2, 0.1))
+                    .with_metallic(0.5)
+                    .with_clearcoat(0.7),
+            );
+        assert_eq!(material, expected);
+        // Emission scaled by its strength belongs to the material, not to the lobe
+        let written = MaterialDescription::from(&material);
+        assert_eq!(written.emission, [4.0, 4.0, 2.0]);
+        let rebuilt = written.build("").unwrap();
+        assert_eq!(rebuilt.emission, material.emission);
+        assert_eq!(rebuilt.lobes.len(), 1);
+        assert_eq!(
+            invalid_entry(&content.replace("0.7", "1.7")),
+            "materials.paint.principled.clearcoat"
+        );
+        assert_eq!(
+            invalid_entry(&content.replace("= 4 }", "= -4 }")),
+            "materials.paint.principled.emission_strength"
+        );
+    }
+
+    #[test]
+    fn lobes_of_the_same_kind_are_kept() {
+        let metals = "[materials.metals]\nconductor = [{ metal = \"gold\", roughness = 0.3 }, { metal = \"silver\" }]\n\n";
+        let content = SCENE.replace("[materials.red]", &format!("{}[materials.red]", metals));
+        let description = SceneDescription::from_toml(&content).unwrap();
+        let written = description.to_toml().unwrap();
+        assert_eq!(SceneDescription::from_toml(&written).unwrap(), description);
+        let material = description.materials["metals"]
+            .build("materials.metals")
+            .unwrap();
+        let expected = Material::default()
+            .with_lobe(Conductor::gold().with_roughness(0.3))
+            .with_lobe(Conductor::silver());
+        assert_eq!(material, expected);
+        // Both lobes are written and read back
+        let written = MaterialDescription::from(&material);
+        assert_eq!(written.conductor.len(), 2);
+        let mut scene = SceneDescription::from_toml(&content).unwrap();
+        scene.materials.insert("metals".to_string(), written);
+        let read = SceneDescription::from_toml(&scene.to_toml().unwrap()).unwrap();
+        assert_eq!(read.materials["metals"].build("").unwrap(), material);
+        assert_eq!(
+            invalid_entry(&content.replace("0.3", "1.3")),
+            "materials.metals.conductor[0].roughness"
+        );
+    }
+
+    #[test]
+    fn errors_inside_lobes_are_reported() {
+        for lobes in [
+            "{ metal = \"gold\", rougness = 0.3 }",
+            "[{ metal = \"gold\" }, { metal = \"silver\", rougness = 0.3 }]",
+        ] {
+            let metals = format!("[materials.metals]\nconductor = {}\n\n", lobes);
+            let content = SCENE.replace("[materials.red]", &format!("{}[materials.red]", metals));
+            let error = SceneDescription::from_toml(&content).unwrap_err();
+            assert!(
+                error.to_string().contains("unknown field `rougness`"),
+                "{}",
+                error
+            );
+        }
+        let content = SCENE.replace(
+            "[materials.red]",
+            "[materials.metals]\nconductor = 1\n\n[materials.red]",
+        );
+        let error = SceneDescription::from_toml(&content).unwrap_err();
+        assert!(
+            error.to_string().contains("a table or an array of tables"),
+            "{}",
+            error
+        );
+    }
+
+    #[test]
+    fn obj_errors_point_at_entry() {
+        let content = format!(
+            "{}\n[[primitives]]\ntype = \"obj\"\npath = \"missing.obj\"\n",
+            SCENE
+        );
+        let error = SceneDescription::from_toml(&content)
+            .unwrap()
+            .build("")
+            .unwrap_err();
+        assert!(
+            matches!(&error, SceneFileError::Obj { entry, .. } if entry == "primitives[3].path")
+        );
+    }
+}
@@ -0,0 +1,14 @@
+mod obj;
+pub use obj::{
+    load_obj, load_obj_into, parse_mtl, parse_obj, MaterialLibrary, ObjError, ObjErrorKind,
+    ObjGroup,
+};
+
+mod description;
+pub use description::{
+    load_scene, CameraDescription, ColourDescription, ConductorDescription, DielectricDescription,
+    EnvironmentDescription, EnvironmentSourceDescription, LightDescription, LightSourceDescription,
+    LoadedScene, MaterialDescription, PrimitiveDescription, PrincipledDescription,
+    SamplerDescription, SceneDescription, SceneFileError, ShapeDescription, TransformDescription,
+    ViewportDescription,
+};
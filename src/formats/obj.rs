@@ -0,0 +1,679 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
+
+use crate::{
+    primitives::Mesh, Colour, Lambertian, Material, Point2, Point3, Scalar, Scene, Vector3,
+};
+
+/// Kind of error raised while reading Wavefront OBJ or MTL file
+#[derive(Debug)]
+pub enum ObjErrorKind {
+    /// File could not be read
+    Io(std::io::Error),
+    /// File content is malformed
+    Syntax(String),
+}
+
+/// Error raised while reading Wavefront OBJ or MTL file
+#[derive(Debug)]
+pub struct ObjError {
+    /// File, in which error occurred, if known
+    pub path: Option<PathBuf>,
+    /// Line number counted from 1, or 0 if error is not related to any line
+    pub line: usize,
+    pub kind: ObjErrorKind,
+}
+
+/// Group of faces read from OBJ file
+#[derive(Debug, PartialEq, Clone)]
+pub struct ObjGroup {
+    /// Name given by `g` or `o` statement
+    pub name: String,
+    pub mesh: Mesh,
+}
+
+/// Materials read from MTL file by their names
+pub type MaterialLibrary = HashMap<String, Material>;
+
+impl ObjError {
+    fn syntax(line: usize, message: String) -> Self {
+        Self {
+            path: None,
+            line,
+            kind: ObjErrorKind::Syntax(message),
+        }
+    }
+
+    fn io(line: usize, error: std::io::Error) -> Self {
+        Self {
+            path: None,
+            line,
+            kind: ObjErrorKind::Io(error),
+        }
+    }
+
+    /// Sets line of the statement, which caused the error, if it is not known yet
+    fn at_line(mut self, line: usize) -> Self {
+        if self.line == 0 {
+            self.line = line;
+        }
+        self
+    }
+
+    /// Sets path of the file, if it is not known yet
+    fn in_file(mut self, path: &Path) -> Self {
+        if self.path.is_none() {
+            self.path = Some(path.to_path_buf());
+        }
+        self
+    }
+}
+
+impl fmt::Display for ObjError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(path) = &self.path {
+            write!(f, "{}:", path.display())?;
+        }
+        if self.line > 0 {
+            write!(f, "{}:", self.line)?;
+        }
+        if self.path.is_some() || self.line > 0 {
+            write!(f, " ")?;
+        }
+        match &self.kind {
+            ObjErrorKind::Io(error) => write!(f, "{}", error),
+            ObjErrorKind::Syntax(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl std::error::Error for ObjError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match &self.kind {
+            ObjErrorKind::Io(error) => Some(error),
+            ObjErrorKind::Syntax(_) => None,
+        }
+    }
+}
+
+/// Reads OBJ file and materials from MTL files it refers to and adds all
+/// groups as meshes to the scene. `material` is used for faces without `usemtl`.
+pub fn load_obj_into(
+    scene: &mut Scene,
+    path: impl AsRef<Path>,
+    material: Material,
+) -> Result<(), ObjError> {
+    for group in load_obj(path, material.clone())? {
+        scene.add(group.mesh, material.clone());
+    }
+    Ok(())
+}
+
+/// Reads OBJ file and materials from MTL files it refers to.
+/// MTL paths are resolved relative to the OBJ file directory.
+/// `default_material` is used for faces without `usemtl` in groups, which use materials.
+pub fn load_obj(
+    path: impl AsRef<Path>,
+    default_material: Material,
+) -> Result<Vec<ObjGroup>, ObjError> {
+    let path = path.as_ref();
+    let directory = path.parent().unwrap_or_else(|| Path::new(""));
+    let file = File::open(path).map_err(|e| ObjError::io(0, e).in_file(path))?;
+    parse_obj(BufReader::new(file), default_material, |name| {
+        let mtl_path = directory.join(name);
+        // Missing library is reported at the `mtllib` statement of the OBJ file
+        let file = File::open(&mtl_path).map_err(|e| {
+            let message = format!("cannot open {}: {}", mtl_path.display(), e);
+            ObjError::io(0, std::io::Error::new(e.kind(), message))
+        })?;
+        parse_mtl(BufReader::new(file)).map_err(|e| e.in_file(&mtl_path))
+    })
+    .map_err(|e| e.in_file(path))
+}
+
+/// Parses MTL file content. Diffuse colour is read from `Kd`
+/// and emission from `Ke` statements, other statements are ignored.
+pub fn parse_mtl<R: BufRead>(reader: R) -> Result<MaterialLibrary, ObjError> {
+    let mut library = MaterialLibrary::new();
+    let mut current: Option<String> = None;
+    for (index, line) in reader.lines().enumerate() {
+        let line_number = index + 1;
+        let line = line.map_err(|e| ObjError::io(line_number, e))?;
+        let (keyword, args) = match split_statement(&line) {
+            Some(statement) => statement,
+            None => continue,
+        };
+        match keyword {
+            "newmtl" => {
+                let name = args.join(" ");
+                if name.is_empty() {
+                    return Err(ObjError::syntax(
+                        line_number,
+                        "missing material name".into(),
+                    ));
+                }
+                library.insert(name.clone(), Material::default());
+                current = Some(name);
+            }
+            "Kd" | "Ke" => {
+                let material = current
+                    .as_ref()
+                    .and_then(|name| library.get_mut(name))
+                    .ok_or_else(|| {
+                        ObjError::syntax(line_number, format!("{} before newmtl", keyword))
+                    })?;
+                let colour = parse_colour(&args, line_number)?;
+                if keyword == "Kd" {
+                    material.lobes = vec![Lambertian::new(colour).into()];
+                } else {
+                    material.emission = colour;
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(library)
+}
+
+/// Parses OBJ file content. Supports positions, normals, texture coordinates,
+/// polygonal faces, which are triangulated, groups and materials.
+/// `load_library` is called with the name of every MTL file referred by `mtllib`.
+pub fn parse_obj<R, F>(
+    reader: R,
+    default_material: Material,
+    mut load_library: F,
+) -> Result<Vec<ObjGroup>, ObjError>
+where
+    R: BufRead,
+    F: FnMut(&str) -> Result<MaterialLibrary, ObjError>,
+{
+    let mut positions: Vec<Point3> = Vec::new();
+    let mut normals: Vec<Vector3> = Vec::new();
+    let mut uvs: Vec<Point2> = Vec::new();
+    let mut library = MaterialLibrary::new();
+    let mut current_material: Option<(String, Material)> = None;
+    let mut groups: Vec<ObjGroup> = Vec::new();
+    let mut group = GroupBuilder::new("default".into());
+
+    for (index, line) in reader.lines().enumerate() {
+        let line_number = index + 1;
+        let line = line.map_err(|e| ObjError::io(line_number, e))?;
+        let (keyword, args) = match split_statement(&line) {
+            Some(statement) => statement,
+            None => continue,
+        };
+        match keyword {
+            "v" => positions.push(Point3::from(parse_vector(&args, line_number)?)),
+            "vn" => normals.push(parse_vector(&args, line_number)?),
+            "vt" => uvs.push(parse_uv(&args, line_number)?),
+            "f" => {
+                if args.len() < 3 {
+                    return Err(ObjError::syntax(
+                        line_number,
+                        "face needs at least 3 vertices".into(),
+                    ));
+                }
+                let corners = args
+                    .iter()
+                    .map(|corner| {
+                        parse_corner(corner, &positions, &normals, &uvs, line_number)
+                            .map(|c| group.add_vertex(c, &positions, &normals, &uvs))
+                    })
+                    .collect::<Result<Vec<u32>, ObjError>>()?;
+                let material = current_material
+                    .as_ref()
+                    .map(|(name, material)| group.material_index(name, material));
+                // Polygons are triangulated as a fan around the first vertex
+                for i in 1..corners.len() - 1 {
+                    group.faces.push([corners[0], corners[i], corners[i + 1]]);
+                    group.face_materials.push(material);
+                }
+            }
+            "g" | "o" => {
+                let name = args.join(" ");
+                let previous = std::mem::replace(&mut group, GroupBuilder::new(name));
+                groups.extend(previous.build(default_material.clone()));
+            }
+            "usemtl" => {
+                let name = args.join(" ");
+                let material = library.get(&name).ok_or_else(|| {
+                    ObjError::syntax(line_number, format!("unknown material '{}'", name))
+                })?;
+                current_material = Some((name, material.clone()));
+            }
+            "mtllib" => {
+                for name in args {
+                    library.extend(load_library(name).map_err(|e| e.at_line(line_number))?);
+                }
+            }
+            _ => {}
+        }
+    }
+    groups.extend(group.build(default_material));
+    Ok(groups)
+}
+
+/// Splits line into keyword and arguments. Returns None for empty lines and comments.
+fn split_statement(line: &str) -> Option<(&str, Vec<&str>)> {
+    let content = line.split('#').next().unwrap_or("");
+    let mut tokens = content.split_whitespace();
+    let keyword = tokens.next()?;
+    Some((keyword, tokens.collect()))
+}
+
+fn parse_scalars(args: &[&str], line: usize) -> Result<Vec<Scalar>, ObjError> {
+    args.iter()
+        .map(|arg| {
+            arg.parse::<Scalar>()
+                .map_err(|_| ObjError::syntax(line, format!("invalid number '{}'", arg)))
+        })
+        .collect()
+}
+
+fn parse_vector(args: &[&str], line: usize) -> Result<Vector3, ObjError> {
+    match parse_scalars(args, line)?.as_slice() {
+        // Optional fourth coordinate is a weight used by rational curves
+        [x, y, z] | [x, y, z, _] => Ok(Vector3::new(*x, *y, *z)),
+        _ => Err(ObjError::syntax(line, "expected 3 coordinates".into())),
+    }
+}
+
+fn parse_uv(args: &[&str], line: usize) -> Result<Point2, ObjError> {
+    match parse_scalars(args, line)?.as_slice() {
+        [u] => Ok(Point2::new(*u, 0.0)),
+        [u, v] | [u, v, _] => Ok(Point2::new(*u, *v)),
+        _ => Err(ObjError::syntax(
+            line,
+            "expected 1 to 3 texture coordinates".into(),
+        )),
+    }
+}
+
+fn parse_colour(args: &[&str], line: usize) -> Result<Colour, ObjError> {
+    match parse_scalars(args, line)?.as_slice() {
+        [c] => Ok(Colour {
+            red: *c,
+            green: *c,
+            blue: *c,
+        }),
+        [red, green, blue] => Ok(Colour {
+            red: *red,
+            green: *green,
+            blue: *blue,
+        }),
+        _ => Err(ObjError::syntax(line, "expected RGB colour".into())),
+    }
+}
+
+/// Indices of position, texture coordinates and normal of face corner
+type Corner = (usize, Option<usize>, Option<usize>);
+
+/// Parses `v`, `v/vt`, `v//vn` or `v/vt/vn` face corner
+fn parse_corner(
+    corner: &str,
+    positions: &[Point3],
+    normals: &[Vector3],
+    uvs: &[Point2],
+    line: usize,
+) -> Result<Corner, ObjError> {
+    let mut parts = corner.split('/');
+    let position = parse_index(parts.next().unwrap_or(""), positions.len(), line)?;
+    let uv = match parts.next() {
+        None | Some("") => None,
+        Some(index) => Some(parse_index(index, uvs.len(), line)?),
+    };
+    let normal = match parts.next() {
+        None | Some("") => None,
+        Some(index) => Some(parse_index(index, normals.len(), line)?),
+    };
+    if parts.next().is_some() {
+        return Err(ObjError::syntax(
+            line,
+            format!("invalid face vertex '{}'", corner),
+        ));
+    }
+    Ok((position, uv, normal))
+}
+
+/// Resolves 1-based or negative, relative OBJ index
+fn parse_index(index: &str, count: usize, line: usize) -> Result<usize, ObjError> {
+    let value = index
+        .parse::<i64>()
+        .map_err(|_| ObjError::syntax(line, format!("invalid index '{}'", index)))?;
+    let resolved = if value > 0 {
+        value - 1
+    } else {
+        count as i64 + value
+    };
+    if value == 0 || resolved < 0 || resolved >= count as i64 {
+        return Err(ObjError::syntax(
+            line,
+            format!("index {} out of range", value),
+        ));
+    }
+    Ok(resolved as usize)
+}
+
+/// Helper struct collecting faces of one group.
+/// Vertices are deduplicated by their position, normal and uv indices.
+struct GroupBuilder {
+    name: String,
+    corners: HashMap<Corner, u32>,
+    vertices: Vec<Point3>,
+    normals: Vec<Option<Vector3>>,
+    uvs: Vec<Option<Point2>>,
+    faces: Vec<[u32; 3]>,
+    material_names: HashMap<String, u32>,
+    materials: Vec<Material>,
+    face_materials: Vec<Option<u32>>,
+}
+
+impl GroupBuilder {
+    fn new(name: String) -> Self {
+        Self {
+            name,
+            corners: HashMap::new(),
+            vertices: Vec::new(),
+            normals: Vec::new(),
+            uvs: Vec::new(),
+            faces: Vec::new(),
+            material_names: HashMap::new(),
+            materials: Vec::new(),
+            face_materials: Vec::new(),
+        }
+    }
+
+    fn add_vertex(
+        &mut self,
+        corner: Corner,
+        positions: &[Point3],
+        normals: &[Vector3],
+        uvs: &[Point2],
+    ) -> u32 {
+        let vertices = &mut self.vertices;
+        let group_normals = &mut self.normals;
+        let group_uvs = &mut self.uvs;
+        *self.corners.entry(corner).or_insert_with(|| {
+            let (position, uv, normal) = corner;
+            vertices.push(positions[position]);
+            group_normals.push(normal.map(|i| normals[i]));
+            group_uvs.push(uv.map(|i| uvs[i]));
+            (vertices.len() - 1) as u32
+        })
+    }
+
+    fn material_index(&mut self, name: &str, material: &Material) -> u32 {
+        let materials = &mut self.materials;
+        *self
+            .material_names
+            .entry(name.to_string())
+            .or_insert_with(|| {
+                materials.push(material.clone());
+                (materials.len() - 1) as u32
+            })
+    }
+
+    /// Creates group mesh. Normals and uvs are used only if all vertices have them.
+    fn build(mut self, default_material: Material) -> Option<ObjGroup> {
+        if self.faces.is_empty() {
+            return None;
+        }
+        let normals: Option<Vec<Vector3>> = self.normals.into_iter().collect();
+        let uvs: Option<Vec<Point2>> = self.uvs.into_iter().collect();
+        let mut mesh = Mesh::new(self.vertices, self.faces);
+        if let Some(normals) = normals {
+            mesh = mesh.with_normals(normals);
+        }
+        if let Some(uvs) = uvs {
+            mesh = mesh.with_uvs(uvs);
+        }
+        if self.face_materials.iter().any(Option::is_some) {
+            let default_index = self.materials.len() as u32;
+            self.materials.push(default_material);
+            let face_materials = self
+                .face_materials
+                .iter()
+                .map(|m| m.unwrap_or(default_index))
+                .collect();
+            mesh = mesh.with_face_materials(self.materials, face_materials);
+        }
+        Some(ObjGroup {
+            name: self.name,
+            mesh,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{rngs::StdRng, SeedableRng};
+    use std::io::Cursor;
+
+    fn no_libraries(name: &str) -> Result<MaterialLibrary, ObjError> {
+        Err(ObjError::syntax(0, format!("unexpected library {}", name)))
+    }
+
+    fn parse(content: &str) -> Result<Vec<ObjGroup>, ObjError> {
+        parse_obj(Cursor::new(content), Material::default(), no_libraries)
+    }
+
+    fn syntax_error_line(result: Result<Vec<ObjGroup>, ObjError>) -> usize {
+        match result {
+            Err(ObjError {
+                line,
+                kind: ObjErrorKind::Syntax(_),
+                ..
+            }) => line,
+            other => panic!("expected syntax error, got {:?}", other),
+        }
+    }
+
+    const QUAD: &str = "
+        # Unit square
+        v 0 0 0
+        v 1 0 0
+        v 1 1 0
+        v 0 1 0
+        f 1 2 3 4
+    ";
+
+    #[test]
+    fn polygons_are_triangulated() {
+        let groups = parse(QUAD).unwrap();
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].name, "default");
+        let mesh = &groups[0].mesh;
+        assert_eq!(mesh.get_vertices().len(), 4);
+        assert_eq!(mesh.get_faces(), &[[0, 1, 2], [0, 2, 3]]);
+        assert_eq!(mesh.get_normals(), None);
+        assert_eq!(mesh.get_uvs(), None);
+    }
+
+    #[test]
+    fn negative_indices_are_relative() {
+        let groups = parse(
+            "v 0 0 0
+             v 1 0 0
+             v 1 1 0
+             f -3 -2 -1",
+        )
+        .unwrap();
+        assert_eq!(groups[0].mesh.get_faces(), &[[0, 1, 2]]);
+    }
+
+    #[test]
+    fn normals_and_uvs_are_read() {
+        let groups = parse(
+            "v 0 0 0
+             v 1 0 0
+             v 1 1 0
+             vt 0 0
+             vt 1 0
+             vt 1 1
+             vn 0 0 2
+             f 1/1/1 2/2/1 3/3/1",
+        )
+        .unwrap();
+        let mesh = &groups[0].mesh;
+        assert_eq!(mesh.get_normals().unwrap(), &[Vector3::z_axis(); 3]);
+        assert_eq!(
+            mesh.get_uvs().unwrap(),
+            &[
+                Point2::new(0.0, 0.0),
+                Point2::new(1.0, 0.0),
+                Point2::new(1.0, 1.0)
+            ]
+        );
+    }
+
+    #[test]
+    fn vertices_are_shared_only_with_same_attributes() {
+        let groups = parse(
+            "v 0 0 0
+             v 1 0 0
+             v 1 1 0
+             v 0 1 0
+             vn 0 0 1
+             vn 0 0 -1
+             f 1//1 2//1 3//1
+             f 1//1 3//1 4//1
+             f 1//2 3//2 4//2",
+        )
+        .unwrap();
+        let mesh = &groups[0].mesh;
+        assert_eq!(mesh.get_vertices().len(), 7);
+        assert_eq!(mesh.get_faces(), &[[0, 1, 2], [0, 2, 3], [4, 5, 6]]);
+    }
+
+    #[test]
+    fn groups_become_separate_meshes() {
+        let groups = parse(
+            "v 0 0 0
+             v 1 0 0
+             v 1 1 0
+             v 0 1 0
+             g first
+             f 1 2 3
+             o second one
+             f 1 3 4
+             g empty",
+        )
+        .unwrap();
+        assert_eq!(groups.len(), 2);
+        assert_eq!(groups[0].name, "first");
+        assert_eq!(groups[1].name, "second one");
+        assert_eq!(groups[1].mesh.get_vertices().len(), 3);
+    }
+
+    #[test]
+    fn materials_are_read_from_libraries() {
+        let mtl = "
+            newmtl light
+            Ke 1 1 0.5
+            Kd 0.5
+            newmtl red
+            Kd 1 0 0
+            Ns 10
+        ";
+        let groups = parse_obj(
+            Cursor::new(
+                "mtllib scene.mtl
+                 v 0 0 0
+                 v 1 0 0
+                 v 1 1 0
+                 f 1 2 3
+                 usemtl red
+                 f 1 2 3
+                 usemtl light
+                 f 1 2 3
+                 usemtl red
+                 f 1 2 3",
+            ),
+            Material::default(),
+            |name| {
+                assert_eq!(name, "scene.mtl");
+                parse_mtl(Cursor::new(mtl))
+            },
+        )
+        .unwrap();
+        let mesh = &groups[0].mesh;
+        let red = Material::diffuse(Colour {
+            red: 1.0,
+            green: 0.0,
+            blue: 0.0,
+        });
+        let light = Material::diffuse(Colour {
+            red: 0.5,
+            green: 0.5,
+            blue: 0.5,
+        })
+        .with_emission(Colour {
+            red: 1.0,
+            green: 1.0,
+            blue: 0.5,
+        });
+        assert_eq!(mesh.get_face_material(0), Some(&Material::default()));
+        assert_eq!(mesh.get_face_material(1), Some(&red));
+        assert_eq!(mesh.get_face_material(2), Some(&light));
+        assert_eq!(mesh.get_face_material(3), Some(&red));
+    }
+
+    #[test]
+    fn errors_point_at_offending_line() {
+        assert_eq!(syntax_error_line(parse("v 0 0 0\nv 1 x 0")), 2);
+        assert_eq!(syntax_error_line(parse("v 0 0\n")), 1);
+        assert_eq!(syntax_error_line(parse("v 0 0 0\n\nf 1 1")), 3);
+        assert_eq!(syntax_error_line(parse("v 0 0 0\nf 1 1 2")), 2);
+        assert_eq!(syntax_error_line(parse("v 0 0 0\nf 1 1 0")), 2);
+        assert_eq!(syntax_error_line(parse("v 0 0 0\nf 1/1 1 1")), 2);
+        assert_eq!(syntax_error_line(parse("usemtl missing")), 1);
+    }
+
+    #[test]
+    fn mtl_errors_point_at_offending_line() {
+        let result = parse_mtl(Cursor::new("newmtl a\nKd 1 0\n"));
+        assert!(matches!(result, Err(ObjError { line: 2, .. })));
+        let result = parse_mtl(Cursor::new("Kd 1 0 0\n"));
+        assert!(matches!(result, Err(ObjError { line: 1, .. })));
+    }
+
+    #[test]
+    fn error_message_contains_file_and_line() {
+        let error = ObjError::syntax(7, "invalid number 'x'".into()).in_file(Path::new("a.obj"));
+        assert_eq!(error.to_string(), "a.obj:7: invalid number 'x'");
+    }
+
+    #[test]
+    fn obj_file_is_loaded_into_scene() {
+        let directory = std::env::temp_dir().join(format!("rustracer-obj-{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        std::fs::write(directory.join("lights.mtl"), "newmtl lamp\nKe 1 1 1\n").unwrap();
+        std::fs::write(
+            directory.join("model.obj"),
+            format!("mtllib lights.mtl\nusemtl lamp\n{}", QUAD),
+        )
+        .unwrap();
+        std::fs::write(
+            directory.join("broken.obj"),
+            "# Missing\nmtllib missing.mtl\n",
+        )
+        .unwrap();
+
+        let mut scene = Scene::new(Material::default(), 0, 1);
+        load_obj_into(&mut scene, directory.join("model.obj"), Material::default()).unwrap();
+        let error = load_obj(directory.join("broken.obj"), Material::default()).unwrap_err();
+        std::fs::remove_dir_all(&directory).unwrap();
+
+        let ray = crate::Ray::new(Point3::new(0.5, 0.5, 1.0), -Vector3::z());
+        #[rustfmt::skip]
+        assert_eq!(Colour {red: 1.0, green: 1.0, blue: 1.0}, scene.trace(&ray, &mut StdRng::seed_from_u64(0)));
+        assert!(matches!(error.kind, ObjErrorKind::Io(_)));
+        assert_eq!(error.path, Some(directory.join("broken.obj")));
+        assert_eq!(error.line, 2);
+        assert!(error.to_string().contains("missing.mtl"), "{}", error);
+    }
+}
@@ -0,0 +1,124 @@
+use nalgebra::Unit;
+
+use crate::{Point2, Scalar, Vector3};
+
+/// Orthonormal basis with `normal` as its z axis.
+/// Local coordinates make shading computations independent of surface orientation.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct Frame {
+    pub tangent: Vector3,
+    pub bitangent: Vector3,
+    pub normal: Vector3,
+}
+
+impl Frame {
+    /// Creates frame around normal with arbitrary, but continuous tangent
+    /// (Duff et al., Building an Orthonormal Basis, Revisited)
+    pub fn from_normal(normal: &Unit<Vector3>) -> Self {
+        let n = normal.into_inner();
+        let sign = 1.0_f32.copysign(n.z);
+        let a = -1.0 / (sign + n.z);
+        let b = n.x * n.y * a;
+        Self {
+            tangent: Vector3::new(1.0 + sign * n.x * n.x * a, sign * b, -sign * n.x),
+            bitangent: Vector3::new(b, sign + n.y * n.y * a, -n.y),
+            normal: n,
+        }
+    }
+
+    /// Transforms direction from world coordinates to frame coordinates
+    pub fn to_local(&self, v: &Vector3) -> Vector3 {
+        Vector3::new(
+            v.dot(&self.tangent),
+            v.dot(&self.bitangent),
+            v.dot(&self.normal),
+        )
+    }
+
+    /// Transforms direction from frame coordinates to world coordinates
+    pub fn to_world(&self, v: &Vector3) -> Vector3 {
+        self.tangent * v.x + self.bitangent * v.y + self.normal * v.z
+    }
+}
+
+/// Maps point of the unit square to direction in the hemisphere around z axis
+/// with density proportional to the cosine of the angle to z axis (Malley's method)
+pub fn sample_cosine_hemisphere(u: &Point2) -> Vector3 {
+    let r = u.x.sqrt();
+    let phi = 2.0 * std::f32::consts::PI * u.y;
+    let z = (1.0 - u.x).max(0.0).sqrt();
+    Vector3::new(r * phi.cos(), r * phi.sin(), z)
+}
+
+/// Density of directions sampled by `sample_cosine_hemisphere`
+pub fn cosine_hemisphere_pdf(local_direction: &Vector3) -> Scalar {
+    local_direction.z.max(0.0) * std::f32::consts::FRAC_1_PI
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    #[test]
+    fn frame_is_orthonormal() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let normals = (0..100)
+            .map(|_| {
+                Vector3::new(
+                    rng.gen_range(-1.0..1.0),
+                    rng.gen_range(-1.0..1.0),
+                    rng.gen_range(-1.0..1.0),
+                )
+            })
+            .chain(vec![
+                Vector3::z(),
+                -Vector3::z(),
+                Vector3::x(),
+                -Vector3::y(),
+            ]);
+        for normal in normals {
+            let frame = Frame::from_normal(&Unit::new_normalize(normal));
+            assert!((frame.tangent.norm() - 1.0).abs() < 1e-5);
+            assert!((frame.bitangent.norm() - 1.0).abs() < 1e-5);
+            assert!(frame.tangent.dot(&frame.bitangent).abs() < 1e-5);
+            assert!(frame.tangent.dot(&frame.normal).abs() < 1e-5);
+            assert!((frame.tangent.cross(&frame.bitangent) - frame.normal).norm() < 1e-5);
+
+            let v = Vector3::new(0.3, -0.2, 0.9);
+            assert!((frame.to_local(&frame.to_world(&v)) - v).norm() < 1e-5);
+            assert!((frame.to_world(&Vector3::z()) - frame.normal).norm() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn cosine_hemisphere_samples_have_expected_moments() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let count = 100_000;
+        let samples: Vec<Vector3> = (0..count)
+            .map(|_| sample_cosine_hemisphere(&Point2::new(rng.gen(), rng.gen())))
+            .collect();
+        assert!(samples
+            .iter()
+            .all(|v| (v.norm() - 1.0).abs() < 1e-5 && v.z >= 0.0));
+        // For cosine density E[cos θ] = 2/3 and directions are symmetric around z axis
+        let mean = samples.iter().sum::<Vector3>() / count as Scalar;
+        assert!((mean.z - 2.0 / 3.0).abs() < 0.01, "{}", mean);
+        assert!(mean.x.abs() < 0.01 && mean.y.abs() < 0.01, "{}", mean);
+    }
+
+    #[test]
+    fn cosine_hemisphere_pdf_integrates_to_one() {
+        // Monte Carlo estimate of the pdf integral over sphere with uniform directions
+        let mut rng = StdRng::seed_from_u64(2);
+        let count = 100_000;
+        let sum: Scalar = (0..count)
+            .map(|_| {
+                let z: Scalar = rng.gen_range(-1.0..1.0);
+                cosine_hemisphere_pdf(&Vector3::new((1.0 - z * z).sqrt(), 0.0, z))
+            })
+            .sum();
+        let integral = sum / count as Scalar * 4.0 * std::f32::consts::PI;
+        assert!((integral - 1.0).abs() < 0.01, "{}", integral);
+    }
+}
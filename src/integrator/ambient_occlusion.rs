@@ -0,0 +1,101 @@
+use rand::{Rng, RngCore};
+
+use crate::{
+    integrator::Integrator, sample_cosine_hemisphere, Colour, Frame, Point2, Ray, Scalar, Scene,
+};
+
+/// Ambient occlusion integrator. Colour of the hit point is the cosine weighted
+/// fraction of directions, in which no other surface lies closer than `distance`.
+/// Materials are ignored and rays leaving the scene give white.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct AmbientOcclusion {
+    /// Occlusion rays cast at every hit
+    pub samples: usize,
+    /// Surfaces farther away do not occlude
+    pub distance: Scalar,
+}
+
+impl AmbientOcclusion {
+    pub fn new(samples: usize, distance: Scalar) -> Self {
+        assert!(samples > 0, "Ambient occlusion needs at least one sample");
+        Self { samples, distance }
+    }
+}
+
+impl Default for AmbientOcclusion {
+    fn default() -> Self {
+        Self::new(16, Scalar::INFINITY)
+    }
+}
+
+impl Integrator for AmbientOcclusion {
+    fn trace(&self, scene: &Scene, ray: &Ray, rng: &mut dyn RngCore) -> Colour {
+        let white = Colour {
+            red: 1.0,
+            green: 1.0,
+            blue: 1.0,
+        };
+        let hit = match scene.closest_hit(ray) {
+            Some(hit) => hit,
+            None => return white,
+        };
+        let intersection = &hit.intersection;
+        let normal = intersection.facing_normal();
+        let frame = Frame::from_normal(&normal);
+        let visible = (0..self.samples)
+            .filter(|_| {
+                let local = sample_cosine_hemisphere(&Point2::new(rng.gen(), rng.gen()));
+                let occlusion_ray = Ray {
+                    t_max: self.distance,
+                    ..Ray::leaving_surface(&intersection.point, &normal, frame.to_world(&local))
+                };
+                scene.closest_hit(&occlusion_ray).is_none()
+            })
+            .count();
+        white * (visible as Scalar / self.samples as Scalar)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{integrator::testing::floor, Material, Point3, Translation3, Vector3};
+    use rand::{rngs::StdRng, SeedableRng};
+
+    #[test]
+    fn open_plane_is_not_occluded() {
+        let mut scene = Scene::new(Material::default(), 0, 1);
+        scene.add(floor(), Material::default());
+        let ray = Ray::new(Point3::new(0.0, 1.0, 0.0), Vector3::new(0.2, -1.0, 0.0));
+        let colour = AmbientOcclusion::default().trace(&scene, &ray, &mut StdRng::seed_from_u64(0));
+        assert_eq!(colour.red, 1.0);
+    }
+
+    #[test]
+    fn ceiling_occludes_within_distance() {
+        let mut scene = Scene::new(Material::default(), 0, 1);
+        scene.add(floor(), Material::default());
+        scene.add(
+            Translation3::new(0.0, 1.0, 0.0) * floor(),
+            Material::default(),
+        );
+        let ray = Ray::new(Point3::new(0.0, 0.5, 0.0), Vector3::new(0.0, -1.0, 0.0));
+        let mut rng = StdRng::seed_from_u64(1);
+        let near = AmbientOcclusion::new(64, 1e4).trace(&scene, &ray, &mut rng);
+        assert_eq!(near.red, 0.0);
+        let far = AmbientOcclusion::new(64, 0.5).trace(&scene, &ray, &mut rng);
+        assert_eq!(far.red, 1.0);
+        // Ceiling at unit height covers half of cosine weighted directions within
+        // distance √2, i.e. those making at most 45° with the normal.
+        let half = AmbientOcclusion::new(10_000, 2.0_f32.sqrt()).trace(&scene, &ray, &mut rng);
+        assert!((half.red - 0.5).abs() < 0.02, "{:?}", half);
+    }
+
+    #[test]
+    fn miss_is_white() {
+        let scene = Scene::new(Material::default(), 0, 1);
+        let ray = Ray::new(Point3::origin(), Vector3::z());
+        let colour = AmbientOcclusion::default().trace(&scene, &ray, &mut StdRng::seed_from_u64(0));
+        assert_eq!(colour.green, 1.0);
+    }
+}
@@ -0,0 +1,432 @@
+use nalgebra::Reflection;
+use rand::{Rng, RngCore};
+
+use crate::{
+    integrator::{vertex::Vertex, Integrator, PathTracer},
+    Bsdf, Colour, Material, Ray, Rotation3, Scalar, Scene, SceneHit, Vector3,
+};
+
+/// The original rustracer algorithm. At every hit it spawns `Scene` beam rays
+/// count of rays in a narrow cone around the mirror reflection, whose width
+/// depends on the size of the hit primitive, and recurses until `Scene` recursion depth.
+/// The cone does not depend on roughness of the material, so rough and smooth metals
+/// reflect beams alike; path tracing follows rough lobes by their distributions instead.
+/// Perfectly smooth lobes, e.g. glass, also spread a beam around the refracted direction.
+/// Light from punctual lights is added at every hit, unless it is in shadow.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub struct BeamTracer {
+    /// Bounces after which every beam ray survives with probability equal to its
+    /// share of the pixel, i.e. the product of albedos along its path divided by
+    /// beam rays count at every bounce, and light it brings is divided by that
+    /// probability. Beams stop multiplying then, but the expected colour does not change.
+    /// `None` disables Russian roulette.
+    pub roulette_depth: Option<usize>,
+}
+
+/// Helper struct describing trace result
+#[derive(Debug, PartialEq, Copy, Clone, Default)]
+struct TraceResult {
+    pub diffuse: Colour,
+    pub emission: Colour,
+}
+
+impl Integrator for BeamTracer {
+    fn trace(&self, scene: &Scene, ray: &Ray, rng: &mut dyn RngCore) -> Colour {
+        let throughput = Colour {
+            red: 1.0,
+            green: 1.0,
+            blue: 1.0,
+        };
+        self.trace_until(scene, ray, 0, throughput, rng).diffuse
+    }
+}
+
+impl BeamTracer {
+    pub const fn new(roulette_depth: Option<usize>) -> Self {
+        Self { roulette_depth }
+    }
+
+    /// Traces ray, whose light is scaled by `throughput` on the way to the camera
+    fn trace_until(
+        &self,
+        scene: &Scene,
+        ray: &Ray,
+        step: usize,
+        throughput: Colour,
+        rng: &mut dyn RngCore,
+    ) -> TraceResult {
+        let default_material = scene.get_default_material();
+        let hit = match scene.closest_hit(ray) {
+            Some(hit) => hit,
+            None => {
+                return match scene.get_environment() {
+                    // Environment is seen directly and lights reflecting surfaces alike
+                    Some(environment) => {
+                        let radiance = environment.get_radiance(&ray.direction);
+                        TraceResult {
+                            diffuse: radiance,
+                            emission: radiance,
+                        }
+                    }
+                    None => TraceResult::from(default_material),
+                };
+            }
+        };
+        let vertex = Vertex::new(hit, ray);
+        let mut trace_result = TraceResult::default();
+        for (central_ray, weight) in Self::get_beams(ray, &vertex) {
+            let tr = if step < scene.get_recursion_depth() {
+                let throughput = throughput * weight;
+                let primitive_size = vertex.hit.primitive_size;
+                self.trace_beam(scene, &central_ray, primitive_size, step, throughput, rng)
+            } else {
+                TraceResult::from(default_material)
+            };
+            trace_result.emission += weight * tr.emission;
+        }
+        let mut trace_result = trace_result.apply_to(vertex.hit.material);
+        let punctual_light = vertex.get_punctual_light(scene);
+        trace_result.diffuse += punctual_light;
+        trace_result.emission += punctual_light;
+        trace_result
+    }
+
+    /// Returns central rays of beams leaving the hit together with parts of light
+    /// they bring. Lobes reflect light like mirrors by their albedo, except for
+    /// perfectly smooth ones, which split it into reflected and refracted beams.
+    fn get_beams(ray: &Ray, vertex: &Vertex<'_>) -> Vec<(Ray, Colour)> {
+        let mut mirror = Colour::default();
+        let mut beams = Vec::new();
+        for lobe in &vertex.hit.material.lobes {
+            let samples = lobe.split_specular(&vertex.wo);
+            if samples.is_empty() {
+                mirror += lobe.get_albedo();
+            }
+            for sample in samples {
+                if sample.direction.z * vertex.wo.z > 0.0 {
+                    mirror += sample.weight;
+                    continue;
+                }
+                let direction = vertex.frame.to_world(&sample.direction);
+                if vertex.is_consistent(&direction, &sample.direction) {
+                    beams.push((vertex.leaving(direction), sample.weight));
+                }
+            }
+        }
+        beams.insert(0, (Self::get_reflected_ray(ray, &vertex.hit), mirror));
+        beams
+    }
+
+    /// Traces beam rays count of rays spread around central ray leaving primitive
+    /// of given size and returns their average light
+    fn trace_beam(
+        &self,
+        scene: &Scene,
+        central_ray: &Ray,
+        primitive_size: Scalar,
+        step: usize,
+        throughput: Colour,
+        rng: &mut dyn RngCore,
+    ) -> TraceResult {
+        let rotation = Self::get_beam_rotation(central_ray);
+        let beam_rays_count = scene.get_beam_rays_count() as Scalar;
+        let throughput = throughput / beam_rays_count;
+        let survival = match self.roulette_depth {
+            Some(depth) if step >= depth => throughput
+                .red
+                .max(throughput.green)
+                .max(throughput.blue)
+                .min(1.0),
+            _ => 1.0,
+        };
+        let mut trace_result = TraceResult::default();
+        for _ in 0..scene.get_beam_rays_count() {
+            if survival < 1.0 && rng.gen::<Scalar>() >= survival {
+                continue;
+            }
+            let beam_ray = Self::get_beam_ray(central_ray, &rotation, primitive_size, rng);
+            let mut tr = self.trace_until(scene, &beam_ray, step + 1, throughput / survival, rng);
+            tr.emission /= survival;
+            trace_result.add_light(&tr);
+        }
+        trace_result.emission /= beam_rays_count;
+        trace_result
+    }
+
+    fn get_reflected_ray(ray: &Ray, hit: &SceneHit<'_>) -> Ray {
+        let mut vector = ray.direction.into_inner().clone_owned();
+        // Directions are reflected by the plane parallel to the surface through the origin
+        let reflection = Reflection::new(hit.intersection.normal, 0.0);
+        reflection.reflect(&mut vector);
+        Ray::leaving_surface(&hit.intersection.point, &hit.intersection.normal, vector)
+    }
+
+    /// Returns rotation from the z axis to the direction of the beam
+    fn get_beam_rotation(ray: &Ray) -> Rotation3 {
+        Rotation3::rotation_between(&Vector3::new(0.0, 0.0, 1.0), &ray.direction.into_inner())
+            .unwrap_or_else(Rotation3::identity)
+    }
+
+    /// Returns one of the rays in the beam spread around given ray
+    fn get_beam_ray(
+        ray: &Ray,
+        rotation: &Rotation3,
+        primitive_size: Scalar,
+        rng: &mut dyn RngCore,
+    ) -> Ray {
+        let r = rng.gen_range(0.0..(0.005 * primitive_size));
+        let alpha = rng.gen_range(0.0..(2.0 * std::f32::consts::PI));
+        let circle_vec = Vector3::new(r * alpha.cos(), r * alpha.sin(), 0.0);
+        Ray::new(
+            ray.origin,
+            ray.direction.into_inner() + rotation * circle_vec,
+        )
+    }
+}
+
+impl Default for BeamTracer {
+    fn default() -> Self {
+        Self::new(Some(PathTracer::DEFAULT_ROULETTE_DEPTH))
+    }
+}
+
+impl TraceResult {
+    pub fn add_light(&mut self, other: &Self) {
+        self.emission += other.emission;
+    }
+
+    /// Adds emission of the material to light it scatters, which is already
+    /// weighted by its lobes
+    pub fn apply_to(&self, material: &Material) -> Self {
+        Self {
+            emission: material.emission + self.emission,
+            diffuse: material.emission + self.emission,
+        }
+    }
+}
+
+impl From<&Material> for TraceResult {
+    fn from(material: &Material) -> Self {
+        Self {
+            emission: material.emission,
+            diffuse: material.get_albedo(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{primitives::Triangle, Dielectric, Point3, PointLight};
+    use rand::{rngs::StdRng, SeedableRng};
+
+    #[test]
+    fn trace_result_can_be_created_from_material() {
+        let material = Material::diffuse(Colour {
+            red: 0.0,
+            green: 0.0,
+            blue: 0.0,
+        })
+        .with_emission(Colour {
+            red: 1.0,
+            green: 1.0,
+            blue: 1.0,
+        });
+        let trace_result = TraceResult::from(&material);
+        assert_eq!(
+            trace_result,
+            TraceResult {
+                #[rustfmt::skip]
+                diffuse: Colour {red: 0.0, green: 0.0, blue: 0.0,},
+                #[rustfmt::skip]
+                emission: Colour {red: 1.0, green: 1.0, blue: 1.0,},
+            }
+        );
+    }
+
+    #[test]
+    fn trace_results_can_add_lights() {
+        let mut tr1 = TraceResult {
+            #[rustfmt::skip]
+            diffuse: Colour {red: 1.0, green: 0.0, blue: 0.0,},
+            #[rustfmt::skip]
+            emission: Colour {red: 1.0, green: 0.0, blue: 0.25,},
+        };
+        let tr2 = TraceResult {
+            #[rustfmt::skip]
+            diffuse: Colour {red: 0.0, green: 1.0, blue: 0.0,},
+            #[rustfmt::skip]
+            emission: Colour {red: 0.0, green: 1.0, blue: 0.35,},
+        };
+        tr1.add_light(&tr2);
+        assert_eq!(
+            tr1,
+            TraceResult {
+                #[rustfmt::skip]
+                diffuse: Colour {red: 1.0, green: 0.0, blue: 0.0,},
+                #[rustfmt::skip]
+                emission: Colour {red: 1.0, green: 1.0, blue: 0.6,},
+            }
+        );
+    }
+
+    #[test]
+    fn reflected_rays_do_not_hit_reflecting_primitive_far_from_origin() {
+        let mut scene = Scene::new(Material::default(), 1, 1);
+        let triangle = Triangle::new([
+            Point3::new(1000.0, -1.0, 1000.0),
+            Point3::new(1000.5, 1.0, 1001.0),
+            Point3::new(999.0, -1.0, 1000.5),
+        ]);
+        scene.add(triangle, Material::default());
+        let mut rng = StdRng::seed_from_u64(3);
+        for _ in 0..1000 {
+            let (u, v) = (rng.gen_range(0.1..0.45), rng.gen_range(0.1..0.45));
+            let target = triangle.get_v(0)
+                + u * (triangle.get_v(1) - triangle.get_v(0))
+                + v * (triangle.get_v(2) - triangle.get_v(0));
+            let origin = target + Vector3::new(rng.gen_range(-5.0..5.0), 3.0, 5.0);
+            let ray = Ray::new(origin, target - origin);
+            let hit = scene.closest_hit(&ray).expect("ray should hit triangle");
+            let reflected_ray = BeamTracer::get_reflected_ray(&ray, &hit);
+            assert_eq!(None, scene.closest_hit(&reflected_ray));
+        }
+    }
+
+    #[test]
+    fn dark_beams_are_terminated_without_changing_mean() {
+        // Diffuse emitters in the y = 0 and y = 1 planes reflect beams between them,
+        // which would take beam rays count to the power of recursion depth rays
+        let mut scene = Scene::new(
+            Material {
+                #[rustfmt::skip]
+                emission: Colour {red: 2.0, green: 2.0, blue: 2.0,},
+                ..Default::default()
+            },
+            30,
+            4,
+        );
+        for y in [0.0, 1.0] {
+            scene.add(
+                Triangle::new([
+                    Point3::new(-1e3, y, -1e3),
+                    Point3::new(0.0, y, 1e3),
+                    Point3::new(1e3, y, -1e3),
+                ]),
+                Material::diffuse(Colour::grey(0.5)).with_emission(Colour::grey(1.0)),
+            );
+        }
+        // Rays leaving the scene or exceeding recursion depth see emission of 2,
+        // so every surface reflects half of it, which gives colour of 2 for any path
+        let ray = Ray::new(Point3::new(0.0, 0.5, 0.0), Vector3::new(1.0, -1.0, 0.0));
+        let mut rng = StdRng::seed_from_u64(0);
+        let count = 2000;
+        let mut sum = 0.0;
+        for _ in 0..count {
+            sum += BeamTracer::new(Some(2)).trace(&scene, &ray, &mut rng).green;
+        }
+        let mean = sum / count as Scalar;
+        assert!((mean - 2.0).abs() < 0.05, "{}", mean);
+    }
+
+    #[test]
+    fn surfaces_are_lit_by_punctual_lights() {
+        let mut scene = Scene::new(Material::default(), 1, 1);
+        scene.add(
+            Triangle::new([
+                Point3::new(-1e3, 0.0, -1e3),
+                Point3::new(0.0, 0.0, 1e3),
+                Point3::new(1e3, 0.0, -1e3),
+            ]),
+            Material::diffuse(Colour::grey(0.5)),
+        );
+        scene.add_light(PointLight::new(
+            Point3::new(0.0, 2.0, 0.0),
+            Colour::grey(1.0),
+            8.0,
+        ));
+        // Light arrives at normal incidence from distance of 2
+        let ray = Ray::new(Point3::new(0.0, 1.0, 1.0), Vector3::new(0.0, -1.0, -1.0));
+        let colour = BeamTracer::default().trace(&scene, &ray, &mut StdRng::seed_from_u64(0));
+        let expected = 0.5 * std::f32::consts::FRAC_1_PI * 8.0 / 4.0;
+        assert!((colour.green - expected).abs() < 1e-5, "{:?}", colour);
+        // Blocked light casts a shadow
+        scene.add(
+            Triangle::new([
+                Point3::new(-0.5, 1.5, -0.5),
+                Point3::new(0.0, 1.5, 0.5),
+                Point3::new(0.5, 1.5, -0.5),
+            ]),
+            Material::diffuse(Colour::grey(0.5)),
+        );
+        let colour = BeamTracer::default().trace(&scene, &ray, &mut StdRng::seed_from_u64(0));
+        assert_eq!(colour, Colour::default());
+    }
+
+    #[test]
+    fn reflected_rays_mirror_direction_at_surface_far_from_origin() {
+        // Wall in the x = 10 plane facing the origin reflects rays straight back
+        let mut scene = Scene::new(Material::default(), 1, 1);
+        scene.add(
+            Triangle::new([
+                Point3::new(10.0, -1.0, -1.0),
+                Point3::new(10.0, -1.0, 1.0),
+                Point3::new(10.0, 1.0, 0.0),
+            ]),
+            Material::default(),
+        );
+        let ray = Ray::new(Point3::origin(), Vector3::x());
+        let hit = scene.closest_hit(&ray).expect("ray should hit wall");
+        let reflected_ray = BeamTracer::get_reflected_ray(&ray, &hit);
+        assert!((reflected_ray.direction.into_inner() + Vector3::x()).norm() < 1e-5);
+    }
+
+    #[test]
+    fn beams_are_scaled_by_albedo_once() {
+        // Narrow beams reflected by a small tile leave the scene and see its emission
+        let mut scene = Scene::new(Material::default().with_emission(Colour::grey(2.0)), 1, 4);
+        scene.add(
+            Triangle::new([
+                Point3::new(-1.0, 0.0, -1.0),
+                Point3::new(0.0, 0.0, 1.0),
+                Point3::new(1.0, 0.0, -1.0),
+            ]),
+            Material::diffuse(Colour::grey(0.5)),
+        );
+        let ray = Ray::new(Point3::new(0.0, 1.0, 1.0), Vector3::new(0.0, -1.0, -1.0));
+        let colour = BeamTracer::default().trace(&scene, &ray, &mut StdRng::seed_from_u64(0));
+        assert!((colour.red - 1.0).abs() < 1e-5, "{:?}", colour);
+    }
+
+    #[test]
+    fn glass_refracts_beams() {
+        // Glass pane in the x = 1 plane with the lamp behind it,
+        // seen at normal incidence from the origin
+        let mut scene = Scene::new(Material::default(), 1, 4);
+        scene.add(
+            Triangle::new([
+                Point3::new(1.0, -1.0, -1.0),
+                Point3::new(1.0, -1.0, 1.0),
+                Point3::new(1.0, 1.0, 0.0),
+            ]),
+            Material::default().with_lobe(Dielectric::new(1.5)),
+        );
+        scene.add(
+            Triangle::new([
+                Point3::new(2.0, -10.0, -10.0),
+                Point3::new(2.0, -10.0, 10.0),
+                Point3::new(2.0, 10.0, 0.0),
+            ]),
+            Material::default().with_emission(Colour::grey(4.0)),
+        );
+        // Beam reflected by the pane sees nothing and the refracted one sees the lamp,
+        // whose radiance is divided by squared index of refraction
+        let ray = Ray::new(Point3::origin(), Vector3::x());
+        let colour = BeamTracer::default().trace(&scene, &ray, &mut StdRng::seed_from_u64(0));
+        assert!(
+            (colour.red - 4.0 * 0.96 / 2.25).abs() < 1e-5,
+            "{:?}",
+            colour
+        );
+    }
+}
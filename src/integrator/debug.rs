@@ -0,0 +1,101 @@
+use rand::RngCore;
+
+use crate::{integrator::Integrator, Colour, Ray, Scalar, Scene, Vector3};
+
+/// Integrators visualizing geometry of the first hit instead of light.
+/// Rays leaving the scene are black.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum DebugIntegrator {
+    /// Shading normal with coordinates mapped from `[-1, 1]` to `[0, 1]`
+    Normals,
+    /// Distance to the hit as shade of grey, white at `max_distance` or farther
+    Depth { max_distance: Scalar },
+    /// Local 2D coordinates `(u, v)` of the hit as colour `(1 - u - v, u, v)`.
+    /// For triangles and meshes without texture coordinates they are barycentric.
+    Barycentrics,
+}
+
+impl Integrator for DebugIntegrator {
+    fn trace(&self, scene: &Scene, ray: &Ray, _rng: &mut dyn RngCore) -> Colour {
+        let intersection = match scene.closest_hit(ray) {
+            Some(hit) => hit.intersection,
+            None => return Colour::default(),
+        };
+        match self {
+            Self::Normals => {
+                let n = (intersection.shading_normal.into_inner() + Vector3::repeat(1.0)) / 2.0;
+                Colour {
+                    red: n.x,
+                    green: n.y,
+                    blue: n.z,
+                }
+            }
+            Self::Depth { max_distance } => {
+                let depth = (intersection.t / max_distance).min(1.0);
+                Colour {
+                    red: depth,
+                    green: depth,
+                    blue: depth,
+                }
+            }
+            Self::Barycentrics => {
+                let uv = intersection.uv;
+                Colour {
+                    red: 1.0 - uv.x - uv.y,
+                    green: uv.x,
+                    blue: uv.y,
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{primitives::Triangle, Material, Point3};
+    use rand::{rngs::StdRng, SeedableRng};
+
+    fn trace(integrator: DebugIntegrator, ray: &Ray) -> Colour {
+        let mut scene = Scene::new(Material::default(), 0, 1);
+        scene.add(
+            Triangle::new([
+                Point3::new(0.0, 0.0, 2.0),
+                Point3::new(1.0, 0.0, 2.0),
+                Point3::new(0.0, 1.0, 2.0),
+            ]),
+            Material::default(),
+        );
+        integrator.trace(&scene, ray, &mut StdRng::seed_from_u64(0))
+    }
+
+    #[test]
+    fn debug_integrators_show_hit_geometry() {
+        let ray = Ray::new(Point3::new(0.25, 0.5, 0.0), Vector3::z());
+        let normal = trace(DebugIntegrator::Normals, &ray);
+        assert_eq!((normal.red, normal.green), (0.5, 0.5));
+        assert!(normal.blue == 0.0 || normal.blue == 1.0);
+
+        let depth = trace(DebugIntegrator::Depth { max_distance: 4.0 }, &ray);
+        assert_eq!(depth.red, 0.5);
+        let depth = trace(DebugIntegrator::Depth { max_distance: 1.0 }, &ray);
+        assert_eq!(depth.red, 1.0);
+
+        let barycentrics = trace(DebugIntegrator::Barycentrics, &ray);
+        assert!((barycentrics.red - 0.25).abs() < 1e-6);
+        assert!((barycentrics.green - 0.25).abs() < 1e-6);
+        assert!((barycentrics.blue - 0.5).abs() < 1e-6);
+    }
+
+    #[test]
+    fn debug_integrators_show_black_background() {
+        let ray = Ray::new(Point3::new(2.0, 2.0, 0.0), Vector3::z());
+        for integrator in [
+            DebugIntegrator::Normals,
+            DebugIntegrator::Depth { max_distance: 1.0 },
+            DebugIntegrator::Barycentrics,
+        ] {
+            assert_eq!(trace(integrator, &ray), Colour::default());
+        }
+    }
+}
@@ -0,0 +1,51 @@
+use std::fmt;
+
+use rand::RngCore;
+
+use crate::{Colour, Ray, Scene};
+
+mod ambient_occlusion;
+mod beam;
+mod debug;
+mod path;
+mod vertex;
+mod whitted;
+pub use ambient_occlusion::AmbientOcclusion;
+pub use beam::BeamTracer;
+pub use debug::DebugIntegrator;
+pub use path::PathTracer;
+pub use whitted::WhittedTracer;
+
+/// Light transport algorithm, which computes colour seen along a camera ray
+pub trait Integrator: fmt::Debug + Send + Sync {
+    /// Estimates colour arriving along the ray, drawing random numbers from `rng`.
+    /// The same generator state gives the same result.
+    fn trace(&self, scene: &Scene, ray: &Ray, rng: &mut dyn RngCore) -> Colour;
+}
+
+/// Scene pieces and assertions shared by tests of integrators
+#[cfg(test)]
+mod testing {
+    use crate::{primitives::Triangle, Colour, Point3};
+
+    /// Large triangle in the y = 0 plane
+    pub fn floor() -> Triangle {
+        Triangle::new([
+            Point3::new(-1e3, 0.0, -1e3),
+            Point3::new(0.0, 0.0, 1e3),
+            Point3::new(1e3, 0.0, -1e3),
+        ])
+    }
+
+    pub fn assert_close(a: Colour, b: Colour) {
+        let difference = a - b;
+        assert!(
+            difference.red.abs() < 1e-4
+                && difference.green.abs() < 1e-4
+                && difference.blue.abs() < 1e-4,
+            "{:?} != {:?}",
+            a,
+            b
+        );
+    }
+}
@@ -0,0 +1,557 @@
+use rand::{Rng, RngCore};
+
+use crate::{
+    integrator::{vertex::Vertex, Integrator},
+    Bsdf, Colour, Point2, Ray, Scalar, Scene,
+};
+
+/// Unidirectional path tracer solving the rendering equation by Monte Carlo
+/// integration. Surfaces scatter light by the BSDF of their material and emit
+/// `Material::emission` radiance. Rays leaving the scene see its environment or
+/// the emission of its default material. Paths end after `Scene` recursion depth
+/// bounces or earlier, when they are terminated by Russian roulette.
+///
+/// At every bounce a point on emissive triangles and a direction of the environment
+/// are sampled and connected with shadow rays (next event estimation). Light found
+/// this way and light hit by scattered rays are combined by multiple importance
+/// sampling with the power heuristic. Punctual lights of the scene can be reached
+/// only by shadow rays, so they are connected at every bounce independent of `light_sampling`.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub struct PathTracer {
+    /// Bounces after which paths survive with probability equal to the largest
+    /// component of their throughput, which is then divided by that probability.
+    /// Dark paths are terminated early without biasing the result.
+    /// `None` disables Russian roulette.
+    pub roulette_depth: Option<usize>,
+    /// Sample emissive triangles and the environment explicitly at every bounce
+    pub light_sampling: bool,
+}
+
+impl PathTracer {
+    pub const DEFAULT_ROULETTE_DEPTH: usize = 3;
+
+    /// Creates path tracer with light sampling
+    pub fn new(roulette_depth: Option<usize>) -> Self {
+        Self {
+            roulette_depth,
+            light_sampling: true,
+        }
+    }
+
+    /// Enables or disables next event estimation
+    pub fn with_light_sampling(self, light_sampling: bool) -> Self {
+        Self {
+            light_sampling,
+            ..self
+        }
+    }
+}
+
+impl Default for PathTracer {
+    fn default() -> Self {
+        Self::new(Some(Self::DEFAULT_ROULETTE_DEPTH))
+    }
+}
+
+impl Integrator for PathTracer {
+    fn trace(&self, scene: &Scene, ray: &Ray, rng: &mut dyn RngCore) -> Colour {
+        let mut radiance = Colour::default();
+        let mut throughput = Colour {
+            red: 1.0,
+            green: 1.0,
+            blue: 1.0,
+        };
+        let mut ray = *ray;
+        // Density of the ray direction, if lights it hits were also sampled explicitly
+        let mut bsdf_pdf = None;
+        for bounce in 0..=scene.get_recursion_depth() {
+            let hit = match scene.closest_hit(&ray) {
+                Some(hit) => hit,
+                None => {
+                    let weight = match (bsdf_pdf, scene.get_environment()) {
+                        (Some(pdf), Some(environment)) => {
+                            power_heuristic(pdf, environment.get_pdf(&ray.direction))
+                        }
+                        _ => 1.0,
+                    };
+                    radiance += throughput * scene.get_background(&ray.direction) * weight;
+                    break;
+                }
+            };
+            let weight = match bsdf_pdf {
+                Some(pdf) => power_heuristic(pdf, scene.get_light_pdf(&ray, &hit)),
+                None => 1.0,
+            };
+            radiance += throughput * hit.material.emission * weight;
+            if bounce == scene.get_recursion_depth() {
+                break;
+            }
+
+            let vertex = Vertex::new(hit, &ray);
+            let material = vertex.hit.material;
+            if material.get_albedo() != Colour::default() {
+                radiance += throughput * vertex.get_punctual_light(scene);
+                if self.light_sampling {
+                    let lights = [
+                        vertex.sample_area_light(scene, rng),
+                        vertex.sample_environment(scene, rng),
+                    ];
+                    for light in lights.iter().flatten() {
+                        let weight = power_heuristic(light.light_pdf, light.bsdf_pdf);
+                        radiance += throughput * light.radiance * weight;
+                    }
+                }
+            }
+            let u = Point2::new(rng.gen(), rng.gen());
+            let sample = match material.sample(&vertex.wo, rng.gen(), &u) {
+                Some(sample) => sample,
+                None => break,
+            };
+            let direction = vertex.frame.to_world(&sample.direction);
+            if !vertex.is_consistent(&direction, &sample.direction) {
+                // Interpolated normals can send rays to the other side of the surface
+                break;
+            }
+            throughput *= sample.weight;
+            if throughput == Colour::default() {
+                break;
+            }
+            if self.roulette_depth.is_some_and(|depth| bounce >= depth) {
+                let survival = throughput.red.max(throughput.green).max(throughput.blue);
+                if survival < 1.0 {
+                    if rng.gen::<Scalar>() >= survival {
+                        break;
+                    }
+                    throughput /= survival;
+                }
+            }
+            bsdf_pdf = Some(sample.pdf).filter(|_| self.light_sampling && !sample.specular);
+            ray = vertex.leaving(direction);
+        }
+        radiance
+    }
+}
+
+/// Weight of a sample drawn with density `pdf`, when another strategy
+/// could draw it with density `other_pdf` (Veach, 1997)
+fn power_heuristic(pdf: Scalar, other_pdf: Scalar) -> Scalar {
+    let (a, b) = (pdf * pdf, other_pdf * other_pdf);
+    if a + b == 0.0 {
+        return 0.0;
+    }
+    a / (a + b)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        integrator::testing::assert_close, primitives::Sphere, primitives::Triangle, Dielectric,
+        DirectionalLight, EnvironmentMap, Light, Material, Point3, PointLight, SpotLight, Vector3,
+    };
+    use rand::{rngs::StdRng, SeedableRng};
+
+    #[test]
+    fn empty_scene_shows_background() {
+        let scene = Scene::new(
+            Material {
+                emission: Colour::grey(0.25),
+                ..Default::default()
+            },
+            3,
+            1,
+        );
+        let ray = Ray::new(Point3::origin(), Vector3::z());
+        let colour = PathTracer::default().trace(&scene, &ray, &mut StdRng::seed_from_u64(0));
+        assert_eq!(colour, Colour::grey(0.25));
+    }
+
+    #[test]
+    fn furnace_converges_to_geometric_series() {
+        // Inside a closed sphere every path bounces until the depth limit, so
+        // radiance is e (1 + a + ... + a^d) exactly for every sample.
+        let (albedo, emission, depth) = (0.5, 0.2, 6);
+        let mut scene = Scene::new(Material::default(), depth, 1);
+        scene.add(
+            Sphere::new(Point3::origin(), 10.0),
+            Material::diffuse(Colour::grey(albedo)).with_emission(Colour::grey(emission)),
+        );
+        let expected = emission * (1.0 - albedo.powi(depth as i32 + 1)) / (1.0 - albedo);
+        let mut rng = StdRng::seed_from_u64(1);
+        for _ in 0..100 {
+            let direction = Vector3::new(rng.gen(), rng.gen(), rng.gen()) - Vector3::repeat(0.5);
+            let ray = Ray::new(Point3::new(1.0, 2.0, 3.0), direction);
+            assert_close(
+                PathTracer::new(None).trace(&scene, &ray, &mut rng),
+                Colour::grey(expected),
+            );
+        }
+    }
+
+    fn furnace(albedo: Scalar, emission: Scalar, depth: usize) -> Scene {
+        let mut scene = Scene::new(Material::default(), depth, 1);
+        scene.add(
+            Sphere::new(Point3::origin(), 10.0),
+            Material::diffuse(Colour::grey(albedo)).with_emission(Colour::grey(emission)),
+        );
+        scene
+    }
+
+    #[test]
+    fn russian_roulette_keeps_furnace_unbiased() {
+        // Without the depth limit radiance is e / (1 - a), but paths reach
+        // the limit only with negligible probability
+        let (albedo, emission) = (0.75, 0.2);
+        let scene = furnace(albedo, emission, 1000);
+        let expected = emission / (1.0 - albedo);
+        let tracer = PathTracer::new(Some(1));
+        let mut rng = StdRng::seed_from_u64(4);
+        let count = 20_000;
+        let ray = Ray::new(Point3::origin(), Vector3::x());
+        let mean = (0..count)
+            .map(|_| tracer.trace(&scene, &ray, &mut rng).red)
+            .sum::<Scalar>()
+            / count as Scalar;
+        assert!(
+            (mean - expected).abs() < 0.02 * expected,
+            "{} != {}",
+            mean,
+            expected
+        );
+    }
+
+    #[test]
+    fn russian_roulette_starts_at_minimum_depth() {
+        // Paths with unit throughput are never terminated
+        let scene = furnace(1.0, 0.25, 10);
+        let mut rng = StdRng::seed_from_u64(5);
+        let ray = Ray::new(Point3::origin(), Vector3::y());
+        for _ in 0..10 {
+            assert_close(
+                PathTracer::new(Some(0)).trace(&scene, &ray, &mut rng),
+                Colour::grey(0.25 * 11.0),
+            );
+        }
+        // Before minimum depth dark paths are traced like without roulette
+        let scene = furnace(0.5, 0.2, 4);
+        let exact = PathTracer::new(None).trace(&scene, &ray, &mut rng);
+        for _ in 0..10 {
+            assert_close(
+                PathTracer::new(Some(4)).trace(&scene, &ray, &mut rng),
+                exact,
+            );
+        }
+    }
+
+    #[test]
+    fn diffuse_plane_reflects_sky() {
+        // Every bounce from a large plane facing the sky escapes, so the plane
+        // reflects albedo times sky radiance independent of the sample.
+        let mut scene = Scene::new(
+            Material {
+                emission: Colour {
+                    red: 1.0,
+                    green: 0.5,
+                    blue: 0.25,
+                },
+                ..Default::default()
+            },
+            4,
+            1,
+        );
+        scene.add(
+            Triangle::new([
+                Point3::new(-1e4, 0.0, -1e4),
+                Point3::new(0.0, 0.0, 1e4),
+                Point3::new(1e4, 0.0, -1e4),
+            ]),
+            Material::diffuse(Colour::grey(0.8)),
+        );
+        let mut rng = StdRng::seed_from_u64(2);
+        let ray = Ray::new(Point3::new(0.0, 1.0, 0.0), Vector3::new(0.3, -1.0, 0.1));
+        for _ in 0..100 {
+            assert_close(
+                PathTracer::default().trace(&scene, &ray, &mut rng),
+                Colour {
+                    red: 0.8,
+                    green: 0.4,
+                    blue: 0.2,
+                },
+            );
+        }
+    }
+
+    /// Square lamp of radiance L at height h above a white plane gives radiance
+    /// 1/π ∫ L cos θ cos θ' / d² dA = L h² / π ∫ 1 / d⁴ dA at the point below it.
+    /// Returns the scene, ray looking at that point and the expected radiance.
+    fn lit_plane() -> (Scene, Ray, Scalar) {
+        let (radiance, half_size, height) = (10.0, 0.25, 1.0);
+        let mut scene = Scene::new(Material::default(), 1, 1);
+        let lamp = [
+            Point3::new(-half_size, height, -half_size),
+            Point3::new(half_size, height, -half_size),
+            Point3::new(half_size, height, half_size),
+            Point3::new(-half_size, height, half_size),
+        ];
+        let emitter = Material {
+            emission: Colour::grey(radiance),
+            ..Default::default()
+        };
+        scene.add(Triangle::new([lamp[0], lamp[1], lamp[2]]), emitter.clone());
+        scene.add(Triangle::new([lamp[0], lamp[2], lamp[3]]), emitter);
+        scene.add(
+            Triangle::new([
+                Point3::new(-100.0, 0.0, -100.0),
+                Point3::new(0.0, 0.0, 100.0),
+                Point3::new(100.0, 0.0, -100.0),
+            ]),
+            Material::diffuse(Colour::grey(1.0)),
+        );
+        let cells = 200;
+        let cell = 2.0 * half_size / cells as Scalar;
+        let integral: Scalar = (0..cells * cells)
+            .map(|i| {
+                let x = -half_size + ((i % cells) as Scalar + 0.5) * cell;
+                let z = -half_size + ((i / cells) as Scalar + 0.5) * cell;
+                (x * x + z * z + height * height).powi(-2) * cell * cell
+            })
+            .sum();
+        let expected = radiance * height * height * integral / std::f32::consts::PI;
+        let ray = Ray::new(Point3::new(0.0, 0.5, -0.5), Vector3::new(0.0, -0.5, 0.5));
+        (scene, ray, expected)
+    }
+
+    /// Returns mean and variance of red component of `count` samples
+    fn estimate(tracer: PathTracer, scene: &Scene, ray: &Ray, count: usize) -> (Scalar, Scalar) {
+        let mut rng = StdRng::seed_from_u64(3);
+        let samples: Vec<Scalar> = (0..count)
+            .map(|_| tracer.trace(scene, ray, &mut rng).red)
+            .collect();
+        let mean = samples.iter().sum::<Scalar>() / count as Scalar;
+        let variance =
+            samples.iter().map(|s| (s - mean).powi(2)).sum::<Scalar>() / (count - 1) as Scalar;
+        (mean, variance)
+    }
+
+    #[test]
+    fn lit_plane_matches_analytic_irradiance() {
+        let (scene, ray, expected) = lit_plane();
+        for tracer in [
+            PathTracer::default(),
+            PathTracer::default().with_light_sampling(false),
+        ] {
+            let (mean, _) = estimate(tracer, &scene, &ray, 100_000);
+            assert!(
+                (mean - expected).abs() < 0.04 * expected,
+                "{:?}: {} != {}",
+                tracer,
+                mean,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn light_sampling_reduces_noise() {
+        let (scene, ray, expected) = lit_plane();
+        let (mean, variance) = estimate(PathTracer::default(), &scene, &ray, 10_000);
+        let (_, bsdf_variance) = estimate(
+            PathTracer::default().with_light_sampling(false),
+            &scene,
+            &ray,
+            10_000,
+        );
+        assert!(
+            (mean - expected).abs() < 0.01 * expected,
+            "{} != {}",
+            mean,
+            expected
+        );
+        assert!(
+            variance < 0.01 * bsdf_variance,
+            "{} {}",
+            variance,
+            bsdf_variance
+        );
+    }
+
+    /// White plane at y = 0 seen from above at the origin under black sky
+    fn plane_with_lights(lights: Vec<Light>) -> (Scene, Ray) {
+        let mut scene = Scene::new(Material::default(), 2, 1);
+        scene.add(
+            Triangle::new([
+                Point3::new(-100.0, 0.0, -100.0),
+                Point3::new(0.0, 0.0, 100.0),
+                Point3::new(100.0, 0.0, -100.0),
+            ]),
+            Material::diffuse(Colour::grey(0.8)),
+        );
+        for light in lights {
+            scene.add_light(light);
+        }
+        (
+            scene,
+            Ray::new(Point3::new(0.0, 1.0, 1.0), Vector3::new(0.0, -1.0, -1.0)),
+        )
+    }
+
+    #[test]
+    fn punctual_lights_are_reached_by_shadow_rays() {
+        let mut rng = StdRng::seed_from_u64(6);
+        let lambert = 0.8 * std::f32::consts::FRAC_1_PI;
+        let point = PointLight::new(Point3::new(0.0, 2.0, 0.0), Colour::grey(1.0), 8.0);
+        let spot = SpotLight::new(
+            Point3::new(0.0, 2.0, 0.0),
+            Vector3::new(1.0, -1.0, 0.0),
+            0.1,
+            0.2,
+            Colour::grey(1.0),
+            8.0,
+        );
+        let sun = DirectionalLight::new(Vector3::new(1.0, -1.0, 0.0), Colour::grey(1.0), 2.0);
+        for (lights, expected) in [
+            (vec![point.into()], lambert * 2.0),
+            // Spot light points away from the origin
+            (vec![spot.into()], 0.0),
+            (vec![sun.into()], lambert * 2.0 * 0.5_f32.sqrt()),
+            (
+                vec![point.into(), sun.into()],
+                lambert * 2.0 * (1.0 + 0.5_f32.sqrt()),
+            ),
+        ] {
+            let (scene, ray) = plane_with_lights(lights);
+            for tracer in [
+                PathTracer::default(),
+                PathTracer::default().with_light_sampling(false),
+            ] {
+                assert_close(tracer.trace(&scene, &ray, &mut rng), Colour::grey(expected));
+            }
+        }
+    }
+
+    #[test]
+    fn punctual_lights_cast_shadows() {
+        let point = PointLight::new(Point3::new(0.0, 2.0, 0.0), Colour::grey(1.0), 8.0);
+        let sun = DirectionalLight::new(-Vector3::y(), Colour::grey(1.0), 2.0);
+        let (mut scene, ray) = plane_with_lights(vec![point.into(), sun.into()]);
+        scene.add(
+            Sphere::new(Point3::new(0.0, 1.0, 0.0), 0.1),
+            Material::default(),
+        );
+        let mut rng = StdRng::seed_from_u64(7);
+        assert_close(
+            PathTracer::default().trace(&scene, &ray, &mut rng),
+            Colour::default(),
+        );
+    }
+
+    /// White plane at y = 0 under environment with dim sky and small bright sun.
+    /// Returns the scene, ray looking at the plane and the expected radiance.
+    fn plane_under_sun() -> (Scene, Ray, Scalar) {
+        let (width, height, sun_row) = (64, 32, 6);
+        let pixels = (0..width * height)
+            .map(|i| match (i % width, i / width) {
+                (40, row) if row == sun_row => Colour::grey(5000.0),
+                (_, row) if row < height / 2 => Colour::grey(0.5),
+                _ => Colour::grey(0.0),
+            })
+            .collect();
+        let mut scene = Scene::new(Material::default(), 1, 1);
+        scene.set_environment(EnvironmentMap::new(width, height, pixels));
+        scene.add(
+            Triangle::new([
+                Point3::new(-1e4, 0.0, -1e4),
+                Point3::new(0.0, 0.0, 1e4),
+                Point3::new(1e4, 0.0, -1e4),
+            ]),
+            Material::diffuse(Colour::grey(1.0)),
+        );
+        // Sky covering the upper hemisphere is reflected as it is and the sun
+        // adds its irradiance L Ω cos θ divided by π
+        let pi = std::f32::consts::PI;
+        let theta = pi * (sun_row as Scalar + 0.5) / height as Scalar;
+        let solid_angle = 2.0 * pi / width as Scalar * pi / height as Scalar * theta.sin();
+        let expected = 0.5 + 5000.0 * solid_angle * theta.cos() / pi;
+        let ray = Ray::new(Point3::new(0.0, 1.0, 0.0), Vector3::new(0.3, -1.0, 0.1));
+        (scene, ray, expected)
+    }
+
+    #[test]
+    fn environment_is_seen_by_escaping_rays() {
+        let (mut scene, _, _) = plane_under_sun();
+        scene.set_environment(
+            EnvironmentMap::new(1, 1, vec![Colour::grey(0.5)]).with_intensity(2.0),
+        );
+        let mut rng = StdRng::seed_from_u64(8);
+        let ray = Ray::new(Point3::new(0.0, 1.0, 0.0), Vector3::y());
+        assert_eq!(
+            PathTracer::default().trace(&scene, &ray, &mut rng),
+            Colour::grey(1.0)
+        );
+        // Plane reflects the uniform environment above it
+        let ray = Ray::new(Point3::new(0.0, 1.0, 0.0), -Vector3::y());
+        for tracer in [
+            PathTracer::default(),
+            PathTracer::default().with_light_sampling(false),
+        ] {
+            let (mean, _) = estimate(tracer, &scene, &ray, 10_000);
+            assert!((mean - 1.0).abs() < 0.01, "{:?}: {}", tracer, mean);
+        }
+    }
+
+    #[test]
+    fn environment_sampling_reduces_noise() {
+        let (scene, ray, expected) = plane_under_sun();
+        let (mean, variance) = estimate(PathTracer::default(), &scene, &ray, 10_000);
+        let (_, bsdf_variance) = estimate(
+            PathTracer::default().with_light_sampling(false),
+            &scene,
+            &ray,
+            10_000,
+        );
+        assert!(
+            (mean - expected).abs() < 0.01 * expected,
+            "{} != {}",
+            mean,
+            expected
+        );
+        assert!(
+            variance < 0.01 * bsdf_variance,
+            "{} {}",
+            variance,
+            bsdf_variance
+        );
+    }
+
+    #[test]
+    fn clear_glass_is_invisible_in_uniform_environment() {
+        // Paths entering the sphere leave it with the same radiance, as scaling
+        // by squared index of refraction cancels between entry and exit
+        let mut scene = Scene::new(Material::default(), 64, 1);
+        scene.add(
+            Sphere::new(Point3::origin(), 1.0),
+            Material::default().with_lobe(Dielectric::new(1.5)),
+        );
+        scene.set_environment(EnvironmentMap::new(1, 1, vec![Colour::grey(0.5)]));
+        let mut rng = StdRng::seed_from_u64(9);
+        for _ in 0..1000 {
+            let target = Point3::new(rng.gen_range(-0.9..0.9), rng.gen_range(-0.9..0.9), 0.0);
+            let ray = Ray::new(
+                Point3::new(0.0, 0.0, -5.0),
+                target - Point3::new(0.0, 0.0, -5.0),
+            );
+            assert_close(
+                PathTracer::new(None).trace(&scene, &ray, &mut rng),
+                Colour::grey(0.5),
+            );
+        }
+    }
+
+    #[test]
+    fn power_heuristic_weights_sum_to_one() {
+        assert_eq!(power_heuristic(1.0, 0.0), 1.0);
+        assert_eq!(power_heuristic(0.0, 0.0), 0.0);
+        assert_eq!(power_heuristic(2.0, 1.0), 0.8);
+        assert!((power_heuristic(0.3, 1.7) + power_heuristic(1.7, 0.3) - 1.0).abs() < 1e-6);
+    }
+}
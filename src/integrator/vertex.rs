@@ -0,0 +1,125 @@
+use rand::{Rng, RngCore};
+
+use crate::{Bsdf, Colour, Frame, Point2, Ray, Scalar, Scene, SceneHit, Vector3};
+
+/// Surface hit by a ray together with its shading frame
+pub(crate) struct Vertex<'a> {
+    pub hit: SceneHit<'a>,
+    pub frame: Frame,
+    /// Direction towards the previous vertex in the shading frame
+    pub wo: Vector3,
+}
+
+/// Light scattered at a vertex coming from a sampled point of a light source
+pub(crate) struct DirectLight {
+    /// Scattered light divided by the density of the sample
+    pub radiance: Colour,
+    /// Density of the light sample with respect to solid angle
+    pub light_pdf: Scalar,
+    /// Density, with which the material samples the same direction
+    pub bsdf_pdf: Scalar,
+}
+
+impl<'a> Vertex<'a> {
+    /// Shading frame follows the orientation of the surface, so BSDFs can tell
+    /// the side of the surface light arrives from
+    pub fn new(hit: SceneHit<'a>, ray: &Ray) -> Self {
+        let frame = Frame::from_normal(&hit.intersection.shading_normal);
+        let wo = frame.to_local(&-ray.direction.into_inner());
+        Self { hit, frame, wo }
+    }
+
+    /// Returns BSDF times cosine and sampling density for light arriving from
+    /// world direction, or `None` if the material does not scatter it
+    pub fn scatter(&self, direction: &Vector3) -> Option<(Colour, Scalar)> {
+        let wi = self.frame.to_local(direction);
+        if !self.is_consistent(direction, &wi) {
+            return None;
+        }
+        let material = self.hit.material;
+        let value = material.evaluate(&self.wo, &wi) * wi.z.abs();
+        if value == Colour::default() {
+            return None;
+        }
+        Some((value, material.get_pdf(&self.wo, &wi)))
+    }
+
+    /// Checks if world direction and its shading frame counterpart point
+    /// to the same side of the surface
+    pub fn is_consistent(&self, direction: &Vector3, wi: &Vector3) -> bool {
+        let side = direction.dot(&self.hit.intersection.normal);
+        side * wi.z > 0.0
+    }
+
+    /// Returns ray leaving the surface in direction
+    pub fn leaving(&self, direction: Vector3) -> Ray {
+        let intersection = &self.hit.intersection;
+        Ray::leaving_surface(&intersection.point, &intersection.normal, direction)
+    }
+
+    /// Computes light scattered at the vertex coming from punctual lights
+    pub fn get_punctual_light(&self, scene: &Scene) -> Colour {
+        let mut radiance = Colour::default();
+        for light in scene.get_lights() {
+            let incidence = match light.illuminate(&self.hit.intersection.point) {
+                Some(incidence) => incidence,
+                None => continue,
+            };
+            let direction = incidence.direction.into_inner();
+            let (value, _) = match self.scatter(&direction) {
+                Some(scattering) => scattering,
+                None => continue,
+            };
+            let origin = self.leaving(direction).origin;
+            if scene.closest_hit(&incidence.shadow_ray(&origin)).is_none() {
+                radiance += value * incidence.irradiance;
+            }
+        }
+        radiance
+    }
+
+    /// Estimates light scattered at the vertex coming directly from a point
+    /// sampled on emissive triangles, which is visible from the vertex
+    pub fn sample_area_light(&self, scene: &Scene, rng: &mut dyn RngCore) -> Option<DirectLight> {
+        let light = scene.sample_light(rng.gen(), &Point2::new(rng.gen(), rng.gen()))?;
+        let to_light = light.point - self.hit.intersection.point;
+        let distance_squared = to_light.norm_squared();
+        let direction = to_light / distance_squared.sqrt();
+        let light_cosine = direction.dot(&light.normal).abs();
+        let (value, bsdf_pdf) = match self.scatter(&direction) {
+            Some(scattering) if light_cosine > 0.0 => scattering,
+            _ => return None,
+        };
+        let origin = self.leaving(direction).origin;
+        if scene
+            .closest_hit(&Ray::between(&origin, &light.point))
+            .is_some()
+        {
+            return None;
+        }
+        let light_pdf = light.pdf * distance_squared / light_cosine;
+        Some(DirectLight {
+            radiance: value * light.emission / light_pdf,
+            light_pdf,
+            bsdf_pdf,
+        })
+    }
+
+    /// Estimates light scattered at the vertex coming from a direction sampled
+    /// on the environment, which is not blocked by the scene
+    pub fn sample_environment(&self, scene: &Scene, rng: &mut dyn RngCore) -> Option<DirectLight> {
+        let sample = scene
+            .get_environment()
+            .and_then(|environment| environment.sample(&Point2::new(rng.gen(), rng.gen())))?;
+        let direction = sample.direction.into_inner();
+        let (value, bsdf_pdf) = self.scatter(&direction)?;
+        if scene.closest_hit(&self.leaving(direction)).is_some() {
+            return None;
+        }
+        Some(DirectLight {
+            radiance: value * sample.radiance / sample.pdf,
+            light_pdf: sample.pdf,
+            bsdf_pdf,
+        })
+    }
+}
@@ -0,0 +1,278 @@
+use rand::RngCore;
+
+use crate::{
+    integrator::{vertex::Vertex, Integrator},
+    Bsdf, Colour, Ray, Scene,
+};
+
+/// Whitted-style ray tracer. Surfaces emit `Material::emission` and scatter light
+/// arriving directly from punctual lights, emissive triangles and the environment,
+/// which are connected with shadow rays. Emissive triangles and the environment
+/// are sampled at one point per hit, so the result converges as pixels get more rays.
+/// Light from other surfaces is gathered only by perfectly smooth lobes, e.g. mirrors
+/// and glass, whose reflected and refracted rays are all followed up to
+/// `Scene` recursion depth times. Rough lobes see only light sources.
+/// Rays leaving the scene see its environment or the emission of its default material.
+#[derive(Debug, PartialEq, Eq, Copy, Clone, Default)]
+pub struct WhittedTracer;
+
+impl Integrator for WhittedTracer {
+    fn trace(&self, scene: &Scene, ray: &Ray, rng: &mut dyn RngCore) -> Colour {
+        Self::trace_until(scene, ray, 0, rng)
+    }
+}
+
+impl WhittedTracer {
+    fn trace_until(scene: &Scene, ray: &Ray, bounce: usize, rng: &mut dyn RngCore) -> Colour {
+        let hit = match scene.closest_hit(ray) {
+            Some(hit) => hit,
+            None => return scene.get_background(&ray.direction),
+        };
+        let mut radiance = hit.material.emission;
+        let vertex = Vertex::new(hit, ray);
+        let material = vertex.hit.material;
+        if material.get_albedo() == Colour::default() {
+            return radiance;
+        }
+
+        radiance += vertex.get_punctual_light(scene);
+        let lights = [
+            vertex.sample_area_light(scene, rng),
+            vertex.sample_environment(scene, rng),
+        ];
+        for light in lights.iter().flatten() {
+            radiance += light.radiance;
+        }
+        if bounce == scene.get_recursion_depth() {
+            return radiance;
+        }
+        for sample in material.split_specular(&vertex.wo) {
+            let direction = vertex.frame.to_world(&sample.direction);
+            if sample.weight != Colour::default()
+                && vertex.is_consistent(&direction, &sample.direction)
+            {
+                let ray = vertex.leaving(direction);
+                radiance += sample.weight * Self::trace_until(scene, &ray, bounce + 1, rng);
+            }
+        }
+        radiance
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        integrator::testing::{assert_close, floor},
+        primitives::{Sphere, Triangle},
+        Conductor, Dielectric, EnvironmentMap, Material, Point3, PointLight, Scalar, Vector3,
+    };
+    use rand::{rngs::StdRng, SeedableRng};
+
+    /// Grey floor in the y = 0 plane under a small lamp of area 0.02
+    /// at height 2 facing it, above the origin
+    fn floor_and_lamp() -> Scene {
+        let mut scene = Scene::new(
+            Material {
+                emission: Colour::grey(0.1),
+                ..Default::default()
+            },
+            1,
+            1,
+        );
+        scene.add(floor(), Material::diffuse(Colour::grey(0.5)));
+        scene.add(
+            Triangle::new([
+                Point3::new(-0.1, 2.0, -0.1),
+                Point3::new(0.1, 2.0, -0.1),
+                Point3::new(0.0, 2.0, 0.1),
+            ]),
+            Material {
+                emission: Colour::grey(100.0),
+                ..Default::default()
+            },
+        );
+        scene
+    }
+
+    #[test]
+    fn lamp_lights_floor_through_shadow_rays() {
+        let scene = floor_and_lamp();
+        let mut rng = StdRng::seed_from_u64(0);
+        // Small lamp of radiance L and area A at height h gives radiance
+        // albedo / π L A / h² at the point below it
+        let expected = 0.5 / std::f32::consts::PI * 100.0 * 0.02 / 4.0;
+        let ray = Ray::new(Point3::new(0.0, 1.0, 1.0), Vector3::new(0.0, -1.0, -1.0));
+        let count = 1000;
+        let mut mean = 0.0;
+        for _ in 0..count {
+            mean += WhittedTracer.trace(&scene, &ray, &mut rng).red / count as Scalar;
+        }
+        assert!((mean - expected).abs() < 0.01 * expected, "{}", mean);
+        // Lamp and sky are seen directly
+        let ray = Ray::new(Point3::new(0.0, 1.0, 0.0), Vector3::y());
+        assert_eq!(
+            WhittedTracer.trace(&scene, &ray, &mut rng),
+            Colour::grey(100.0)
+        );
+        let ray = Ray::new(Point3::new(1.0, 1.0, 0.0), Vector3::y());
+        assert_eq!(
+            WhittedTracer.trace(&scene, &ray, &mut rng),
+            Colour::grey(0.1)
+        );
+    }
+
+    #[test]
+    fn blocked_lamp_casts_shadow() {
+        let mut scene = floor_and_lamp();
+        scene.add(
+            Sphere::new(Point3::new(0.0, 1.0, 0.0), 0.5),
+            Material::default(),
+        );
+        let mut rng = StdRng::seed_from_u64(1);
+        let ray = Ray::new(Point3::new(1.0, 1.0, 0.0), Vector3::new(-1.0, -1.0, 0.0));
+        for _ in 0..100 {
+            assert_close(
+                WhittedTracer.trace(&scene, &ray, &mut rng),
+                Colour::default(),
+            );
+        }
+    }
+
+    #[test]
+    fn point_light_lights_floor_through_shadow_rays() {
+        let mut scene = Scene::new(Material::default(), 1, 1);
+        scene.add(floor(), Material::diffuse(Colour::grey(0.5)));
+        scene.add_light(PointLight::new(
+            Point3::new(0.0, 2.0, 0.0),
+            Colour::grey(1.0),
+            8.0,
+        ));
+        // Light arrives at normal incidence from distance of 2
+        let ray = Ray::new(Point3::new(0.0, 1.0, 1.0), Vector3::new(0.0, -1.0, -1.0));
+        let mut rng = StdRng::seed_from_u64(2);
+        let expected = 0.5 * std::f32::consts::FRAC_1_PI * 8.0 / 4.0;
+        assert_close(
+            WhittedTracer.trace(&scene, &ray, &mut rng),
+            Colour::grey(expected),
+        );
+        scene.add(
+            Sphere::new(Point3::new(0.0, 1.0, 0.0), 0.5),
+            Material::default(),
+        );
+        assert_eq!(
+            WhittedTracer.trace(&scene, &ray, &mut rng),
+            Colour::default()
+        );
+    }
+
+    #[test]
+    fn floor_is_lit_by_environment() {
+        let mut scene = Scene::new(Material::default(), 1, 1);
+        scene.add(floor(), Material::diffuse(Colour::grey(0.5)));
+        scene.set_environment(EnvironmentMap::new(1, 1, vec![Colour::grey(2.0)]));
+        let ray = Ray::new(Point3::new(0.0, 1.0, 1.0), Vector3::new(0.0, -1.0, -1.0));
+        let mut rng = StdRng::seed_from_u64(3);
+        let count = 20_000;
+        let mut sum = 0.0;
+        for _ in 0..count {
+            sum += WhittedTracer.trace(&scene, &ray, &mut rng).green;
+        }
+        // Uniform environment is reflected by the albedo
+        let mean = sum / count as Scalar;
+        assert!((mean - 1.0).abs() < 0.03, "{}", mean);
+        // and seen directly by rays leaving the scene
+        let ray = Ray::new(Point3::new(0.0, 1.0, 0.0), Vector3::y());
+        assert_eq!(
+            WhittedTracer.trace(&scene, &ray, &mut rng),
+            Colour::grey(2.0)
+        );
+    }
+
+    /// Large mirror in the y = 0 plane and a lamp in the x = 2 plane,
+    /// which can only be seen in the mirror from the origin
+    fn mirror_and_lamp(depth: usize) -> Scene {
+        let mut scene = Scene::new(
+            Material {
+                emission: Colour::grey(0.1),
+                ..Default::default()
+            },
+            depth,
+            1,
+        );
+        scene.add(floor(), Material::default().with_lobe(Conductor::silver()));
+        scene.add(
+            Triangle::new([
+                Point3::new(2.0, -1.0, -1.0),
+                Point3::new(2.0, 1.0, 0.0),
+                Point3::new(2.0, -1.0, 1.0),
+            ]),
+            Material {
+                emission: Colour::grey(4.0),
+                ..Default::default()
+            },
+        );
+        scene
+    }
+
+    #[test]
+    fn mirror_reflects_lamp() {
+        let scene = mirror_and_lamp(1);
+        let mut rng = StdRng::seed_from_u64(0);
+        let reflectance = Conductor::silver().get_reflectance(std::f32::consts::FRAC_1_SQRT_2);
+        // Ray hits mirror at (0.5, 0, 0) and is reflected to (2, 1.5, 0), above the lamp
+        let ray = Ray::new(Point3::new(0.0, 0.5, 0.0), Vector3::new(1.0, -1.0, 0.0));
+        let colour = WhittedTracer.trace(&scene, &ray, &mut rng);
+        assert_close(colour, reflectance * 0.1);
+        // Ray hits mirror at (1.5, 0, 0) and is reflected to (2, 0.5, 0), inside the lamp
+        let ray = Ray::new(Point3::new(0.0, 1.5, 0.0), Vector3::new(1.0, -1.0, 0.0));
+        let colour = WhittedTracer.trace(&scene, &ray, &mut rng);
+        assert_close(colour, reflectance * 4.0);
+    }
+
+    #[test]
+    fn reflections_stop_at_recursion_depth() {
+        let scene = mirror_and_lamp(0);
+        let ray = Ray::new(Point3::new(0.0, 1.5, 0.0), Vector3::new(1.0, -1.0, 0.0));
+        assert_eq!(
+            WhittedTracer.trace(&scene, &ray, &mut StdRng::seed_from_u64(0)),
+            Colour::default()
+        );
+    }
+
+    #[test]
+    fn glass_reflects_and_refracts() {
+        // Glass pane in the x = 1 plane with the lamp behind it,
+        // seen at normal incidence from the origin
+        let mut scene = Scene::new(Material::default(), 1, 1);
+        scene.add(
+            Triangle::new([
+                Point3::new(1.0, -10.0, -10.0),
+                Point3::new(1.0, -10.0, 10.0),
+                Point3::new(1.0, 10.0, 0.0),
+            ]),
+            Material::default().with_lobe(Dielectric::new(1.5)),
+        );
+        scene.add(
+            Triangle::new([
+                Point3::new(2.0, -1.0, -1.0),
+                Point3::new(2.0, 1.0, 0.0),
+                Point3::new(2.0, -1.0, 1.0),
+            ]),
+            Material {
+                emission: Colour::grey(4.0),
+                ..Default::default()
+            },
+        );
+        // The pane normal points at -x, so the ray enters glass and radiance
+        // is divided by squared index of refraction
+        let ray = Ray::new(Point3::origin(), Vector3::x());
+        let colour = WhittedTracer.trace(&scene, &ray, &mut StdRng::seed_from_u64(0));
+        assert!((colour.red - 4.0 * 0.96 / 2.25).abs() < 1e-5);
+        // Ray from behind sees the lamp reflected and the outside through the pane
+        scene.set_environment(EnvironmentMap::new(1, 1, vec![Colour::grey(1.0)]));
+        let ray = Ray::new(Point3::new(1.5, 0.0, 0.0), -Vector3::x());
+        let colour = WhittedTracer.trace(&scene, &ray, &mut StdRng::seed_from_u64(0));
+        assert!((colour.red - (4.0 * 0.04 + 0.96 * 2.25)).abs() < 1e-5);
+    }
+}
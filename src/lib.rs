@@ -14,16 +14,40 @@ pub type Quaternion = nalgebra::Quaternion<Scalar>;
 // pub type Vector4 = nalgebra::Vector4<Scalar>;
 pub type Matrix4 = nalgebra::Matrix4<Scalar>;
 
+mod bvh;
+pub use bvh::Aabb;
+
+mod frame;
+pub use frame::{cosine_hemisphere_pdf, sample_cosine_hemisphere, Frame};
+
 mod ray;
-pub use ray::{Ray, RayTraceable};
+pub use ray::{Intersection, Ray, RayTraceable};
+
+mod sampler;
+pub use sampler::{HaltonSampler, Sampler, SobolSampler, StratifiedSampler, UniformSampler};
 
 mod viewport;
-pub use viewport::{Perspective3, Viewport};
+pub use viewport::{Perspective3, ScreenPoint, Viewport};
 
 pub mod primitives;
 
 mod material;
-pub use material::{Colour, Material};
+pub use material::{
+    Bsdf, BsdfSample, Colour, Conductor, Dielectric, Ggx, Lambertian, Lobe, Material, Principled,
+};
+
+mod lights;
+pub use lights::{DirectionalLight, Light, LightIncidence, LightSample, PointLight, SpotLight};
+
+mod environment;
+pub use environment::{Environment, EnvironmentMap, EnvironmentSample, Sky};
 
 mod scene;
-pub use scene::Scene;
+pub use scene::{Scene, SceneHit};
+
+pub mod integrator;
+
+mod renderer;
+pub use renderer::{Cancelled, Film, Progress, Renderer, Tile};
+
+pub mod formats;
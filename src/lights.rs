@@ -0,0 +1,412 @@
+use nalgebra::Unit;
+
+use crate::{primitives::Triangle, Colour, Point2, Point3, Ray, RayTraceable, Scalar, Vector3};
+
+/// Light emitting the same radiant `intensity` in every direction.
+/// Irradiance falls off with inverse square of the distance.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct PointLight {
+    pub position: Point3,
+    pub colour: Colour,
+    pub intensity: Scalar,
+}
+
+/// Point light shining within a cone around `direction`. Intensity is full
+/// up to `inner_angle` from the direction and falls smoothly to zero at `outer_angle`.
+/// Angles are given in radians.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct SpotLight {
+    pub position: Point3,
+    pub direction: Unit<Vector3>,
+    pub inner_angle: Scalar,
+    pub outer_angle: Scalar,
+    pub colour: Colour,
+    pub intensity: Scalar,
+}
+
+/// Infinitely distant light, like the sun, shining along `direction`.
+/// `intensity` is irradiance of surfaces facing the light.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct DirectionalLight {
+    pub direction: Unit<Vector3>,
+    pub colour: Colour,
+    pub intensity: Scalar,
+}
+
+/// Any of the punctual lights, which can be stored in scene. They have no area,
+/// so rays never hit them and they can only be reached by shadow rays.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum Light {
+    Point(PointLight),
+    Spot(SpotLight),
+    Directional(DirectionalLight),
+}
+
+/// Light arriving at a point from a punctual light
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct LightIncidence {
+    /// Direction from the point towards the light
+    pub direction: Unit<Vector3>,
+    /// Distance to the light, infinite for directional lights
+    pub distance: Scalar,
+    /// Irradiance of surface perpendicular to direction
+    pub irradiance: Colour,
+}
+
+/// Point sampled on a light source
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct LightSample {
+    pub point: Point3,
+    /// Normal of the light surface at the point
+    pub normal: Unit<Vector3>,
+    /// Radiance emitted from the point, the same to both sides of the surface
+    pub emission: Colour,
+    /// Density of sampling the point with respect to area
+    pub pdf: Scalar,
+}
+
+impl PointLight {
+    pub fn new(position: Point3, colour: Colour, intensity: Scalar) -> Self {
+        Self {
+            position,
+            colour,
+            intensity,
+        }
+    }
+}
+
+impl SpotLight {
+    pub fn new(
+        position: Point3,
+        direction: Vector3,
+        inner_angle: Scalar,
+        outer_angle: Scalar,
+        colour: Colour,
+        intensity: Scalar,
+    ) -> Self {
+        assert!(
+            0.0 <= inner_angle && inner_angle <= outer_angle && outer_angle <= std::f32::consts::PI,
+            "Spot light angles have to satisfy 0 <= inner <= outer <= π"
+        );
+        Self {
+            position,
+            direction: Unit::new_normalize(direction),
+            inner_angle,
+            outer_angle,
+            colour,
+            intensity,
+        }
+    }
+
+    /// Returns part of the intensity emitted in given direction
+    pub fn get_falloff(&self, direction: &Vector3) -> Scalar {
+        let cosine = direction.normalize().dot(&self.direction);
+        let (cos_inner, cos_outer) = (self.inner_angle.cos(), self.outer_angle.cos());
+        if cosine >= cos_inner {
+            1.0
+        } else if cosine <= cos_outer {
+            0.0
+        } else {
+            let x = (cosine - cos_outer) / (cos_inner - cos_outer);
+            x * x * (3.0 - 2.0 * x)
+        }
+    }
+}
+
+impl DirectionalLight {
+    pub fn new(direction: Vector3, colour: Colour, intensity: Scalar) -> Self {
+        Self {
+            direction: Unit::new_normalize(direction),
+            colour,
+            intensity,
+        }
+    }
+}
+
+impl Light {
+    /// Computes light arriving at point or returns `None`, if the point is not lit.
+    /// Occlusion is not checked.
+    pub fn illuminate(&self, point: &Point3) -> Option<LightIncidence> {
+        let (to_light, radiant_intensity) = match self {
+            Light::Point(light) => (light.position - point, light.colour * light.intensity),
+            Light::Spot(light) => {
+                let falloff = light.get_falloff(&(point - light.position));
+                (
+                    light.position - point,
+                    light.colour * (light.intensity * falloff),
+                )
+            }
+            Light::Directional(light) => {
+                return Some(LightIncidence {
+                    direction: -light.direction,
+                    distance: Scalar::INFINITY,
+                    irradiance: light.colour * light.intensity,
+                })
+            }
+        };
+        let (direction, distance) = Unit::try_new_and_get(to_light, 0.0)?;
+        if radiant_intensity == Colour::default() {
+            return None;
+        }
+        Some(LightIncidence {
+            direction,
+            distance,
+            irradiance: radiant_intensity / (distance * distance),
+        })
+    }
+}
+
+impl From<PointLight> for Light {
+    fn from(light: PointLight) -> Self {
+        Light::Point(light)
+    }
+}
+
+impl From<SpotLight> for Light {
+    fn from(light: SpotLight) -> Self {
+        Light::Spot(light)
+    }
+}
+
+impl From<DirectionalLight> for Light {
+    fn from(light: DirectionalLight) -> Self {
+        Light::Directional(light)
+    }
+}
+
+impl LightIncidence {
+    /// Creates ray from point to the light, which checks if light is occluded
+    pub fn shadow_ray(&self, origin: &Point3) -> Ray {
+        if self.distance.is_finite() {
+            Ray::between(
+                origin,
+                &(origin + self.direction.into_inner() * self.distance),
+            )
+        } else {
+            Ray::new(*origin, self.direction.into_inner())
+        }
+    }
+}
+
+/// Emissive triangles of the scene, which are sampled with probability
+/// proportional to their area. Every point of every light is thus sampled
+/// with the same density equal to one over the total area.
+#[derive(Debug, PartialEq, Clone, Default)]
+pub(crate) struct AreaLights {
+    triangles: Vec<(Triangle, Colour)>,
+    /// Cumulative areas of triangles
+    cdf: Vec<Scalar>,
+}
+
+impl AreaLights {
+    /// Creates lights from triangles with their emission. Triangles without area are skipped.
+    pub fn new(triangles: Vec<(Triangle, Colour)>) -> Self {
+        let triangles: Vec<_> = triangles
+            .into_iter()
+            .filter(|(triangle, _)| triangle.get_size() > 0.0)
+            .collect();
+        let cdf = triangles
+            .iter()
+            .scan(0.0, |total, (triangle, _)| {
+                *total += triangle.get_size();
+                Some(*total)
+            })
+            .collect();
+        Self { triangles, cdf }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.triangles.is_empty()
+    }
+
+    pub fn get_total_area(&self) -> Scalar {
+        self.cdf.last().copied().unwrap_or(0.0)
+    }
+
+    /// Chooses triangle with `selector` in `[0, 1)` and point on it with `u`
+    /// from the unit square, both uniformly distributed
+    pub fn sample(&self, selector: Scalar, u: &Point2) -> Option<LightSample> {
+        let total_area = self.get_total_area();
+        if self.is_empty() {
+            return None;
+        }
+        let index = self
+            .cdf
+            .partition_point(|&area| area <= selector * total_area)
+            .min(self.triangles.len() - 1);
+        let (triangle, emission) = &self.triangles[index];
+        Some(LightSample {
+            point: sample_triangle(triangle, u),
+            normal: triangle.get_normal(),
+            emission: *emission,
+            pdf: 1.0 / total_area,
+        })
+    }
+}
+
+/// Maps point of the unit square to uniformly distributed point of the triangle
+fn sample_triangle(triangle: &Triangle, u: &Point2) -> Point3 {
+    let s = u.x.sqrt();
+    let (b1, b2) = (s * (1.0 - u.y), s * u.y);
+    let [v0, v1, v2] = [triangle.get_v(0), triangle.get_v(1), triangle.get_v(2)];
+    v0 + b1 * (v1 - v0) + b2 * (v2 - v0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    fn lights() -> AreaLights {
+        AreaLights::new(vec![
+            (
+                Triangle::new([
+                    Point3::new(0.0, 0.0, 0.0),
+                    Point3::new(1.0, 0.0, 0.0),
+                    Point3::new(0.0, 1.0, 0.0),
+                ]),
+                Colour::grey(1.0),
+            ),
+            (
+                Triangle::new([
+                    Point3::new(0.0, 0.0, 5.0),
+                    Point3::new(3.0, 0.0, 5.0),
+                    Point3::new(0.0, 1.0, 5.0),
+                ]),
+                Colour::grey(2.0),
+            ),
+        ])
+    }
+
+    #[test]
+    fn point_light_falls_off_with_inverse_square_distance() {
+        let light = Light::from(PointLight::new(
+            Point3::new(0.0, 2.0, 0.0),
+            Colour::grey(0.5),
+            8.0,
+        ));
+        let incidence = light.illuminate(&Point3::origin()).unwrap();
+        assert_eq!(incidence.direction.into_inner(), Vector3::y());
+        assert_eq!(incidence.distance, 2.0);
+        assert_eq!(incidence.irradiance, Colour::grey(1.0));
+        let incidence = light.illuminate(&Point3::new(0.0, 6.0, 0.0)).unwrap();
+        assert_eq!(incidence.direction.into_inner(), -Vector3::y());
+        assert_eq!(incidence.irradiance, Colour::grey(0.25));
+        assert_eq!(light.illuminate(&Point3::new(0.0, 2.0, 0.0)), None);
+    }
+
+    #[test]
+    fn spot_light_shines_within_cone() {
+        let (inner, outer) = (20.0_f32.to_radians(), 40.0_f32.to_radians());
+        let light = Light::from(SpotLight::new(
+            Point3::new(0.0, 1.0, 0.0),
+            -Vector3::y(),
+            inner,
+            outer,
+            Colour::grey(1.0),
+            4.0,
+        ));
+        let at_angle = |angle: Scalar| {
+            let point = Point3::new(angle.tan(), 0.0, 0.0);
+            light.illuminate(&point).map(|incidence| {
+                incidence.irradiance.red * (point - Point3::new(0.0, 1.0, 0.0)).norm_squared()
+            })
+        };
+        assert_eq!(at_angle(0.0), Some(4.0));
+        assert!((at_angle(inner * 0.99).unwrap() - 4.0).abs() < 1e-4);
+        assert_eq!(at_angle(outer * 1.01), None);
+        let middle = at_angle((inner + outer) / 2.0).unwrap();
+        assert!(middle > 0.0 && middle < 4.0, "{}", middle);
+        assert!(at_angle(30.0_f32.to_radians()) > at_angle(35.0_f32.to_radians()));
+    }
+
+    #[test]
+    fn directional_light_is_the_same_everywhere() {
+        let light = Light::from(DirectionalLight::new(
+            Vector3::new(0.0, -2.0, 0.0),
+            Colour::grey(1.0),
+            3.0,
+        ));
+        for point in [Point3::origin(), Point3::new(100.0, -50.0, 3.0)] {
+            let incidence = light.illuminate(&point).unwrap();
+            assert_eq!(incidence.direction.into_inner(), Vector3::y());
+            assert_eq!(incidence.distance, Scalar::INFINITY);
+            assert_eq!(incidence.irradiance, Colour::grey(3.0));
+            let ray = incidence.shadow_ray(&point);
+            assert_eq!(ray.t_max, Scalar::INFINITY);
+        }
+    }
+
+    #[test]
+    fn shadow_ray_ends_before_light() {
+        let light = Light::from(PointLight::new(
+            Point3::new(0.0, 2.0, 0.0),
+            Colour::grey(1.0),
+            1.0,
+        ));
+        let ray = light
+            .illuminate(&Point3::origin())
+            .unwrap()
+            .shadow_ray(&Point3::origin());
+        assert!(ray.t_max < 2.0 && ray.t_max > 1.99);
+        assert_eq!(ray.direction.into_inner(), Vector3::y());
+    }
+
+    #[test]
+    fn no_lights_give_no_samples() {
+        let lights = AreaLights::new(vec![(
+            Triangle::new([Point3::origin(), Point3::origin(), Point3::origin()]),
+            Colour::grey(1.0),
+        )]);
+        assert!(lights.is_empty());
+        assert_eq!(lights.sample(0.5, &Point2::new(0.5, 0.5)), None);
+    }
+
+    #[test]
+    fn lights_are_chosen_by_area() {
+        let lights = lights();
+        assert_eq!(lights.get_total_area(), 2.0);
+        let mut rng = StdRng::seed_from_u64(0);
+        let count = 10_000;
+        let mut second = 0;
+        for _ in 0..count {
+            let sample = lights
+                .sample(rng.gen(), &Point2::new(rng.gen(), rng.gen()))
+                .unwrap();
+            assert_eq!(sample.pdf, 0.5);
+            if sample.point.z == 5.0 {
+                assert_eq!(sample.emission, Colour::grey(2.0));
+                second += 1;
+            } else {
+                assert_eq!(sample.emission, Colour::grey(1.0));
+            }
+        }
+        let fraction = second as Scalar / count as Scalar;
+        assert!((fraction - 0.75).abs() < 0.02, "{}", fraction);
+    }
+
+    #[test]
+    fn samples_are_uniform_on_triangle() {
+        let triangle = Triangle::new([
+            Point3::new(1.0, 0.0, 0.0),
+            Point3::new(3.0, 0.0, 0.0),
+            Point3::new(1.0, 0.0, 2.0),
+        ]);
+        let lights = AreaLights::new(vec![(triangle, Colour::grey(1.0))]);
+        let mut rng = StdRng::seed_from_u64(1);
+        let count = 10_000;
+        let mut centroid = Vector3::zeros();
+        for _ in 0..count {
+            let sample = lights
+                .sample(rng.gen(), &Point2::new(rng.gen(), rng.gen()))
+                .unwrap();
+            let local = triangle.local_2d_coordinates(&sample.point);
+            assert!(local.x >= -1e-6 && local.y >= -1e-6 && local.x + local.y <= 1.0 + 1e-6);
+            assert_eq!(sample.normal, triangle.get_normal());
+            centroid += sample.point.coords;
+        }
+        // Mean of uniformly distributed points is the centroid of the triangle
+        let centroid = centroid / count as Scalar;
+        assert!((centroid - Vector3::new(5.0 / 3.0, 0.0, 2.0 / 3.0)).norm() < 0.02);
+    }
+}
@@ -1,93 +1,192 @@
-use image::{ImageBuffer, Rgb};
-use nalgebra::{Isometry3, Point2, Point3, Rotation3, Similarity3, Translation3, Vector3};
+use std::error::Error;
+use std::io::IsTerminal;
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::sync::atomic::AtomicBool;
 
-use rustracer::primitives::Triangle;
-use rustracer::{Colour, Material, Scalar, Scene, Viewport};
+use clap::{Parser, ValueEnum};
+use image::{ImageFormat, RgbImage};
 
-fn main() {
-    let up_triangle = Triangle::new([
-        Point3::new(1.0, 0.0, 0.0),
-        Point3::new(0.0, 1.0, 0.0),
-        Point3::new(-1.0, 0.0, 0.0),
-    ]);
+use rustracer::formats::{LoadedScene, SceneDescription, SceneFileError};
+use rustracer::integrator::{
+    AmbientOcclusion, BeamTracer, DebugIntegrator, Integrator, PathTracer, WhittedTracer,
+};
+use rustracer::Renderer;
 
-    let world = Translation3::new(-1.0f32, -0.5, 0.0)
-        * Rotation3::new(Vector3::new(0.0f32, std::f32::consts::PI * 0.1, 0.0));
-    let mut scene = Scene::new(
-        Material {
-            #[rustfmt::skip]
-            diffuse: Colour {red: 0.0, green: 0.0, blue: 0.0,},
-            #[rustfmt::skip]
-            emission: Colour {red: 0.0, green: 0.0, blue: 0.0,},
-        },
-        5,
-        5,
-    );
-    scene.add_triangle(
-        world * up_triangle,
-        Material {
-            #[rustfmt::skip]
-            diffuse: Colour {red: 1.0, green: 1.0, blue: 0.0,},
-            #[rustfmt::skip]
-            emission: Colour {red: 1.0, green: 1.0, blue: 0.0,},
-        },
-    );
-    scene.add_triangle(
-        world
-            * Translation3::new(0.15f32, 0.15, 1.0)
-            * (Similarity3::from_scaling(0.5f32) * up_triangle),
-        Material {
-            #[rustfmt::skip]
-            diffuse: Colour {red: 1.0, green: 0.0, blue: 0.0,},
-            ..Default::default()
-        },
-    );
-    scene.add_triangle(
-        world
-            * Translation3::new(0.25f32, 0.25, 1.5)
-            * (Similarity3::from_scaling(0.25f32) * up_triangle),
-        Material {
-            #[rustfmt::skip]
-            diffuse: Colour {red: 0.2, green: 1.0, blue: 0.0,},
-            ..Default::default()
-        },
-    );
-    scene.add_triangle(
-        world
-            * Translation3::new(0.0f32, -1.0, 6.0)
-            * (Similarity3::from_scaling(2.5f32) * up_triangle),
-        Material {
-            #[rustfmt::skip]
-            diffuse: Colour {red: 1.0, green: 1.0, blue: 1.0,},
-            #[rustfmt::skip]
-            emission: Colour {red: 1.0, green: 1.0, blue: 1.0,},
-        },
-    );
+/// Renders scene described in TOML file
+#[derive(Debug, Parser)]
+#[command(name = "rustracer", version)]
+struct Options {
+    /// Scene description file
+    scene: PathBuf,
+    /// Output image path
+    #[arg(short, long, default_value = "image.png")]
+    output: PathBuf,
+    /// Output image format (png, jpeg, bmp, ppm, ...), guessed from output path by default
+    #[arg(short, long, value_parser = parse_format)]
+    format: Option<ImageFormat>,
+    /// Image width, overrides scene file
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..))]
+    width: Option<u32>,
+    /// Image height, overrides scene file
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..))]
+    height: Option<u32>,
+    /// Rays cast through every pixel, overrides scene file
+    #[arg(short, long)]
+    samples: Option<NonZeroUsize>,
+    /// Recursion depth of traced rays, overrides scene file
+    #[arg(short, long)]
+    depth: Option<usize>,
+    /// Rays in reflected beam, overrides scene file
+    #[arg(short, long)]
+    beams: Option<NonZeroUsize>,
+    /// Seed of random number generators, overrides scene file.
+    /// The same seed gives the same image for any number of threads.
+    #[arg(long)]
+    seed: Option<u64>,
+    /// Algorithm computing colours of rays
+    #[arg(short, long, value_enum, default_value_t = IntegratorKind::Beam)]
+    integrator: IntegratorKind,
+    /// Bounces of path and beam tracing, after which dark paths are terminated by Russian roulette
+    #[arg(long, default_value_t = PathTracer::DEFAULT_ROULETTE_DEPTH)]
+    roulette_depth: usize,
+    /// Disable Russian roulette, so that paths end only at recursion depth
+    #[arg(long, conflicts_with = "roulette_depth")]
+    no_roulette: bool,
+    /// Disable sampling of emissive triangles in path tracing
+    #[arg(long)]
+    no_light_sampling: bool,
+    /// Number of rendering threads, all available cores by default
+    #[arg(short = 'j', long)]
+    threads: Option<NonZeroUsize>,
+}
 
-    let mut image_data = ImageBuffer::new(800, 600);
+/// Integrators selectable from the command line
+#[derive(Debug, Copy, Clone, PartialEq, Eq, ValueEnum)]
+enum IntegratorKind {
+    /// Reflected beams of rays
+    Beam,
+    /// Direct lighting with mirror reflections and refractions
+    Whitted,
+    /// Ambient occlusion
+    Ao,
+    /// Unbiased path tracing
+    Path,
+    /// Shading normals
+    Normals,
+    /// Distance to camera, white at the far plane
+    Depth,
+    /// Local 2D coordinates of surfaces
+    Barycentrics,
+}
+
+fn parse_format(format: &str) -> Result<ImageFormat, String> {
+    // Formats are recognized by the same names as file extensions
+    ImageFormat::from_path(Path::new("image").with_extension(format))
+        .map_err(|_| format!("unknown image format '{}'", format))
+}
 
-    let viewport = Viewport::new(
-        image_data.width(),
-        image_data.height(),
-        std::f32::consts::PI / 2.0,
-        1.0,
-        1000.0,
-        100,
-    );
+impl Options {
+    /// Applies command line overrides to the scene description
+    fn apply_to(&self, description: &mut SceneDescription) {
+        if let Some(width) = self.width {
+            description.viewport.width = width;
+        }
+        if let Some(height) = self.height {
+            description.viewport.height = height;
+        }
+        if let Some(samples) = self.samples {
+            description.viewport.samples = samples.get();
+        }
+        if let Some(seed) = self.seed {
+            description.seed = seed;
+        }
+        if let Some(depth) = self.depth {
+            description.recursion_depth = depth;
+        }
+        if let Some(beams) = self.beams {
+            description.beam_rays_count = beams.get();
+        }
+    }
+
+    fn create_integrator(&self, loaded: &LoadedScene) -> Box<dyn Integrator> {
+        match self.integrator {
+            IntegratorKind::Beam => Box::new(BeamTracer::new(self.get_roulette_depth())),
+            IntegratorKind::Whitted => Box::new(WhittedTracer),
+            IntegratorKind::Ao => Box::new(AmbientOcclusion::default()),
+            IntegratorKind::Path => Box::new(
+                PathTracer::new(self.get_roulette_depth())
+                    .with_light_sampling(!self.no_light_sampling),
+            ),
+            IntegratorKind::Normals => Box::new(DebugIntegrator::Normals),
+            IntegratorKind::Depth => Box::new(DebugIntegrator::Depth {
+                max_distance: loaded.viewport.get_projection().zfar(),
+            }),
+            IntegratorKind::Barycentrics => Box::new(DebugIntegrator::Barycentrics),
+        }
+    }
 
-    let eye = Point3::new(0.0f32, 0.0, 5.0);
-    let target = Point3::new(0.0f32, 0.0, 0.0);
-    let camera = Isometry3::look_at_rh(&eye, &target, &Vector3::y()).inverse();
+    fn get_roulette_depth(&self) -> Option<usize> {
+        Some(self.roulette_depth).filter(|_| !self.no_roulette)
+    }
+
+    fn get_threads(&self) -> NonZeroUsize {
+        self.threads
+            .or_else(|| std::thread::available_parallelism().ok())
+            .unwrap_or(NonZeroUsize::MIN)
+    }
+}
+
+fn save(
+    image_data: &RgbImage,
+    path: &Path,
+    format: Option<ImageFormat>,
+) -> Result<(), Box<dyn Error>> {
+    match format {
+        Some(format) => image_data.save_with_format(path, format)?,
+        None => image_data.save(path)?,
+    }
+    Ok(())
+}
+
+fn run(options: &Options) -> Result<(), Box<dyn Error>> {
+    let in_scene = |error: SceneFileError| match error {
+        SceneFileError::Io { .. } => error.to_string(),
+        _ => format!("{}: {}", options.scene.display(), error),
+    };
+    let mut description = SceneDescription::load(&options.scene).map_err(in_scene)?;
+    options.apply_to(&mut description);
+    let base = options.scene.parent().unwrap_or_else(|| Path::new(""));
+    let loaded = description.build(base).map_err(in_scene)?;
+    let integrator = options.create_integrator(&loaded);
+    let renderer = Renderer::new(&loaded.scene, &loaded.viewport, loaded.camera)
+        .with_threads(options.get_threads())
+        .with_seed(loaded.seed)
+        .with_integrator(integrator.as_ref());
+    let show_progress = std::io::stderr().is_terminal();
+    let film = renderer.render_with(
+        |progress| {
+            if show_progress {
+                eprint!("\rRendering {:3.0}%", 100.0 * progress.get_fraction());
+            }
+        },
+        &AtomicBool::new(false),
+    )?;
+    if show_progress {
+        eprintln!();
+    }
+    save(&film.to_rgb_image(), &options.output, options.format)
+        .map_err(|e| format!("{}: {}", options.output.display(), e).into())
+}
 
-    for (x, y, pixel) in image_data.enumerate_pixels_mut() {
-        let colour = viewport
-            .cast_ray(Point2::new(x, y))
-            .map(|ray| camera * ray)
-            .map(|ray| scene.trace(&ray))
-            .fold(Colour::default(), |acc, x| acc + x)
-            / viewport.get_rays_count() as Scalar;
-        let colour: Rgb<u8> = colour.into();
-        *pixel = colour;
+fn main() -> ExitCode {
+    let options = Options::parse();
+    match run(&options) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => {
+            eprintln!("rustracer: {}", error);
+            ExitCode::FAILURE
+        }
     }
-    image_data.save("image.png").unwrap();
 }
@@ -0,0 +1,48 @@
+use crate::{Colour, Point2, Scalar, Vector3};
+
+/// Bidirectional scattering distribution function describing how a surface scatters light.
+/// Directions are given in the local shading frame with the normal along +z and point away
+/// from the surface: `wo` towards the viewer and `wi` towards the light.
+pub trait Bsdf {
+    /// Returns ratio of radiance scattered towards `wo` to irradiance arriving from `wi`.
+    /// Specular lobes, which scatter light only into discrete directions, return black.
+    fn evaluate(&self, wo: &Vector3, wi: &Vector3) -> Colour;
+
+    /// Samples direction `wi`, from which light scattered towards `wo` arrives.
+    /// `uc` chooses between scattering events, e.g. lobes or reflection and refraction,
+    /// and `u` the direction. They should be uniformly distributed in the unit interval
+    /// and the unit square.
+    fn sample(&self, wo: &Vector3, uc: Scalar, u: &Point2) -> Option<BsdfSample>;
+
+    /// Returns density with respect to solid angle, with which `sample` generates `wi`.
+    /// It is zero for specular lobes.
+    fn get_pdf(&self, wo: &Vector3, wi: &Vector3) -> Scalar;
+
+    /// Returns colour of scattered light, which is used as reflectance by integrators
+    /// tracing only mirror reflections
+    fn get_albedo(&self) -> Colour;
+
+    /// Returns all directions, into which perfectly smooth lobes scatter light arriving
+    /// from `wo`. Weights are the scattered parts of light and they are not divided
+    /// by probabilities. Rough lobes, whose light is given by `evaluate`, return none.
+    fn split_specular(&self, _wo: &Vector3) -> Vec<BsdfSample> {
+        Vec::new()
+    }
+}
+
+/// Direction sampled from BSDF
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct BsdfSample {
+    /// Direction `wi` in the local shading frame
+    pub direction: Vector3,
+    /// Value of BSDF times cosine of the direction divided by pdf
+    pub weight: Colour,
+    pub pdf: Scalar,
+    /// Direction was chosen from a discrete set, so `pdf` is not a density
+    pub specular: bool,
+}
+
+/// Returns direction mirrored to the upper hemisphere of the shading frame
+pub(crate) fn upper(w: &Vector3) -> Vector3 {
+    Vector3::new(w.x, w.y, w.z.abs())
+}
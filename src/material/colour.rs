@@ -9,6 +9,15 @@ pub struct Colour {
 }
 
 impl Colour {
+    /// Creates colour with all components equal to `value`
+    pub fn grey(value: f32) -> Self {
+        Self {
+            red: value,
+            green: value,
+            blue: value,
+        }
+    }
+
     pub fn clamped(&self) -> Self {
         let components = [self.red, self.green, self.blue];
         let norm = components
@@ -17,6 +26,11 @@ impl Colour {
             .unwrap();
         self / norm.max(1.0)
     }
+
+    /// Returns perceived brightness of linear colour with Rec. 709 primaries
+    pub fn luminance(&self) -> f32 {
+        0.2126 * self.red + 0.7152 * self.green + 0.0722 * self.blue
+    }
 }
 
 impl From<Rgb<u8>> for Colour {
@@ -29,9 +43,9 @@ impl From<Rgb<u8>> for Colour {
     }
 }
 
-impl Into<Rgb<u8>> for Colour {
-    fn into(self) -> Rgb<u8> {
-        let self_byted = self.clamped() * 255.0;
+impl From<Colour> for Rgb<u8> {
+    fn from(cl: Colour) -> Self {
+        let self_byted = cl.clamped() * 255.0;
         Rgb([
             self_byted.red as u8,
             self_byted.green as u8,
@@ -129,6 +143,12 @@ impl_op_ex!(/=|c: &mut Colour, s: &f32| {
 mod tests {
     use super::*;
 
+    #[test]
+    fn grey_colour_has_equal_components() {
+        #[rustfmt::skip]
+        assert_eq!(Colour::grey(0.5), Colour {red: 0.5, green: 0.5, blue: 0.5});
+    }
+
     #[test]
     fn clamped_colour_has_components_between_0_and_1() {
         #[rustfmt::skip]
@@ -145,6 +165,16 @@ mod tests {
         assert_eq!(Colour{red: 0.5, green: 1.0, blue: 0.75}, c.clamped());
     }
 
+    #[test]
+    fn luminance_weights_components() {
+        #[rustfmt::skip]
+        let white = Colour {red: 2.0, green: 2.0, blue: 2.0};
+        assert!((white.luminance() - 2.0).abs() < 1e-6);
+        #[rustfmt::skip]
+        let green = Colour {red: 0.0, green: 1.0, blue: 0.0};
+        assert_eq!(green.luminance(), 0.7152);
+    }
+
     #[test]
     fn colours_can_be_multiplied_by_scalar() {
         #[rustfmt::skip]
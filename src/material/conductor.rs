@@ -0,0 +1,265 @@
+use crate::{
+    material::{bsdf::upper, Bsdf, BsdfSample, Ggx},
+    Colour, Point2, Scalar, Vector3,
+};
+
+/// Metal surface with complex index of refraction `eta + i k` for red, green and blue light.
+/// Roughness of 0 makes a perfect mirror. Rougher surfaces spread reflections by GGX
+/// distribution of microfacets, whose width is the squared roughness.
+/// Anisotropy stretches reflections along the tangent of the shading frame like on brushed metal.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct Conductor {
+    pub eta: Colour,
+    pub k: Colour,
+    pub roughness: Scalar,
+    pub anisotropy: Scalar,
+}
+
+impl Conductor {
+    /// Creates perfectly smooth conductor
+    pub fn new(eta: Colour, k: Colour) -> Self {
+        Self {
+            eta,
+            k,
+            roughness: 0.0,
+            anisotropy: 0.0,
+        }
+    }
+
+    pub fn gold() -> Self {
+        Self::new(
+            Colour {
+                red: 0.143,
+                green: 0.374,
+                blue: 1.442,
+            },
+            Colour {
+                red: 3.983,
+                green: 2.385,
+                blue: 1.603,
+            },
+        )
+    }
+
+    pub fn silver() -> Self {
+        Self::new(
+            Colour {
+                red: 0.155,
+                green: 0.117,
+                blue: 0.138,
+            },
+            Colour {
+                red: 4.828,
+                green: 3.122,
+                blue: 2.147,
+            },
+        )
+    }
+
+    pub fn copper() -> Self {
+        Self::new(
+            Colour {
+                red: 0.200,
+                green: 0.924,
+                blue: 1.102,
+            },
+            Colour {
+                red: 3.912,
+                green: 2.452,
+                blue: 2.142,
+            },
+        )
+    }
+
+    pub fn aluminium() -> Self {
+        Self::new(
+            Colour {
+                red: 1.657,
+                green: 0.880,
+                blue: 0.521,
+            },
+            Colour {
+                red: 9.224,
+                green: 6.270,
+                blue: 4.837,
+            },
+        )
+    }
+
+    pub fn with_roughness(self, roughness: Scalar) -> Self {
+        assert!(
+            (0.0..=1.0).contains(&roughness),
+            "Roughness has to be between 0 and 1"
+        );
+        Self { roughness, ..self }
+    }
+
+    pub fn with_anisotropy(self, anisotropy: Scalar) -> Self {
+        assert!(
+            (0.0..=1.0).contains(&anisotropy),
+            "Anisotropy has to be between 0 and 1"
+        );
+        Self { anisotropy, ..self }
+    }
+
+    /// Returns reflectance for light arriving at angle with given cosine to the normal
+    pub fn get_reflectance(&self, cos_i: Scalar) -> Colour {
+        Colour {
+            red: fresnel_conductor(cos_i, self.eta.red, self.k.red),
+            green: fresnel_conductor(cos_i, self.eta.green, self.k.green),
+            blue: fresnel_conductor(cos_i, self.eta.blue, self.k.blue),
+        }
+    }
+
+    /// Returns distribution of microfacets or `None` for mirrors
+    fn get_distribution(&self) -> Option<Ggx> {
+        Ggx::from_roughness(self.roughness, self.anisotropy)
+    }
+}
+
+impl Bsdf for Conductor {
+    fn evaluate(&self, wo: &Vector3, wi: &Vector3) -> Colour {
+        let ggx = match self.get_distribution() {
+            Some(ggx) if wo.z * wi.z > 0.0 => ggx,
+            _ => return Colour::default(),
+        };
+        // Light is reflected on the side it arrives from
+        let (wo, wi) = (upper(wo), upper(wi));
+        let h = (wo + wi).normalize();
+        self.get_reflectance(wo.dot(&h)) * ggx.get_reflection(&wo, &wi)
+    }
+
+    fn sample(&self, wo: &Vector3, _uc: Scalar, u: &Point2) -> Option<BsdfSample> {
+        if wo.z == 0.0 {
+            return None;
+        }
+        let ggx = match self.get_distribution() {
+            Some(ggx) => ggx,
+            None => {
+                return Some(BsdfSample {
+                    direction: Vector3::new(-wo.x, -wo.y, wo.z),
+                    weight: self.get_reflectance(wo.z.abs()),
+                    pdf: 1.0,
+                    specular: true,
+                })
+            }
+        };
+        let mut direction = ggx.sample_reflection(&upper(wo), u)?;
+        direction.z *= wo.z.signum();
+        let pdf = self.get_pdf(wo, &direction);
+        if pdf <= 0.0 {
+            return None;
+        }
+        Some(BsdfSample {
+            direction,
+            weight: self.evaluate(wo, &direction) * (direction.z.abs() / pdf),
+            pdf,
+            specular: false,
+        })
+    }
+
+    fn get_pdf(&self, wo: &Vector3, wi: &Vector3) -> Scalar {
+        let ggx = match self.get_distribution() {
+            Some(ggx) if wo.z * wi.z > 0.0 => ggx,
+            _ => return 0.0,
+        };
+        ggx.get_reflection_pdf(&upper(wo), &upper(wi))
+    }
+
+    fn get_albedo(&self) -> Colour {
+        self.get_reflectance(1.0)
+    }
+
+    fn split_specular(&self, wo: &Vector3) -> Vec<BsdfSample> {
+        if self.get_distribution().is_some() {
+            return Vec::new();
+        }
+        self.sample(wo, 0.0, &Point2::origin())
+            .into_iter()
+            .collect()
+    }
+}
+
+/// Returns fraction of unpolarized light reflected by smooth conductor with
+/// complex index of refraction `eta + i k` in air, where `cos_i` is cosine of
+/// the incident angle
+fn fresnel_conductor(cos_i: Scalar, eta: Scalar, k: Scalar) -> Scalar {
+    let cos2 = cos_i * cos_i;
+    let sin2 = 1.0 - cos2;
+    let t0 = eta * eta - k * k - sin2;
+    let a2b2 = (t0 * t0 + 4.0 * eta * eta * k * k).sqrt();
+    let a = (0.5 * (a2b2 + t0)).max(0.0).sqrt();
+    let t1 = a2b2 + cos2;
+    let t2 = 2.0 * cos_i * a;
+    let perpendicular = (t1 - t2) / (t1 + t2);
+    let t3 = cos2 * a2b2 + sin2 * sin2;
+    let t4 = t2 * sin2;
+    let parallel = perpendicular * (t3 - t4) / (t3 + t4);
+    (parallel + perpendicular) / 2.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    #[test]
+    fn fresnel_reflectance_matches_known_values() {
+        // Normal incidence gives ((n - 1)² + k²) / ((n + 1)² + k²)
+        let (eta, k) = (0.2, 3.9);
+        let expected = ((eta - 1.0) * (eta - 1.0) + k * k) / ((eta + 1.0) * (eta + 1.0) + k * k);
+        assert!((fresnel_conductor(1.0, eta, k) - expected).abs() < 1e-6);
+        assert!((fresnel_conductor(0.0, eta, k) - 1.0).abs() < 1e-6);
+        // Without absorption it matches dielectric reflectance
+        assert!((fresnel_conductor(1.0, 1.5, 0.0) - 0.04).abs() < 1e-6);
+        // Gold reflects more red than blue light
+        let gold = Conductor::gold().get_albedo();
+        assert!(gold.red > 0.9 && gold.blue < 0.5);
+        let aluminium = Conductor::aluminium().get_albedo();
+        assert!(aluminium.red > 0.85 && aluminium.blue > 0.85);
+    }
+
+    #[test]
+    fn smooth_conductor_is_mirror() {
+        let copper = Conductor::copper();
+        let wo = Vector3::new(0.6, 0.0, 0.8);
+        let sample = copper.sample(&wo, 0.5, &Point2::new(0.3, 0.7)).unwrap();
+        assert_eq!(sample.direction, Vector3::new(-0.6, 0.0, 0.8));
+        assert_eq!(sample.weight, copper.get_reflectance(0.8));
+        assert!(sample.specular);
+        assert_eq!(copper.evaluate(&wo, &sample.direction), Colour::default());
+        assert_eq!(copper.get_pdf(&wo, &sample.direction), 0.0);
+        assert_eq!(copper.split_specular(&wo), vec![sample]);
+        assert!(copper.with_roughness(0.5).split_specular(&wo).is_empty());
+    }
+
+    #[test]
+    fn rough_conductor_sampling_matches_evaluation() {
+        let metal = Conductor::silver().with_roughness(0.6);
+        let wo = Vector3::new(0.5, 0.2, 0.7).normalize();
+        let mut rng = StdRng::seed_from_u64(0);
+        let count = 200_000;
+        // Reflected light estimated by importance and uniform hemisphere sampling
+        let (mut sampled, mut uniform) = (Colour::default(), Colour::default());
+        for _ in 0..count {
+            if let Some(sample) = metal.sample(&wo, rng.gen(), &Point2::new(rng.gen(), rng.gen())) {
+                assert!(sample.direction.z > 0.0);
+                let pdf = metal.get_pdf(&wo, &sample.direction);
+                assert!((sample.pdf - pdf).abs() <= 1e-3 * pdf);
+                sampled += sample.weight;
+            }
+            let z: Scalar = rng.gen();
+            let phi = 2.0 * std::f32::consts::PI * rng.gen::<Scalar>();
+            let r = (1.0 - z * z).sqrt();
+            let wi = Vector3::new(r * phi.cos(), r * phi.sin(), z);
+            uniform += metal.evaluate(&wo, &wi) * (wi.z * 2.0 * std::f32::consts::PI);
+        }
+        let (sampled, uniform) = (sampled / count as Scalar, uniform / count as Scalar);
+        assert!((sampled.green - uniform.green).abs() < 0.01);
+        assert!(sampled.green < 1.0 && sampled.green > 0.7);
+        // Light arriving from below is reflected below
+        let sample = metal.sample(&-wo, 0.5, &Point2::new(0.3, 0.6)).unwrap();
+        assert!(sample.direction.z < 0.0);
+        assert_eq!(metal.evaluate(&wo, &sample.direction), Colour::default());
+    }
+}
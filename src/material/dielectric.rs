@@ -0,0 +1,308 @@
+use crate::{
+    material::{Bsdf, BsdfSample, Ggx},
+    Colour, Point2, Scalar, Vector3,
+};
+
+/// Boundary of transparent medium with index of refraction `ior`, e.g. 1.5
+/// for glass or 1.33 for water, surrounded by air. Normal of the surface points out
+/// of the medium. Light is reflected or refracted with probabilities given by
+/// Fresnel equations and it is totally reflected inside the medium beyond
+/// the critical angle. Both reflected and refracted light is scaled by `tint`.
+/// Rough surfaces, e.g. frosted glass, spread light by GGX distribution of microfacets.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct Dielectric {
+    pub ior: Scalar,
+    pub tint: Colour,
+    pub roughness: Scalar,
+    pub anisotropy: Scalar,
+}
+
+impl Dielectric {
+    /// Creates colourless, perfectly smooth dielectric
+    pub fn new(ior: Scalar) -> Self {
+        assert!(ior > 0.0, "Index of refraction has to be positive");
+        Self {
+            ior,
+            tint: Colour::grey(1.0),
+            roughness: 0.0,
+            anisotropy: 0.0,
+        }
+    }
+
+    pub fn with_tint(self, tint: Colour) -> Self {
+        Self { tint, ..self }
+    }
+
+    pub fn with_roughness(self, roughness: Scalar) -> Self {
+        assert!(
+            (0.0..=1.0).contains(&roughness),
+            "Roughness has to be between 0 and 1"
+        );
+        Self { roughness, ..self }
+    }
+
+    /// Stretches reflections along the tangent of the shading frame
+    pub fn with_anisotropy(self, anisotropy: Scalar) -> Self {
+        assert!(
+            (0.0..=1.0).contains(&anisotropy),
+            "Anisotropy has to be between 0 and 1"
+        );
+        Self { anisotropy, ..self }
+    }
+
+    /// Returns reflected and refracted direction of the smooth surface with weights
+    /// and probabilities given by Fresnel reflectance. There is no refraction after
+    /// total internal reflection.
+    pub fn split(&self, wo: &Vector3) -> (BsdfSample, Option<BsdfSample>) {
+        let eta = self.get_eta(wo);
+        let normal = Vector3::new(0.0, 0.0, wo.z.signum());
+        let reflectance = fresnel_dielectric(wo.z.abs(), eta);
+        let reflected = BsdfSample {
+            direction: Vector3::new(-wo.x, -wo.y, wo.z),
+            weight: self.tint * reflectance,
+            pdf: reflectance,
+            specular: true,
+        };
+        let direction = match refract(wo, &normal, eta) {
+            Some(direction) if reflectance < 1.0 => direction,
+            _ => return (reflected, None),
+        };
+        // Radiance is compressed into smaller solid angle after entering denser medium
+        let refracted = BsdfSample {
+            direction,
+            weight: self.tint * ((1.0 - reflectance) / (eta * eta)),
+            pdf: 1.0 - reflectance,
+            specular: true,
+        };
+        (reflected, Some(refracted))
+    }
+
+    /// Returns ratio of the index of refraction on the other side of the surface
+    /// to the one on the side of `wo`. Light entering the medium comes from the side of the normal.
+    fn get_eta(&self, wo: &Vector3) -> Scalar {
+        if wo.z > 0.0 {
+            self.ior
+        } else {
+            1.0 / self.ior
+        }
+    }
+
+    /// Returns normal of microfacet scattering light between directions, which faces
+    /// the upper hemisphere, or `None` if no microfacet visible from both can do it
+    fn get_microfacet_normal(&self, wo: &Vector3, wi: &Vector3) -> Option<Vector3> {
+        if wo.z == 0.0 || wi.z == 0.0 {
+            return None;
+        }
+        // Generalized half vector of reflection and refraction (Walter et al., 2007)
+        let eta = if wo.z * wi.z > 0.0 {
+            1.0
+        } else {
+            self.get_eta(wo)
+        };
+        let m = wi * eta + wo;
+        if m.norm_squared() == 0.0 {
+            return None;
+        }
+        let m = m.normalize() * m.z.signum();
+        if m.dot(wi) * wi.z <= 0.0 || m.dot(wo) * wo.z <= 0.0 {
+            return None;
+        }
+        Some(m)
+    }
+}
+
+impl Bsdf for Dielectric {
+    fn evaluate(&self, wo: &Vector3, wi: &Vector3) -> Colour {
+        let ggx = match Ggx::from_roughness(self.roughness, self.anisotropy) {
+            Some(ggx) => ggx,
+            None => return Colour::default(),
+        };
+        let m = match self.get_microfacet_normal(wo, wi) {
+            Some(m) => m,
+            None => return Colour::default(),
+        };
+        let eta = self.get_eta(wo);
+        let (cos_o, cos_i) = (wo.dot(&m), wi.dot(&m));
+        let reflectance = fresnel_dielectric(cos_o.abs(), eta);
+        let value = ggx.get_density(&m) * ggx.get_masking_shadowing(wo, wi);
+        if wo.z * wi.z > 0.0 {
+            return self.tint * (value * reflectance / (4.0 * wo.z * wi.z).abs());
+        }
+        let denominator = (cos_i + cos_o / eta).powi(2) * wo.z * wi.z;
+        let transmittance = value * (1.0 - reflectance) * (cos_i * cos_o / denominator).abs();
+        self.tint * (transmittance / (eta * eta))
+    }
+
+    fn sample(&self, wo: &Vector3, uc: Scalar, u: &Point2) -> Option<BsdfSample> {
+        if wo.z == 0.0 {
+            return None;
+        }
+        let ggx = match Ggx::from_roughness(self.roughness, self.anisotropy) {
+            Some(ggx) => ggx,
+            None => {
+                let (reflected, refracted) = self.split(wo);
+                let sample = match refracted {
+                    Some(refracted) if uc >= reflected.pdf => refracted,
+                    _ => reflected,
+                };
+                return Some(BsdfSample {
+                    weight: sample.weight / sample.pdf,
+                    ..sample
+                });
+            }
+        };
+        // Microfacets facing the viewer are sampled from the upper hemisphere
+        let m = ggx.sample_normal(&(wo * wo.z.signum()), u) * wo.z.signum();
+        let cos_o = wo.dot(&m);
+        if cos_o <= 0.0 {
+            return None;
+        }
+        let eta = self.get_eta(wo);
+        let direction = if uc < fresnel_dielectric(cos_o, eta) {
+            Some(2.0 * cos_o * m - wo).filter(|wi| wi.z * wo.z > 0.0)
+        } else {
+            refract(wo, &m, eta).filter(|wi| wi.z * wo.z < 0.0)
+        }?;
+        let pdf = self.get_pdf(wo, &direction);
+        if pdf <= 0.0 {
+            return None;
+        }
+        Some(BsdfSample {
+            direction,
+            weight: self.evaluate(wo, &direction) * (direction.z.abs() / pdf),
+            pdf,
+            specular: false,
+        })
+    }
+
+    fn get_pdf(&self, wo: &Vector3, wi: &Vector3) -> Scalar {
+        let ggx = match Ggx::from_roughness(self.roughness, self.anisotropy) {
+            Some(ggx) => ggx,
+            None => return 0.0,
+        };
+        let m = match self.get_microfacet_normal(wo, wi) {
+            Some(m) => m,
+            None => return 0.0,
+        };
+        let eta = self.get_eta(wo);
+        let (cos_o, cos_i) = (wo.dot(&m), wi.dot(&m));
+        let reflectance = fresnel_dielectric(cos_o.abs(), eta);
+        let pdf = ggx.get_normal_pdf(&(wo * wo.z.signum()), &m);
+        // Jacobians of the reflection and the refraction about microfacet normal
+        if wo.z * wi.z > 0.0 {
+            pdf * reflectance / (4.0 * cos_o.abs())
+        } else {
+            pdf * (1.0 - reflectance) * cos_i.abs() / (cos_i + cos_o / eta).powi(2)
+        }
+    }
+
+    fn get_albedo(&self) -> Colour {
+        self.tint
+    }
+
+    fn split_specular(&self, wo: &Vector3) -> Vec<BsdfSample> {
+        if Ggx::from_roughness(self.roughness, self.anisotropy).is_some() {
+            return Vec::new();
+        }
+        let (reflected, refracted) = self.split(wo);
+        std::iter::once(reflected).chain(refracted).collect()
+    }
+}
+
+/// Returns direction refracted by Snell's law, where `normal` faces `wo`,
+/// or `None` after total internal reflection
+fn refract(wo: &Vector3, normal: &Vector3, eta: Scalar) -> Option<Vector3> {
+    let cos_i = wo.dot(normal);
+    let sin2_t = (1.0 - cos_i * cos_i).max(0.0) / (eta * eta);
+    if sin2_t >= 1.0 {
+        return None;
+    }
+    let cos_t = (1.0 - sin2_t).sqrt();
+    Some(-wo / eta + (cos_i / eta - cos_t) * normal)
+}
+
+/// Returns fraction of unpolarized light reflected at smooth boundary, where `cos_i`
+/// is cosine of the incident angle and `eta` is ratio of the index of refraction
+/// on the other side of the boundary to the one on the incident side
+fn fresnel_dielectric(cos_i: Scalar, eta: Scalar) -> Scalar {
+    let sin2_t = (1.0 - cos_i * cos_i).max(0.0) / (eta * eta);
+    if sin2_t >= 1.0 {
+        return 1.0;
+    }
+    let cos_t = (1.0 - sin2_t).sqrt();
+    let parallel = (eta * cos_i - cos_t) / (eta * cos_i + cos_t);
+    let perpendicular = (cos_i - eta * cos_t) / (cos_i + eta * cos_t);
+    (parallel * parallel + perpendicular * perpendicular) / 2.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn direction(sin_theta: Scalar, upper: bool) -> Vector3 {
+        let cos_theta = (1.0 - sin_theta * sin_theta).sqrt();
+        Vector3::new(sin_theta, 0.0, if upper { cos_theta } else { -cos_theta })
+    }
+
+    #[test]
+    fn fresnel_reflectance_matches_known_values() {
+        // Normal incidence gives ((n - 1) / (n + 1))² from both sides
+        assert!((fresnel_dielectric(1.0, 1.5) - 0.04).abs() < 1e-6);
+        assert!((fresnel_dielectric(1.0, 1.0 / 1.5) - 0.04).abs() < 1e-6);
+        // Brewster's angle reflects only perpendicular polarization
+        let brewster = 1.5_f32.atan();
+        let reflectance = fresnel_dielectric(brewster.cos(), 1.5);
+        let cos_t = (1.0 - (brewster.sin() / 1.5).powi(2)).sqrt();
+        let perpendicular = (brewster.cos() - 1.5 * cos_t) / (brewster.cos() + 1.5 * cos_t);
+        assert!((reflectance - perpendicular * perpendicular / 2.0).abs() < 1e-6);
+        assert_eq!(fresnel_dielectric(0.0, 1.5), 1.0);
+        // Beyond critical angle of 41.8° inside glass light is totally reflected
+        assert!(fresnel_dielectric(40.0_f32.to_radians().cos(), 1.0 / 1.5) < 1.0);
+        assert_eq!(
+            fresnel_dielectric(43.0_f32.to_radians().cos(), 1.0 / 1.5),
+            1.0
+        );
+    }
+
+    #[test]
+    fn refraction_follows_snells_law_on_both_sides() {
+        let glass = Dielectric::new(1.5);
+        // Entering from above bends towards the normal
+        let (reflected, refracted) = glass.split(&direction(0.6, true));
+        assert_eq!(reflected.direction, Vector3::new(-0.6, 0.0, 0.8));
+        let refracted = refracted.unwrap();
+        assert!((refracted.direction - -direction(0.4, true)).norm() < 1e-6);
+        assert!((reflected.pdf + refracted.pdf - 1.0).abs() < 1e-6);
+        assert!((refracted.weight.red - refracted.pdf / 2.25).abs() < 1e-6);
+        // Exiting from below bends away from the normal
+        let (_, refracted) = glass.split(&direction(0.4, false));
+        let refracted = refracted.unwrap();
+        assert!((refracted.direction - direction(-0.6, true)).norm() < 1e-6);
+        assert!((refracted.weight.red - refracted.pdf * 2.25).abs() < 1e-5);
+        // and inside beyond the critical angle it is totally reflected
+        let (reflected, refracted) = glass.split(&direction(0.7, false));
+        assert_eq!(refracted, None);
+        assert_eq!(reflected.pdf, 1.0);
+        assert!((reflected.direction - direction(-0.7, false)).norm() < 1e-6);
+    }
+
+    #[test]
+    fn sampling_chooses_reflection_with_fresnel_probability() {
+        #[rustfmt::skip]
+        let glass = Dielectric::new(1.5).with_tint(Colour {red: 1.0, green: 0.5, blue: 0.25});
+        let wo = direction(0.8, true);
+        let (reflected, refracted) = glass.split(&wo);
+        let refracted = refracted.unwrap();
+        let below = glass.sample(&wo, reflected.pdf * 0.99, &Point2::new(0.5, 0.5));
+        assert_eq!(below.unwrap().direction, reflected.direction);
+        assert_eq!(below.unwrap().weight, glass.tint);
+        let above = glass
+            .sample(&wo, reflected.pdf * 1.01, &Point2::new(0.5, 0.5))
+            .unwrap();
+        assert_eq!(above.direction, refracted.direction);
+        assert!(above.specular);
+        assert!((above.weight.green - 0.5 / 2.25).abs() < 1e-6);
+        assert_eq!(glass.evaluate(&wo, &refracted.direction), Colour::default());
+        assert_eq!(glass.get_pdf(&wo, &refracted.direction), 0.0);
+    }
+}
@@ -0,0 +1,93 @@
+use crate::{
+    cosine_hemisphere_pdf, material::BsdfSample, sample_cosine_hemisphere, Bsdf, Colour, Point2,
+    Scalar, Vector3,
+};
+
+/// Ideal diffuse reflector scattering `albedo` part of light evenly in all directions
+/// on the side of the surface, from which light arrives
+#[derive(Debug, PartialEq, Copy, Clone, Default)]
+pub struct Lambertian {
+    pub albedo: Colour,
+}
+
+impl Lambertian {
+    pub fn new(albedo: Colour) -> Self {
+        Self { albedo }
+    }
+}
+
+impl Bsdf for Lambertian {
+    fn evaluate(&self, wo: &Vector3, wi: &Vector3) -> Colour {
+        if wo.z * wi.z <= 0.0 {
+            return Colour::default();
+        }
+        self.albedo * std::f32::consts::FRAC_1_PI
+    }
+
+    fn sample(&self, wo: &Vector3, _uc: Scalar, u: &Point2) -> Option<BsdfSample> {
+        let mut direction = sample_cosine_hemisphere(u);
+        if wo.z < 0.0 {
+            direction.z = -direction.z;
+        }
+        let pdf = self.get_pdf(wo, &direction);
+        if pdf <= 0.0 {
+            return None;
+        }
+        // Cosine of the direction cancels out with the density
+        Some(BsdfSample {
+            direction,
+            weight: self.albedo,
+            pdf,
+            specular: false,
+        })
+    }
+
+    fn get_pdf(&self, wo: &Vector3, wi: &Vector3) -> Scalar {
+        if wo.z * wi.z <= 0.0 {
+            return 0.0;
+        }
+        cosine_hemisphere_pdf(&Vector3::new(wi.x, wi.y, wi.z.abs()))
+    }
+
+    fn get_albedo(&self) -> Colour {
+        self.albedo
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    #[test]
+    fn samples_match_evaluation() {
+        #[rustfmt::skip]
+        let lambertian = Lambertian::new(Colour {red: 0.5, green: 0.25, blue: 1.0});
+        let wo = Vector3::new(0.3, -0.2, 0.9).normalize();
+        let mut rng = StdRng::seed_from_u64(0);
+        for _ in 0..100 {
+            let sample = lambertian
+                .sample(&wo, rng.gen(), &Point2::new(rng.gen(), rng.gen()))
+                .unwrap();
+            let wi = sample.direction;
+            assert!(wi.z > 0.0 && (wi.norm() - 1.0).abs() < 1e-5);
+            assert!((sample.pdf - lambertian.get_pdf(&wo, &wi)).abs() < 1e-5);
+            let weight = lambertian.evaluate(&wo, &wi) * (wi.z / sample.pdf);
+            assert!((weight - sample.weight).red.abs() < 1e-5);
+            assert!((weight - sample.weight).blue.abs() < 1e-5);
+        }
+        let below = Vector3::new(0.0, 0.6, -0.8);
+        assert_eq!(lambertian.evaluate(&wo, &below), Colour::default());
+        assert_eq!(lambertian.get_pdf(&wo, &below), 0.0);
+        // Light arriving from below is reflected below
+        assert_eq!(
+            lambertian.evaluate(&-wo, &below),
+            lambertian.albedo / std::f32::consts::PI
+        );
+        let sample = lambertian
+            .sample(&-wo, 0.5, &Point2::new(0.3, 0.6))
+            .unwrap();
+        assert!(sample.direction.z < 0.0);
+        assert!((sample.pdf - lambertian.get_pdf(&-wo, &sample.direction)).abs() < 1e-6);
+    }
+}
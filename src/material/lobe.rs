@@ -0,0 +1,84 @@
+use crate::{
+    material::{Bsdf, BsdfSample, Conductor, Dielectric, Lambertian, Principled},
+    Colour, Point2, Scalar, Vector3,
+};
+
+/// Any reflectance model, which can be part of material
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum Lobe {
+    Lambertian(Lambertian),
+    Dielectric(Dielectric),
+    Conductor(Conductor),
+    Principled(Principled),
+}
+
+impl Bsdf for Lobe {
+    fn evaluate(&self, wo: &Vector3, wi: &Vector3) -> Colour {
+        match self {
+            Lobe::Lambertian(lambertian) => lambertian.evaluate(wo, wi),
+            Lobe::Dielectric(dielectric) => dielectric.evaluate(wo, wi),
+            Lobe::Conductor(conductor) => conductor.evaluate(wo, wi),
+            Lobe::Principled(principled) => principled.evaluate(wo, wi),
+        }
+    }
+
+    fn sample(&self, wo: &Vector3, uc: Scalar, u: &Point2) -> Option<BsdfSample> {
+        match self {
+            Lobe::Lambertian(lambertian) => lambertian.sample(wo, uc, u),
+            Lobe::Dielectric(dielectric) => dielectric.sample(wo, uc, u),
+            Lobe::Conductor(conductor) => conductor.sample(wo, uc, u),
+            Lobe::Principled(principled) => principled.sample(wo, uc, u),
+        }
+    }
+
+    fn get_pdf(&self, wo: &Vector3, wi: &Vector3) -> Scalar {
+        match self {
+            Lobe::Lambertian(lambertian) => lambertian.get_pdf(wo, wi),
+            Lobe::Dielectric(dielectric) => dielectric.get_pdf(wo, wi),
+            Lobe::Conductor(conductor) => conductor.get_pdf(wo, wi),
+            Lobe::Principled(principled) => principled.get_pdf(wo, wi),
+        }
+    }
+
+    fn get_albedo(&self) -> Colour {
+        match self {
+            Lobe::Lambertian(lambertian) => lambertian.get_albedo(),
+            Lobe::Dielectric(dielectric) => dielectric.get_albedo(),
+            Lobe::Conductor(conductor) => conductor.get_albedo(),
+            Lobe::Principled(principled) => principled.get_albedo(),
+        }
+    }
+
+    fn split_specular(&self, wo: &Vector3) -> Vec<BsdfSample> {
+        match self {
+            Lobe::Lambertian(lambertian) => lambertian.split_specular(wo),
+            Lobe::Dielectric(dielectric) => dielectric.split_specular(wo),
+            Lobe::Conductor(conductor) => conductor.split_specular(wo),
+            Lobe::Principled(principled) => principled.split_specular(wo),
+        }
+    }
+}
+
+impl From<Lambertian> for Lobe {
+    fn from(lambertian: Lambertian) -> Self {
+        Lobe::Lambertian(lambertian)
+    }
+}
+
+impl From<Dielectric> for Lobe {
+    fn from(dielectric: Dielectric) -> Self {
+        Lobe::Dielectric(dielectric)
+    }
+}
+
+impl From<Conductor> for Lobe {
+    fn from(conductor: Conductor) -> Self {
+        Lobe::Conductor(conductor)
+    }
+}
+
+impl From<Principled> for Lobe {
+    fn from(principled: Principled) -> Self {
+        Lobe::Principled(principled)
+    }
+}
@@ -1,7 +1,128 @@
-use crate::Colour;
+use crate::{
+    material::{Bsdf, BsdfSample, Lambertian, Lobe},
+    Colour, Point2, Scalar, Vector3,
+};
 
-#[derive(Debug, PartialEq, Copy, Clone, Default)]
+/// Surface emitting `emission` radiance, which scatters light by the sum of its lobes.
+/// Material without lobes is black.
+#[derive(Debug, PartialEq, Clone, Default)]
 pub struct Material {
     pub emission: Colour,
-    pub diffuse: Colour,
+    pub lobes: Vec<Lobe>,
+}
+
+impl Material {
+    /// Creates material with single Lambertian lobe
+    pub fn diffuse(albedo: Colour) -> Self {
+        Self::default().with_lobe(Lambertian::new(albedo))
+    }
+
+    pub fn with_emission(self, emission: Colour) -> Self {
+        Self { emission, ..self }
+    }
+
+    /// Adds lobe scattering light in addition to the existing ones
+    pub fn with_lobe<L: Into<Lobe>>(mut self, lobe: L) -> Self {
+        self.lobes.push(lobe.into());
+        self
+    }
+}
+
+/// Lobes are sampled with equal probabilities
+impl Bsdf for Material {
+    fn evaluate(&self, wo: &Vector3, wi: &Vector3) -> Colour {
+        let mut value = Colour::default();
+        for lobe in &self.lobes {
+            value += lobe.evaluate(wo, wi);
+        }
+        value
+    }
+
+    fn sample(&self, wo: &Vector3, uc: Scalar, u: &Point2) -> Option<BsdfSample> {
+        let count = self.lobes.len();
+        if count == 0 {
+            return None;
+        }
+        let index = ((uc * count as Scalar) as usize).min(count - 1);
+        let uc = (uc * count as Scalar - index as Scalar).min(1.0 - Scalar::EPSILON);
+        let sample = self.lobes[index].sample(wo, uc, u)?;
+        if count == 1 {
+            return Some(sample);
+        }
+        if sample.specular {
+            // Other lobes can not scatter light into the discrete direction
+            return Some(BsdfSample {
+                weight: sample.weight * count as Scalar,
+                pdf: sample.pdf / count as Scalar,
+                ..sample
+            });
+        }
+        let pdf = self.get_pdf(wo, &sample.direction);
+        if pdf <= 0.0 {
+            return None;
+        }
+        Some(BsdfSample {
+            weight: self.evaluate(wo, &sample.direction) * (sample.direction.z.abs() / pdf),
+            pdf,
+            ..sample
+        })
+    }
+
+    fn get_pdf(&self, wo: &Vector3, wi: &Vector3) -> Scalar {
+        if self.lobes.is_empty() {
+            return 0.0;
+        }
+        let pdf: Scalar = self.lobes.iter().map(|lobe| lobe.get_pdf(wo, wi)).sum();
+        pdf / self.lobes.len() as Scalar
+    }
+
+    fn get_albedo(&self) -> Colour {
+        let mut albedo = Colour::default();
+        for lobe in &self.lobes {
+            albedo += lobe.get_albedo();
+        }
+        albedo
+    }
+
+    fn split_specular(&self, wo: &Vector3) -> Vec<BsdfSample> {
+        self.lobes
+            .iter()
+            .flat_map(|lobe| lobe.split_specular(wo))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    #[test]
+    fn material_without_lobes_is_black() {
+        let material = Material::default().with_emission(Colour::grey(1.0));
+        let (wo, wi) = (Vector3::z(), Vector3::new(0.6, 0.0, 0.8));
+        assert_eq!(material.evaluate(&wo, &wi), Colour::default());
+        assert_eq!(material.sample(&wo, 0.5, &Point2::new(0.5, 0.5)), None);
+        assert_eq!(material.get_pdf(&wo, &wi), 0.0);
+        assert_eq!(material.get_albedo(), Colour::default());
+    }
+
+    #[test]
+    fn lobes_are_summed() {
+        let material =
+            Material::diffuse(Colour::grey(0.25)).with_lobe(Lambertian::new(Colour::grey(0.5)));
+        let single = Material::diffuse(Colour::grey(0.75));
+        assert_eq!(material.get_albedo(), Colour::grey(0.75));
+        let wo = Vector3::new(0.3, -0.2, 0.9).normalize();
+        let mut rng = StdRng::seed_from_u64(0);
+        for _ in 0..100 {
+            let sample = material
+                .sample(&wo, rng.gen(), &Point2::new(rng.gen(), rng.gen()))
+                .unwrap();
+            let wi = sample.direction;
+            assert_eq!(material.evaluate(&wo, &wi), single.evaluate(&wo, &wi));
+            assert!((sample.pdf - single.get_pdf(&wo, &wi)).abs() < 1e-5);
+            assert!((sample.weight - Colour::grey(0.75)).red.abs() < 1e-5);
+        }
+    }
 }
@@ -0,0 +1,373 @@
+use crate::{Point2, Scalar, Vector3};
+
+/// Trowbridge-Reitz (GGX) distribution of microfacet normals with widths `alpha_x`
+/// and `alpha_y` along the tangent and the bitangent of the local shading frame.
+/// Microfacet normals lie in the upper hemisphere.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct Ggx {
+    pub alpha_x: Scalar,
+    pub alpha_y: Scalar,
+}
+
+impl Ggx {
+    /// Roughness, below which surfaces are treated as perfectly smooth
+    pub const SPECULAR_ROUGHNESS: Scalar = 0.01;
+
+    /// Creates isotropic distribution
+    pub fn new(alpha: Scalar) -> Self {
+        Self::anisotropic(alpha, alpha)
+    }
+
+    pub fn anisotropic(alpha_x: Scalar, alpha_y: Scalar) -> Self {
+        assert!(
+            alpha_x > 0.0 && alpha_y > 0.0,
+            "Widths of microfacet distribution have to be positive"
+        );
+        Self { alpha_x, alpha_y }
+    }
+
+    /// Creates distribution from perceptual roughness, whose square is the width,
+    /// or returns `None` for perfectly smooth surfaces. Anisotropy between 0 and 1
+    /// stretches highlights along the tangent (Burley, 2012).
+    pub fn from_roughness(roughness: Scalar, anisotropy: Scalar) -> Option<Self> {
+        if roughness < Self::SPECULAR_ROUGHNESS {
+            return None;
+        }
+        let aspect = (1.0 - 0.9 * anisotropy).sqrt();
+        let alpha = roughness * roughness;
+        Some(Self::anisotropic(alpha / aspect, alpha * aspect))
+    }
+
+    /// Returns density of microfacets with normal `m` per unit of surface area and solid angle
+    pub fn get_density(&self, m: &Vector3) -> Scalar {
+        if m.z <= 0.0 {
+            return 0.0;
+        }
+        let x = m.x / self.alpha_x;
+        let y = m.y / self.alpha_y;
+        let t = x * x + y * y + m.z * m.z;
+        1.0 / (std::f32::consts::PI * self.alpha_x * self.alpha_y * t * t)
+    }
+
+    /// Returns fraction of microfacets visible from direction `w`
+    pub fn get_masking(&self, w: &Vector3) -> Scalar {
+        1.0 / (1.0 + self.get_lambda(w))
+    }
+
+    /// Returns fraction of microfacets visible from both directions, which
+    /// accounts for correlation of their heights (Smith model)
+    pub fn get_masking_shadowing(&self, wo: &Vector3, wi: &Vector3) -> Scalar {
+        1.0 / (1.0 + self.get_lambda(wo) + self.get_lambda(wi))
+    }
+
+    /// Samples normal of microfacet visible from direction `w` in the upper hemisphere
+    /// with density proportional to its projected area (Heitz, 2018)
+    pub fn sample_normal(&self, w: &Vector3, u: &Point2) -> Vector3 {
+        // Stretch the view, so the distribution becomes a hemisphere
+        let v = Vector3::new(self.alpha_x * w.x, self.alpha_y * w.y, w.z).normalize();
+        let length2 = v.x * v.x + v.y * v.y;
+        let t1 = if length2 > 0.0 {
+            Vector3::new(-v.y, v.x, 0.0) / length2.sqrt()
+        } else {
+            Vector3::x()
+        };
+        let t2 = v.cross(&t1);
+        // Sample the projected hemisphere as a disk with its far half squashed
+        let r = u.x.sqrt();
+        let phi = 2.0 * std::f32::consts::PI * u.y;
+        let p1 = r * phi.cos();
+        let s = 0.5 * (1.0 + v.z);
+        let p2 = (1.0 - s) * (1.0 - p1 * p1).max(0.0).sqrt() + s * r * phi.sin();
+        let n = p1 * t1 + p2 * t2 + (1.0 - p1 * p1 - p2 * p2).max(0.0).sqrt() * v;
+        Vector3::new(
+            self.alpha_x * n.x,
+            self.alpha_y * n.y,
+            n.z.max(Scalar::EPSILON),
+        )
+        .normalize()
+    }
+
+    /// Density of normals sampled by `sample_normal` for direction `w`
+    pub fn get_normal_pdf(&self, w: &Vector3, m: &Vector3) -> Scalar {
+        if w.z <= 0.0 {
+            return 0.0;
+        }
+        self.get_masking(w) * w.dot(m).max(0.0) * self.get_density(m) / w.z
+    }
+
+    /// Returns reflectance of microfacets mirroring light between directions in
+    /// the upper hemisphere, before it is multiplied by Fresnel reflectance
+    pub fn get_reflection(&self, wo: &Vector3, wi: &Vector3) -> Scalar {
+        if wo.z <= 0.0 || wi.z <= 0.0 {
+            return 0.0;
+        }
+        let h = (wo + wi).normalize();
+        self.get_density(&h) * self.get_masking_shadowing(wo, wi) / (4.0 * wo.z * wi.z)
+    }
+
+    /// Samples direction of light mirrored towards `wo` by visible microfacet.
+    /// Both directions are in the upper hemisphere.
+    pub fn sample_reflection(&self, wo: &Vector3, u: &Point2) -> Option<Vector3> {
+        let h = self.sample_normal(wo, u);
+        let wi = 2.0 * wo.dot(&h) * h - wo;
+        Some(wi).filter(|wi| wi.z > 0.0)
+    }
+
+    /// Density of directions sampled by `sample_reflection`
+    pub fn get_reflection_pdf(&self, wo: &Vector3, wi: &Vector3) -> Scalar {
+        if wo.z <= 0.0 || wi.z <= 0.0 {
+            return 0.0;
+        }
+        let h = (wo + wi).normalize();
+        // Jacobian of the reflection about microfacet normal
+        self.get_normal_pdf(wo, &h) / (4.0 * wo.dot(&h))
+    }
+
+    /// Smith auxiliary function measuring microfacet area hidden when seen from `w`
+    fn get_lambda(&self, w: &Vector3) -> Scalar {
+        let cos2_theta = w.z * w.z;
+        if cos2_theta == 0.0 {
+            return Scalar::INFINITY;
+        }
+        let x = self.alpha_x * w.x;
+        let y = self.alpha_y * w.y;
+        let alpha2_tan2_theta = (x * x + y * y) / cos2_theta;
+        ((1.0 + alpha2_tan2_theta).sqrt() - 1.0) / 2.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        material::{Bsdf, Conductor, Dielectric, Principled},
+        Colour,
+    };
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    fn spherical(cos_theta: Scalar, phi: Scalar) -> Vector3 {
+        let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+        Vector3::new(sin_theta * phi.cos(), sin_theta * phi.sin(), cos_theta)
+    }
+
+    #[test]
+    fn projected_microfacet_area_equals_macro_surface_area() {
+        // ∫ D(m) cos θ dm = 1 and ∫ D_w(m) dm = 1 by midpoint quadrature over the hemisphere
+        let w = spherical(0.6, 1.0);
+        for ggx in [Ggx::new(0.1), Ggx::new(0.5), Ggx::anisotropic(0.2, 0.8)] {
+            let (thetas, phis) = (2000, 64);
+            let d_theta = std::f32::consts::FRAC_PI_2 / thetas as Scalar;
+            let d_phi = 2.0 * std::f32::consts::PI / phis as Scalar;
+            let (mut area, mut visible) = (0.0, 0.0);
+            for i in 0..thetas {
+                let theta = (i as Scalar + 0.5) * d_theta;
+                for j in 0..phis {
+                    let m = spherical(theta.cos(), (j as Scalar + 0.5) * d_phi);
+                    let d_omega = theta.sin() * d_theta * d_phi;
+                    area += ggx.get_density(&m) * m.z * d_omega;
+                    visible += ggx.get_normal_pdf(&w, &m) * d_omega;
+                }
+            }
+            assert!((area - 1.0).abs() < 0.01, "{:?}: {}", ggx, area);
+            assert!((visible - 1.0).abs() < 0.01, "{:?}: {}", ggx, visible);
+        }
+    }
+
+    #[test]
+    fn masking_decreases_towards_horizon() {
+        let ggx = Ggx::new(0.4);
+        assert_eq!(ggx.get_masking(&Vector3::z()), 1.0);
+        let mut previous = 1.0;
+        for degrees in (10..90).step_by(10) {
+            let w = spherical((degrees as Scalar).to_radians().cos(), 0.0);
+            let masking = ggx.get_masking(&w);
+            assert!(masking < previous && masking > 0.0);
+            // Correlated heights hide less than independent masking and shadowing
+            let masking_shadowing = ggx.get_masking_shadowing(&w, &w);
+            assert!(masking_shadowing >= masking * masking && masking_shadowing <= masking);
+            previous = masking;
+        }
+        assert_eq!(ggx.get_masking(&Vector3::x()), 0.0);
+        // Anisotropic surface is rougher along the tangent
+        let ggx = Ggx::from_roughness(0.5, 0.8).unwrap();
+        assert_eq!(Ggx::from_roughness(0.0, 0.8), None);
+        assert!(ggx.get_masking(&spherical(0.3, 0.0)) < ggx.get_masking(&spherical(0.3, 1.5)));
+    }
+
+    #[test]
+    fn sampled_normals_are_visible() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let ggx = Ggx::anisotropic(0.3, 0.6);
+        for _ in 0..1000 {
+            let w = spherical(rng.gen(), rng.gen_range(0.0..2.0 * std::f32::consts::PI));
+            let m = ggx.sample_normal(&w, &Point2::new(rng.gen(), rng.gen()));
+            assert!(m.z > 0.0 && (m.norm() - 1.0).abs() < 1e-5);
+            assert!(w.dot(&m) >= -1e-5);
+        }
+    }
+
+    /// Microfacet lobes of given roughness and anisotropy
+    fn lobes(roughness: Scalar, anisotropy: Scalar) -> Vec<(&'static str, Box<dyn Bsdf>)> {
+        vec![
+            (
+                "copper",
+                Box::new(
+                    Conductor::copper()
+                        .with_roughness(roughness)
+                        .with_anisotropy(anisotropy),
+                ),
+            ),
+            (
+                "glass",
+                Box::new(
+                    Dielectric::new(1.5)
+                        .with_roughness(roughness)
+                        .with_anisotropy(anisotropy),
+                ),
+            ),
+        ]
+    }
+
+    fn directions() -> Vec<Vector3> {
+        vec![
+            spherical(0.9, 0.3),
+            spherical(0.2, 2.0),
+            spherical(-0.8, 1.0),
+            spherical(-0.3, 5.0),
+        ]
+    }
+
+    #[test]
+    fn microfacet_lobes_do_not_create_energy() {
+        let mut rng = StdRng::seed_from_u64(1);
+        for (roughness, anisotropy) in [(0.1, 0.0), (0.9, 0.0), (0.5, 0.9)] {
+            for (name, lobe) in lobes(roughness, anisotropy) {
+                for wo in directions() {
+                    let count = 10_000;
+                    let mut albedo = 0.0;
+                    for _ in 0..count {
+                        let u = Point2::new(rng.gen(), rng.gen());
+                        if let Some(sample) = lobe.sample(&wo, rng.gen(), &u) {
+                            let mut weight = sample.weight.green;
+                            if wo.z * sample.direction.z < 0.0 {
+                                // Undo compression of radiance by refraction
+                                let eta: Scalar = if wo.z > 0.0 { 1.5 } else { 1.0 / 1.5 };
+                                weight *= eta * eta;
+                            }
+                            assert!(weight >= 0.0 && weight.is_finite());
+                            albedo += weight;
+                        }
+                    }
+                    let albedo = albedo / count as Scalar;
+                    assert!(albedo <= 1.0, "{} {:?}: {}", name, wo, albedo);
+                    // Single scattering loses little energy from smooth surfaces
+                    if roughness <= 0.1 && name == "glass" {
+                        assert!(albedo > 0.97, "{} {:?}: {}", name, wo, albedo);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Upper quantile of the chi-square distribution with significance 0.001
+    /// by Wilson-Hilferty approximation
+    fn chi_square_threshold(degrees: usize) -> Scalar {
+        let k = degrees as Scalar;
+        let z = 3.09;
+        k * (1.0 - 2.0 / (9.0 * k) + z * (2.0 / (9.0 * k)).sqrt()).powi(3)
+    }
+
+    #[test]
+    fn sampling_matches_pdf_and_evaluation() {
+        // Pearson's test comparing histogram of samples over the sphere with
+        // the probabilities of its cells integrated from pdf
+        let (thetas, phis, sub) = (16, 32, 10);
+        let count = 40_000;
+        let mut rng = StdRng::seed_from_u64(2);
+        for (roughness, anisotropy) in [(0.4, 0.0), (0.6, 0.8)] {
+            let mut lobes = lobes(roughness, anisotropy);
+            if anisotropy == 0.0 {
+                // Clear coat is left out as its narrow lobe is not resolved by the cells
+                let principled = Principled::new(Colour {
+                    red: 0.9,
+                    green: 0.6,
+                    blue: 0.3,
+                })
+                .with_roughness(roughness)
+                .with_metallic(0.3)
+                .with_sheen(0.5)
+                .with_transmission(0.5);
+                lobes.push(("principled", Box::new(principled)));
+            }
+            for (name, lobe) in lobes {
+                for wo in directions() {
+                    let cell = |w: &Vector3| {
+                        let theta =
+                            (((1.0 - w.z) / 2.0 * thetas as Scalar) as usize).min(thetas - 1);
+                        let phi = w.y.atan2(w.x).rem_euclid(2.0 * std::f32::consts::PI);
+                        let phi = ((phi / (2.0 * std::f32::consts::PI) * phis as Scalar) as usize)
+                            .min(phis - 1);
+                        theta * phis + phi
+                    };
+                    let mut observed = vec![0.0; thetas * phis];
+                    for _ in 0..count {
+                        let u = Point2::new(rng.gen(), rng.gen());
+                        let sample = match lobe.sample(&wo, rng.gen(), &u) {
+                            Some(sample) => sample,
+                            None => continue,
+                        };
+                        let wi = sample.direction;
+                        let pdf = lobe.get_pdf(&wo, &wi);
+                        assert!((sample.pdf - pdf).abs() <= 1e-3 * pdf, "{}", name);
+                        let weight = lobe.evaluate(&wo, &wi) * (wi.z.abs() / pdf);
+                        let difference: Colour = sample.weight - weight;
+                        assert!(difference.red.abs() <= 1e-3 * weight.red.max(1.0));
+                        observed[cell(&wi)] += 1.0;
+                    }
+                    // Cells are uniform in cosine of θ and in φ, so they have equal solid angles
+                    let d_omega =
+                        4.0 * std::f32::consts::PI / (thetas * phis * sub * sub) as Scalar;
+                    let mut expected = vec![0.0; thetas * phis];
+                    for i in 0..thetas * sub {
+                        let cos_theta = 1.0 - 2.0 * (i as Scalar + 0.5) / (thetas * sub) as Scalar;
+                        for j in 0..phis * sub {
+                            let phi = 2.0 * std::f32::consts::PI * (j as Scalar + 0.5)
+                                / (phis * sub) as Scalar;
+                            let wi = spherical(cos_theta, phi);
+                            expected[cell(&wi)] +=
+                                lobe.get_pdf(&wo, &wi) * d_omega * count as Scalar;
+                        }
+                    }
+                    // Cells with few expected samples are pooled together
+                    let (mut statistic, mut degrees) = (0.0, 0);
+                    let (mut pooled_observed, mut pooled_expected) = (0.0, 0.0);
+                    for (o, e) in observed.iter().zip(&expected) {
+                        if *e < 5.0 {
+                            pooled_observed += o;
+                            pooled_expected += e;
+                        } else {
+                            statistic += (o - e) * (o - e) / e;
+                            degrees += 1;
+                        }
+                    }
+                    if pooled_expected >= 5.0 {
+                        let difference = pooled_observed - pooled_expected;
+                        statistic += difference * difference / pooled_expected;
+                        degrees += 1;
+                    }
+                    let threshold = chi_square_threshold(degrees - 1);
+                    assert!(
+                        statistic < threshold,
+                        "{} {:?} roughness {} anisotropy {}: {} >= {}",
+                        name,
+                        wo,
+                        roughness,
+                        anisotropy,
+                        statistic,
+                        threshold
+                    );
+                }
+            }
+        }
+    }
+}
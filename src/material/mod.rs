@@ -1,6 +1,27 @@
+mod bsdf;
+pub use bsdf::{Bsdf, BsdfSample};
+
 mod colour;
 pub use colour::Colour;
 
+mod conductor;
+pub use conductor::Conductor;
+
+mod dielectric;
+pub use dielectric::Dielectric;
+
+mod lambertian;
+pub use lambertian::Lambertian;
+
+mod lobe;
+pub use lobe::Lobe;
+
+mod microfacet;
+pub use microfacet::Ggx;
+
+mod principled;
+pub use principled::Principled;
+
 #[allow(clippy::module_inception)]
 mod material;
 pub use material::Material;
@@ -0,0 +1,421 @@
+use crate::{
+    cosine_hemisphere_pdf,
+    material::{bsdf::upper, Bsdf, BsdfSample, Dielectric, Ggx},
+    sample_cosine_hemisphere, Colour, Point2, Scalar, Vector3,
+};
+
+/// Material driven by artist friendly parameters in the spirit of Disney principled
+/// BSDF (Burley, 2012). It mixes diffuse base with white sheen at grazing angles,
+/// specular reflection, rough transmission and clear coat. Parameters other than
+/// colours are between 0 and 1. Metals reflect light tinted by `base_colour`.
+/// Dielectrics reflect `0.08 * specular` of light at normal incidence, tinted towards
+/// the base colour by `specular_tint`, and transmit it through the surface with
+/// index of refraction matching that reflectance.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct Principled {
+    pub base_colour: Colour,
+    pub metallic: Scalar,
+    pub roughness: Scalar,
+    pub specular: Scalar,
+    pub specular_tint: Scalar,
+    pub clearcoat: Scalar,
+    pub sheen: Scalar,
+    pub transmission: Scalar,
+}
+
+impl Principled {
+    /// Roughness of the clear coat
+    const CLEARCOAT_ROUGHNESS: Scalar = 0.1;
+
+    /// Creates rough dielectric with 4% reflectance at normal incidence
+    pub fn new(base_colour: Colour) -> Self {
+        Self {
+            base_colour,
+            metallic: 0.0,
+            roughness: 0.5,
+            specular: 0.5,
+            specular_tint: 0.0,
+            clearcoat: 0.0,
+            sheen: 0.0,
+            transmission: 0.0,
+        }
+    }
+
+    pub fn with_metallic(self, metallic: Scalar) -> Self {
+        assert_unit(metallic, "Metallic");
+        Self { metallic, ..self }
+    }
+
+    pub fn with_roughness(self, roughness: Scalar) -> Self {
+        assert_unit(roughness, "Roughness");
+        Self { roughness, ..self }
+    }
+
+    pub fn with_specular(self, specular: Scalar) -> Self {
+        assert_unit(specular, "Specular");
+        Self { specular, ..self }
+    }
+
+    pub fn with_specular_tint(self, specular_tint: Scalar) -> Self {
+        assert_unit(specular_tint, "Specular tint");
+        Self {
+            specular_tint,
+            ..self
+        }
+    }
+
+    pub fn with_clearcoat(self, clearcoat: Scalar) -> Self {
+        assert_unit(clearcoat, "Clear coat");
+        Self { clearcoat, ..self }
+    }
+
+    pub fn with_sheen(self, sheen: Scalar) -> Self {
+        assert_unit(sheen, "Sheen");
+        Self { sheen, ..self }
+    }
+
+    pub fn with_transmission(self, transmission: Scalar) -> Self {
+        assert_unit(transmission, "Transmission");
+        Self {
+            transmission,
+            ..self
+        }
+    }
+
+    /// Returns reflectance of the specular component at normal incidence
+    pub fn get_specular_colour(&self) -> Colour {
+        let white = Colour::grey(1.0);
+        let luminance = self.base_colour.luminance();
+        let tint = if luminance > 0.0 {
+            self.base_colour / luminance
+        } else {
+            white
+        };
+        let dielectric = lerp(&white, &tint, self.specular_tint) * (0.08 * self.specular);
+        lerp(&dielectric, &self.base_colour, self.metallic)
+    }
+
+    /// Returns weights of diffuse, specular, transmission and clear coat components,
+    /// which are also proportional to their sampling probabilities
+    fn get_weights(&self) -> [Scalar; 4] {
+        let dielectric = 1.0 - self.metallic;
+        [
+            dielectric * (1.0 - self.transmission),
+            1.0 - dielectric * self.transmission,
+            dielectric * self.transmission,
+            0.25 * self.clearcoat,
+        ]
+    }
+
+    /// Returns transmitting component
+    fn get_dielectric(&self) -> Dielectric {
+        let f0 = (0.08 * self.specular).sqrt();
+        Dielectric::new((1.0 + f0) / (1.0 - f0))
+            .with_tint(self.base_colour)
+            .with_roughness(self.roughness)
+    }
+
+    /// Returns diffuse reflectance with retro-reflection of rough surfaces and sheen
+    fn get_diffuse(&self, wo: &Vector3, wi: &Vector3, cos_d: Scalar) -> Colour {
+        let fd90 = 0.5 + 2.0 * self.roughness * cos_d * cos_d;
+        let fo = 1.0 + (fd90 - 1.0) * (1.0 - wo.z).powi(5);
+        let fi = 1.0 + (fd90 - 1.0) * (1.0 - wi.z).powi(5);
+        let sheen = self.sheen * (1.0 - cos_d).powi(5);
+        self.base_colour * (fo * fi * std::f32::consts::FRAC_1_PI) + Colour::grey(sheen)
+    }
+}
+
+impl Default for Principled {
+    fn default() -> Self {
+        Self::new(Colour::grey(0.8))
+    }
+}
+
+impl Bsdf for Principled {
+    fn evaluate(&self, wo: &Vector3, wi: &Vector3) -> Colour {
+        let [diffuse, specular, transmission, clearcoat] = self.get_weights();
+        let mut value = Colour::default();
+        if transmission > 0.0 {
+            value += self.get_dielectric().evaluate(wo, wi) * transmission;
+        }
+        if wo.z * wi.z <= 0.0 {
+            return value;
+        }
+        // Other components reflect light on the side it arrives from
+        let (wo, wi) = (upper(wo), upper(wi));
+        let cos_d = wi.dot(&(wo + wi).normalize());
+        if diffuse > 0.0 {
+            value += self.get_diffuse(&wo, &wi, cos_d) * diffuse;
+        }
+        if let Some(ggx) = Ggx::from_roughness(self.roughness, 0.0) {
+            let fresnel = schlick(&self.get_specular_colour(), cos_d);
+            value += fresnel * (ggx.get_reflection(&wo, &wi) * specular);
+        }
+        if clearcoat > 0.0 {
+            let ggx = Ggx::from_roughness(Self::CLEARCOAT_ROUGHNESS, 0.0).unwrap();
+            value +=
+                schlick(&Colour::grey(0.04), cos_d) * (ggx.get_reflection(&wo, &wi) * clearcoat);
+        }
+        value
+    }
+
+    fn sample(&self, wo: &Vector3, uc: Scalar, u: &Point2) -> Option<BsdfSample> {
+        if wo.z == 0.0 {
+            return None;
+        }
+        let weights = self.get_weights();
+        let total: Scalar = weights.iter().sum();
+        let (component, uc) = choose(&weights, uc * total)?;
+        let probability = weights[component] / total;
+        // Reflections are sampled above the surface and moved to the side of `wo`
+        let on_side = |mut direction: Vector3| {
+            direction.z *= wo.z.signum();
+            direction
+        };
+        let direction = match component {
+            0 => on_side(sample_cosine_hemisphere(u)),
+            1 => match Ggx::from_roughness(self.roughness, 0.0) {
+                Some(ggx) => on_side(ggx.sample_reflection(&upper(wo), u)?),
+                None => {
+                    let fresnel = schlick(&self.get_specular_colour(), wo.z.abs());
+                    return Some(BsdfSample {
+                        direction: Vector3::new(-wo.x, -wo.y, wo.z),
+                        weight: fresnel * (weights[component] / probability),
+                        pdf: probability,
+                        specular: true,
+                    });
+                }
+            },
+            2 => {
+                let sample = self.get_dielectric().sample(wo, uc, u)?;
+                if sample.specular {
+                    return Some(BsdfSample {
+                        weight: sample.weight * (weights[component] / probability),
+                        pdf: sample.pdf * probability,
+                        ..sample
+                    });
+                }
+                sample.direction
+            }
+            _ => on_side(
+                Ggx::from_roughness(Self::CLEARCOAT_ROUGHNESS, 0.0)
+                    .unwrap()
+                    .sample_reflection(&upper(wo), u)?,
+            ),
+        };
+        let pdf = self.get_pdf(wo, &direction);
+        if pdf <= 0.0 {
+            return None;
+        }
+        Some(BsdfSample {
+            direction,
+            weight: self.evaluate(wo, &direction) * (direction.z.abs() / pdf),
+            pdf,
+            specular: false,
+        })
+    }
+
+    fn get_pdf(&self, wo: &Vector3, wi: &Vector3) -> Scalar {
+        let [diffuse, specular, transmission, clearcoat] = self.get_weights();
+        let total = diffuse + specular + transmission + clearcoat;
+        if total <= 0.0 {
+            return 0.0;
+        }
+        let mut pdf = 0.0;
+        if transmission > 0.0 {
+            pdf += self.get_dielectric().get_pdf(wo, wi) * transmission;
+        }
+        if wo.z * wi.z > 0.0 {
+            let (wo, wi) = (upper(wo), upper(wi));
+            pdf += cosine_hemisphere_pdf(&wi) * diffuse;
+            if let Some(ggx) = Ggx::from_roughness(self.roughness, 0.0) {
+                pdf += ggx.get_reflection_pdf(&wo, &wi) * specular;
+            }
+            let ggx = Ggx::from_roughness(Self::CLEARCOAT_ROUGHNESS, 0.0).unwrap();
+            pdf += ggx.get_reflection_pdf(&wo, &wi) * clearcoat;
+        }
+        pdf / total
+    }
+
+    fn get_albedo(&self) -> Colour {
+        let [diffuse, specular, transmission, _] = self.get_weights();
+        self.base_colour * (diffuse + transmission) + self.get_specular_colour() * specular
+    }
+
+    fn split_specular(&self, wo: &Vector3) -> Vec<BsdfSample> {
+        if Ggx::from_roughness(self.roughness, 0.0).is_some() || wo.z == 0.0 {
+            return Vec::new();
+        }
+        let [_, specular, transmission, _] = self.get_weights();
+        let mut samples = Vec::new();
+        if specular > 0.0 {
+            samples.push(BsdfSample {
+                direction: Vector3::new(-wo.x, -wo.y, wo.z),
+                weight: schlick(&self.get_specular_colour(), wo.z.abs()) * specular,
+                pdf: 1.0,
+                specular: true,
+            });
+        }
+        if transmission > 0.0 {
+            samples.extend(
+                self.get_dielectric()
+                    .split_specular(wo)
+                    .into_iter()
+                    .map(|sample| BsdfSample {
+                        weight: sample.weight * transmission,
+                        ..sample
+                    }),
+            );
+        }
+        samples
+    }
+}
+
+fn assert_unit(value: Scalar, name: &str) {
+    assert!(
+        (0.0..=1.0).contains(&value),
+        "{} has to be between 0 and 1",
+        name
+    );
+}
+
+fn lerp(a: &Colour, b: &Colour, t: Scalar) -> Colour {
+    a * (1.0 - t) + b * t
+}
+
+/// Schlick's approximation of Fresnel reflectance with reflectance `f0` at normal incidence
+fn schlick(f0: &Colour, cos_i: Scalar) -> Colour {
+    f0 + (Colour::grey(1.0) - f0) * (1.0 - cos_i).max(0.0).powi(5)
+}
+
+/// Returns index of the interval of `u` when `weights` are laid one after another
+/// and `u` remapped to the unit interval within it
+fn choose(weights: &[Scalar], mut u: Scalar) -> Option<(usize, Scalar)> {
+    let mut chosen = None;
+    for (index, weight) in weights.iter().enumerate() {
+        if *weight <= 0.0 {
+            continue;
+        }
+        chosen = Some((index, (u / weight).min(1.0 - Scalar::EPSILON)));
+        if u < *weight {
+            break;
+        }
+        u -= weight;
+    }
+    chosen
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    fn colour(red: Scalar, green: Scalar, blue: Scalar) -> Colour {
+        Colour { red, green, blue }
+    }
+
+    #[test]
+    fn smooth_metal_reflects_base_colour() {
+        let gold = Principled::new(colour(1.0, 0.8, 0.3))
+            .with_metallic(1.0)
+            .with_roughness(0.0);
+        let sample = gold
+            .sample(&Vector3::z(), 0.5, &Point2::new(0.3, 0.7))
+            .unwrap();
+        assert_eq!(sample.direction, Vector3::z());
+        assert!(sample.specular);
+        assert!((sample.weight - colour(1.0, 0.8, 0.3)).blue.abs() < 1e-6);
+        assert_eq!(gold.get_albedo(), colour(1.0, 0.8, 0.3));
+        // Dielectrics reflect 4% of light, tinted towards the base colour on demand
+        let plastic = Principled::new(colour(0.5, 0.0, 0.0));
+        assert!(
+            (plastic.get_specular_colour() - colour(0.04, 0.04, 0.04))
+                .red
+                .abs()
+                < 1e-6
+        );
+        let tinted = plastic.with_specular_tint(1.0).get_specular_colour();
+        assert!(tinted.red > 0.04 && tinted.green == 0.0);
+    }
+
+    #[test]
+    fn smooth_glass_splits_into_reflection_and_refraction() {
+        let wo = Vector3::new(0.6, 0.0, 0.8);
+        let glass = Principled::new(colour(1.0, 1.0, 1.0))
+            .with_roughness(0.0)
+            .with_transmission(1.0);
+        let samples = glass.split_specular(&wo);
+        assert_eq!(samples.len(), 2);
+        assert_eq!(samples[0].direction, Vector3::new(-0.6, 0.0, 0.8));
+        assert!(samples[1].direction.z < 0.0);
+        // Half transparent plastic reflects also by its specular component
+        let plastic = glass.with_transmission(0.5).split_specular(&wo);
+        assert_eq!(plastic.len(), 3);
+        assert!(Principled::default().split_specular(&wo).is_empty());
+    }
+
+    #[test]
+    fn transmission_replaces_diffuse_base() {
+        let wo = Vector3::new(0.3, 0.2, 0.9).normalize();
+        let wi = Vector3::new(-0.4, 0.1, -0.8).normalize();
+        let base = colour(0.9, 0.9, 0.5);
+        let glass = Principled::new(base).with_transmission(1.0);
+        let dielectric = Dielectric::new(1.5).with_tint(base).with_roughness(0.5);
+        let difference = glass.evaluate(&wo, &wi) - dielectric.evaluate(&wo, &wi);
+        assert!(difference.blue.abs() < 1e-6);
+        let pdf = dielectric.get_pdf(&wo, &wi);
+        assert!((glass.get_pdf(&wo, &wi) - pdf).abs() <= 1e-4 * pdf);
+        // Opaque material does not transmit light
+        let opaque = Principled::new(base);
+        assert_eq!(opaque.evaluate(&wo, &wi), Colour::default());
+        assert_eq!(opaque.get_pdf(&wo, &wi), 0.0);
+    }
+
+    #[test]
+    fn sampling_matches_evaluation() {
+        let materials = [
+            Principled::default(),
+            Principled::new(colour(0.2, 0.5, 0.9))
+                .with_metallic(0.4)
+                .with_roughness(0.3)
+                .with_clearcoat(0.3)
+                .with_sheen(0.5),
+            Principled::new(colour(0.9, 0.9, 0.9))
+                .with_roughness(0.6)
+                .with_specular(0.8)
+                .with_transmission(0.7),
+        ];
+        let mut rng = StdRng::seed_from_u64(0);
+        let count = 30_000;
+        for material in &materials {
+            for wo in [Vector3::new(0.5, 0.2, 0.7), Vector3::new(0.1, -0.6, -0.3)] {
+                let wo = wo.normalize();
+                // Scattered light estimated by importance and uniform sphere sampling
+                let (mut sampled, mut uniform) = (0.0, 0.0);
+                for _ in 0..count {
+                    let u = Point2::new(rng.gen(), rng.gen());
+                    if let Some(sample) = material.sample(&wo, rng.gen(), &u) {
+                        let pdf = material.get_pdf(&wo, &sample.direction);
+                        assert!((sample.pdf - pdf).abs() <= 1e-3 * pdf);
+                        sampled += sample.weight.green;
+                    }
+                    let z: Scalar = rng.gen_range(-1.0..1.0);
+                    let phi = 2.0 * std::f32::consts::PI * rng.gen::<Scalar>();
+                    let r = (1.0 - z * z).sqrt();
+                    let wi = Vector3::new(r * phi.cos(), r * phi.sin(), z);
+                    let value = material.evaluate(&wo, &wi).green;
+                    uniform += value * z.abs() * 4.0 * std::f32::consts::PI;
+                }
+                let (sampled, uniform) = (sampled / count as Scalar, uniform / count as Scalar);
+                assert!(
+                    (sampled - uniform).abs() < 0.03,
+                    "{:?} {:?}: {} != {}",
+                    material,
+                    wo,
+                    sampled,
+                    uniform
+                );
+            }
+        }
+    }
+}
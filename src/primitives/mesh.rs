@@ -0,0 +1,599 @@
+use auto_ops::*;
+use nalgebra::{Unit, U3};
+
+use crate::{
+    bvh::Bvh, primitives::Triangle, ray::RayTraceable, Aabb, Intersection, Isometry3, Material,
+    Matrix3, Matrix4, Point2, Point3, Ray, Rotation3, Scalar, Similarity3, Transform3,
+    Translation3, Vector3,
+};
+
+/// Tolerance of barycentric coordinates when looking for face containing a point
+const BARYCENTRIC_TOLERANCE: Scalar = 1e-4;
+
+/// A triangle mesh primitive with vertices shared between faces.
+/// Faces are triples of indices into vertex buffer. Vertex normals
+/// and texture coordinates are optional and indexed the same as vertices.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Mesh {
+    vertices: Vec<Point3>,
+    faces: Vec<[u32; 3]>,
+    normals: Option<Vec<Unit<Vector3>>>,
+    uvs: Option<Vec<Point2>>,
+    materials: Vec<Material>,
+    /// Index into `materials` for every face. Empty, if mesh has no per-face materials.
+    face_materials: Vec<u32>,
+    bvh: Bvh,
+}
+
+impl Mesh {
+    /// Creates mesh from vertex and index buffers.
+    ///
+    /// # Panics
+    /// Panics if any face refers to a vertex out of vertex buffer.
+    pub fn new(vertices: Vec<Point3>, faces: Vec<[u32; 3]>) -> Self {
+        assert!(
+            faces
+                .iter()
+                .flatten()
+                .all(|&i| (i as usize) < vertices.len()),
+            "mesh face refers to nonexistent vertex"
+        );
+        let mut mesh = Self {
+            vertices,
+            faces,
+            normals: None,
+            uvs: None,
+            materials: Vec::new(),
+            face_materials: Vec::new(),
+            bvh: Bvh::default(),
+        };
+        mesh.build_bvh();
+        mesh
+    }
+
+    /// Returns mesh with per-vertex normals used for shading.
+    ///
+    /// # Panics
+    /// Panics if number of normals differs from number of vertices.
+    pub fn with_normals(self, normals: Vec<Vector3>) -> Self {
+        assert_eq!(normals.len(), self.vertices.len(), "one normal per vertex");
+        Self {
+            normals: Some(normals.into_iter().map(Unit::new_normalize).collect()),
+            ..self
+        }
+    }
+
+    /// Returns mesh with per-vertex texture coordinates.
+    ///
+    /// # Panics
+    /// Panics if number of coordinates differs from number of vertices.
+    pub fn with_uvs(self, uvs: Vec<Point2>) -> Self {
+        assert_eq!(uvs.len(), self.vertices.len(), "one uv per vertex");
+        Self {
+            uvs: Some(uvs),
+            ..self
+        }
+    }
+
+    /// Returns mesh with material assigned to every face.
+    /// `face_materials` contains index into `materials` for each face.
+    /// Per-face materials take precedence over material mesh is added with to the scene.
+    ///
+    /// # Panics
+    /// Panics if there is not exactly one valid material index per face.
+    pub fn with_face_materials(self, materials: Vec<Material>, face_materials: Vec<u32>) -> Self {
+        assert_eq!(
+            face_materials.len(),
+            self.faces.len(),
+            "one material per face"
+        );
+        assert!(
+            face_materials
+                .iter()
+                .all(|&i| (i as usize) < materials.len()),
+            "mesh face refers to nonexistent material"
+        );
+        Self {
+            materials,
+            face_materials,
+            ..self
+        }
+    }
+
+    pub fn get_vertices(&self) -> &[Point3] {
+        &self.vertices
+    }
+
+    pub fn get_faces(&self) -> &[[u32; 3]] {
+        &self.faces
+    }
+
+    pub fn get_normals(&self) -> Option<&[Unit<Vector3>]> {
+        self.normals.as_deref()
+    }
+
+    pub fn get_uvs(&self) -> Option<&[Point2]> {
+        self.uvs.as_deref()
+    }
+
+    /// Returns material of face, if mesh has per-face materials
+    pub fn get_face_material(&self, face: usize) -> Option<&Material> {
+        self.face_materials
+            .get(face)
+            .map(|&i| &self.materials[i as usize])
+    }
+
+    /// Returns face as a standalone triangle
+    pub fn get_triangle(&self, face: usize) -> Triangle {
+        let [a, b, c] = self.face_vertices(face);
+        Triangle::new([*a, *b, *c])
+    }
+
+    fn face_vertices(&self, face: usize) -> [&Point3; 3] {
+        let [a, b, c] = self.faces[face];
+        [
+            &self.vertices[a as usize],
+            &self.vertices[b as usize],
+            &self.vertices[c as usize],
+        ]
+    }
+
+    /// Interpolates per-vertex attribute with barycentric weights of second and third vertex
+    fn interpolate<T, F>(&self, face: usize, uv: &Point2, attribute: F) -> T
+    where
+        T: std::ops::Mul<Scalar, Output = T> + std::ops::Add<Output = T>,
+        F: Fn(usize) -> T,
+    {
+        let [a, b, c] = self.faces[face];
+        attribute(a as usize) * (1.0 - uv.x - uv.y)
+            + attribute(b as usize) * uv.x
+            + attribute(c as usize) * uv.y
+    }
+
+    /// Interpolates texture coordinates or returns barycentric ones, if there are none
+    fn face_uv(&self, face: usize, uv: Point2) -> Point2 {
+        match &self.uvs {
+            Some(uvs) => Point2::from(self.interpolate(face, &uv, |i| uvs[i].coords)),
+            None => uv,
+        }
+    }
+
+    fn intersection_with_face(
+        &self,
+        ray: &Ray,
+        face: usize,
+        t: Scalar,
+        uv: Point2,
+    ) -> Intersection {
+        let [a, b, c] = self.face_vertices(face);
+        let mut normal = Triangle::calculate_normal([*a, *b, *c]);
+        let shading_normal = match &self.normals {
+            Some(normals) => {
+                let shading_normal =
+                    Unit::new_normalize(self.interpolate(face, &uv, |i| normals[i].into_inner()));
+                // Geometric normal follows side defined by vertex normals
+                if normal.dot(&shading_normal) < 0.0 {
+                    normal = -normal;
+                }
+                shading_normal
+            }
+            None => normal,
+        };
+        Intersection {
+            t,
+            point: ray.at(t),
+            normal,
+            shading_normal,
+            uv: self.face_uv(face, uv),
+            front_face: ray.direction.dot(&normal) < 0.0,
+            face,
+        }
+    }
+
+    fn build_bvh(&mut self) {
+        let bounds: Vec<Aabb> = (0..self.faces.len())
+            .map(|face| Aabb::from_points(self.face_vertices(face).iter().copied()))
+            .collect();
+        self.bvh = Bvh::new(&bounds);
+    }
+
+    /// Creates mesh with vertices transformed by `transform_point`
+    /// and normals by inverse transposed `linear` part of the transformation.
+    fn transformed<F: Fn(&Point3) -> Point3>(&self, transform_point: F, linear: &Matrix3) -> Self {
+        let normal_matrix = linear
+            .try_inverse()
+            .map_or(*linear, |inverse| inverse.transpose());
+        let mut mesh = Self {
+            vertices: self.vertices.iter().map(transform_point).collect(),
+            faces: self.faces.clone(),
+            normals: self.normals.as_ref().map(|normals| {
+                normals
+                    .iter()
+                    .map(|n| Unit::new_normalize(normal_matrix * n.into_inner()))
+                    .collect()
+            }),
+            uvs: self.uvs.clone(),
+            materials: self.materials.clone(),
+            face_materials: self.face_materials.clone(),
+            bvh: Bvh::default(),
+        };
+        mesh.build_bvh();
+        mesh
+    }
+}
+
+impl RayTraceable for Mesh {
+    fn get_size(&self) -> Scalar {
+        (0..self.faces.len())
+            .map(|face| {
+                let [a, b, c] = self.face_vertices(face);
+                Triangle::doubled_area_of([*a, *b, *c]) * 0.5
+            })
+            .sum()
+    }
+
+    fn get_bounds(&self) -> Aabb {
+        Aabb::from_points(&self.vertices)
+    }
+
+    fn intersects(&self, ray: &Ray) -> Option<Intersection> {
+        let (face, hit) = self.bvh.closest_hit(ray, |face, closest| {
+            let ray = Ray {
+                t_max: closest,
+                ..*ray
+            };
+            let hit = Triangle::intersect_vertices(self.face_vertices(face), &ray)?;
+            Some((hit.t, hit))
+        })?;
+        Some(self.intersection_with_face(ray, face, hit.t, hit.uv))
+    }
+
+    /// Finds face containing point by checking all faces,
+    /// so it is much slower than intersection.
+    fn local_2d_coordinates(&self, point: &Point3) -> Point2 {
+        let closest = (0..self.faces.len())
+            .filter_map(|face| {
+                let [a, b, c] = self.face_vertices(face);
+                let (e1, e2, p) = (b - a, c - a, point - a);
+                let (d11, d12, d22) = (e1.dot(&e1), e1.dot(&e2), e2.dot(&e2));
+                let (dp1, dp2) = (p.dot(&e1), p.dot(&e2));
+                let denominator = d11 * d22 - d12 * d12;
+                let u = (d22 * dp1 - d12 * dp2) / denominator;
+                let v = (d11 * dp2 - d12 * dp1) / denominator;
+                let inside = u >= -BARYCENTRIC_TOLERANCE
+                    && v >= -BARYCENTRIC_TOLERANCE
+                    && u + v <= 1.0 + BARYCENTRIC_TOLERANCE;
+                if !inside {
+                    return None;
+                }
+                let normal = Triangle::calculate_normal([*a, *b, *c]);
+                Some((p.dot(&normal).abs(), face, Point2::new(u, v)))
+            })
+            .min_by(|(d1, _, _), (d2, _, _)| d1.partial_cmp(d2).unwrap());
+        closest.map_or_else(Point2::origin, |(_, face, uv)| self.face_uv(face, uv))
+    }
+}
+
+impl_op_ex!(*|a: &Matrix3, b: &Mesh| -> Mesh { b.transformed(|p| a * p, a) });
+
+impl_op_ex!(*|a: &Rotation3, b: &Mesh| -> Mesh { b.transformed(|p| a * p, a.matrix()) });
+
+impl_op_ex!(*|a: &Translation3, b: &Mesh| -> Mesh {
+    b.transformed(|p| a * p, &Matrix3::identity())
+});
+
+impl_op_ex!(*|a: &Isometry3, b: &Mesh| -> Mesh {
+    b.transformed(|p| a * p, a.rotation.to_rotation_matrix().matrix())
+});
+
+impl_op_ex!(
+    *|a: &nalgebra::Isometry<Scalar, nalgebra::U3, Rotation3>, b: &Mesh| -> Mesh {
+        b.transformed(|p| a * p, a.rotation.matrix())
+    }
+);
+
+impl_op_ex!(*|a: &Similarity3, b: &Mesh| -> Mesh {
+    b.transformed(
+        |p| a * p,
+        &(a.isometry.rotation.to_rotation_matrix().into_inner() * a.scaling()),
+    )
+});
+
+impl_op_ex!(*|a: &Transform3, b: &Mesh| -> Mesh {
+    b.transformed(
+        |p| a * p,
+        &a.matrix().fixed_slice::<U3, U3>(0, 0).into_owned(),
+    )
+});
+
+impl_op_ex!(*|a: &Matrix4, b: &Mesh| -> Mesh {
+    b.transformed(
+        |p| a.transform_point(p),
+        &a.fixed_slice::<U3, U3>(0, 0).into_owned(),
+    )
+});
+
+#[allow(clippy::op_ref)]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Colour, Quaternion};
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    /// Unit square in z = 0 plane made of two triangles, facing +z
+    fn square() -> Mesh {
+        Mesh::new(
+            vec![
+                Point3::new(0.0, 0.0, 0.0),
+                Point3::new(1.0, 0.0, 0.0),
+                Point3::new(1.0, 1.0, 0.0),
+                Point3::new(0.0, 1.0, 0.0),
+            ],
+            vec![[0, 1, 2], [0, 2, 3]],
+        )
+    }
+
+    fn assert_same_vertices(mesh: &Mesh, expected: &[Point3]) {
+        for (v, e) in mesh.get_vertices().iter().zip(expected) {
+            assert!((v - e).norm() <= 1e-5, "{} != {}", v, e);
+        }
+    }
+
+    #[test]
+    fn mesh_creation() {
+        let mesh = square();
+        assert_eq!(mesh.get_vertices().len(), 4);
+        assert_eq!(mesh.get_faces(), &[[0, 1, 2], [0, 2, 3]]);
+        assert_eq!(mesh.get_normals(), None);
+        assert_eq!(mesh.get_uvs(), None);
+        assert_eq!(mesh.get_face_material(0), None);
+        assert_eq!(
+            mesh.get_triangle(1),
+            Triangle::new([
+                Point3::new(0.0, 0.0, 0.0),
+                Point3::new(1.0, 1.0, 0.0),
+                Point3::new(0.0, 1.0, 0.0),
+            ])
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "nonexistent vertex")]
+    fn mesh_cannot_refer_to_missing_vertex() {
+        Mesh::new(vec![Point3::origin(); 3], vec![[0, 1, 3]]);
+    }
+
+    #[test]
+    #[should_panic(expected = "one material per face")]
+    fn mesh_needs_material_for_every_face() {
+        square().with_face_materials(vec![Material::default()], vec![0]);
+    }
+
+    #[test]
+    fn mesh_size_is_equal_to_its_area() {
+        assert!((square().get_size() - 1.0).abs() <= Scalar::EPSILON);
+    }
+
+    #[test]
+    fn mesh_bounds() {
+        assert_eq!(
+            square().get_bounds(),
+            Aabb::new(Point3::new(0.0, 0.0, 0.0), Point3::new(1.0, 1.0, 0.0))
+        );
+    }
+
+    #[test]
+    fn mesh_intersection_tells_which_face_was_hit() {
+        let mesh = square();
+        let ray = Ray::new(Point3::new(0.75, 0.25, 1.0), Vector3::new(0.0, 0.0, -1.0));
+        let hit = mesh.intersects(&ray).unwrap();
+        assert_eq!(hit.face, 0);
+        assert_eq!(hit.point, Point3::new(0.75, 0.25, 0.0));
+        assert_eq!(hit.normal, Vector3::z_axis());
+        assert!(hit.front_face);
+
+        let ray = Ray::new(Point3::new(0.25, 0.75, -1.0), Vector3::new(0.0, 0.0, 1.0));
+        let hit = mesh.intersects(&ray).unwrap();
+        assert_eq!(hit.face, 1);
+        assert!(!hit.front_face);
+    }
+
+    #[test]
+    fn mesh_does_not_intersect_missing_ray() {
+        let ray = Ray::new(Point3::new(1.5, 0.5, 1.0), Vector3::new(0.0, 0.0, -1.0));
+        assert_eq!(square().intersects(&ray), None);
+    }
+
+    #[test]
+    fn mesh_intersections_match_its_triangles() {
+        let mut rng = StdRng::seed_from_u64(5);
+        let vertices: Vec<Point3> = (0..60)
+            .map(|_| {
+                Point3::new(
+                    rng.gen_range(-1.0..1.0),
+                    rng.gen_range(-1.0..1.0),
+                    rng.gen_range(-1.0..1.0),
+                )
+            })
+            .collect();
+        let faces: Vec<[u32; 3]> = (0..100)
+            .map(|_| {
+                [
+                    rng.gen_range(0..60),
+                    rng.gen_range(0..60),
+                    rng.gen_range(0..60),
+                ]
+            })
+            .collect();
+        let mesh = Mesh::new(vertices, faces);
+        for _ in 0..200 {
+            let origin = Point3::new(rng.gen_range(-3.0..3.0), rng.gen_range(-3.0..3.0), 3.0);
+            let target = Point3::new(rng.gen_range(-1.0..1.0), rng.gen_range(-1.0..1.0), 0.0);
+            let ray = Ray::new(origin, target - origin);
+            let expected = (0..mesh.get_faces().len())
+                .filter_map(|face| {
+                    mesh.get_triangle(face)
+                        .intersects(&ray)
+                        .map(|h| (face, h.t))
+                })
+                .min_by(|(_, t1), (_, t2)| t1.partial_cmp(t2).unwrap());
+            assert_eq!(
+                expected,
+                mesh.intersects(&ray).map(|h| (h.face, h.t)),
+                "for {:?}",
+                ray
+            );
+        }
+    }
+
+    #[test]
+    fn mesh_interpolates_vertex_normals() {
+        let mesh = square().with_normals(vec![
+            Vector3::new(-1.0, 0.0, 1.0),
+            Vector3::new(1.0, 0.0, 1.0),
+            Vector3::new(1.0, 0.0, 1.0),
+            Vector3::new(-1.0, 0.0, 1.0),
+        ]);
+        let ray = Ray::new(Point3::new(0.5, 0.25, 1.0), Vector3::new(0.0, 0.0, -1.0));
+        let hit = mesh.intersects(&ray).unwrap();
+        assert!((hit.shading_normal.into_inner() - Vector3::z()).norm() <= 1e-6);
+        assert_eq!(hit.normal, Vector3::z_axis());
+    }
+
+    #[test]
+    fn geometric_normal_follows_vertex_normals() {
+        let mesh = square().with_normals(vec![-Vector3::z(); 4]);
+        let ray = Ray::new(Point3::new(0.75, 0.25, 1.0), Vector3::new(0.0, 0.0, -1.0));
+        let hit = mesh.intersects(&ray).unwrap();
+        assert_eq!(hit.normal, -Vector3::z_axis());
+        assert_eq!(hit.shading_normal, -Vector3::z_axis());
+        assert!(!hit.front_face);
+    }
+
+    #[test]
+    fn mesh_interpolates_vertex_uvs() {
+        let mesh = square().with_uvs(vec![
+            Point2::new(0.0, 0.0),
+            Point2::new(2.0, 0.0),
+            Point2::new(2.0, 2.0),
+            Point2::new(0.0, 2.0),
+        ]);
+        let ray = Ray::new(Point3::new(0.75, 0.25, 1.0), Vector3::new(0.0, 0.0, -1.0));
+        let hit = mesh.intersects(&ray).unwrap();
+        assert!((hit.uv - Point2::new(1.5, 0.5)).norm() <= 1e-6);
+        assert!((mesh.local_2d_coordinates(&hit.point) - hit.uv).norm() <= 1e-6);
+    }
+
+    #[test]
+    fn mesh_2d_coordinates_without_uvs_are_barycentric() {
+        let mesh = square();
+        let ray = Ray::new(Point3::new(0.25, 0.75, 1.0), Vector3::new(0.0, 0.0, -1.0));
+        let hit = mesh.intersects(&ray).unwrap();
+        assert!((mesh.local_2d_coordinates(&hit.point) - hit.uv).norm() <= 1e-6);
+    }
+
+    #[test]
+    fn mesh_has_per_face_materials() {
+        let red = Material::diffuse(Colour {
+            red: 1.0,
+            green: 0.0,
+            blue: 0.0,
+        });
+        let mesh = square().with_face_materials(vec![Material::default(), red.clone()], vec![1, 0]);
+        assert_eq!(mesh.get_face_material(0), Some(&red));
+        assert_eq!(mesh.get_face_material(1), Some(&Material::default()));
+    }
+
+    #[test]
+    fn mesh_can_be_translated() {
+        let mesh = square();
+        let translation = Translation3::new(-1.0, 2.5, 0.0);
+        let expected: Vec<Point3> = mesh
+            .get_vertices()
+            .iter()
+            .map(|v| translation * v)
+            .collect();
+        let transformed = translation * &mesh;
+
+        assert_same_vertices(&transformed, &expected);
+        assert_eq!(&translation * &mesh, transformed);
+        assert_eq!(translation * mesh.clone(), transformed);
+        assert_eq!(&translation * mesh, transformed);
+    }
+
+    #[test]
+    fn mesh_can_be_rotated_and_translated() {
+        let mesh = square().with_normals(vec![Vector3::z(); 4]);
+        let isometry = Isometry3::new(Vector3::new(-1.0, 2.5, 0.0), Vector3::new(1.57, 0.0, -0.75));
+        let expected: Vec<Point3> = mesh.get_vertices().iter().map(|v| isometry * v).collect();
+        let transformed = isometry * &mesh;
+
+        assert_same_vertices(&transformed, &expected);
+        let normal = isometry * Vector3::z();
+        for n in transformed.get_normals().unwrap() {
+            assert!((n.into_inner() - normal).norm() <= 1e-6);
+        }
+        assert_eq!(&isometry * mesh.clone(), transformed);
+        assert_eq!(isometry * &mesh, transformed);
+        assert_eq!(isometry * mesh, transformed);
+    }
+
+    #[test]
+    fn mesh_can_be_rotated() {
+        let mesh = square();
+        let rotation = Rotation3::new(Vector3::new(1.57, 0.0, -0.75));
+        let expected: Vec<Point3> = mesh.get_vertices().iter().map(|v| rotation * v).collect();
+
+        assert_same_vertices(&(rotation * &mesh), &expected);
+    }
+
+    #[test]
+    fn mesh_can_be_transformed_into_similar_mesh() {
+        let mesh = square();
+        let translation = Translation3::new(-1.0, 2.5, 0.0);
+        let rotation = Unit::new_normalize(Quaternion::new(1.75, 0.0, 1.0, 2.0));
+        let similarity = Similarity3::from_parts(translation, rotation, 2.0);
+        let expected: Vec<Point3> = mesh.get_vertices().iter().map(|v| similarity * v).collect();
+        let transformed = similarity * &mesh;
+
+        assert_same_vertices(&transformed, &expected);
+        assert!((transformed.get_size() - 4.0).abs() <= 1e-5);
+    }
+
+    #[test]
+    fn mesh_normals_stay_perpendicular_after_nonuniform_scaling() {
+        let mesh = Mesh::new(
+            vec![
+                Point3::new(1.0, 0.0, 0.0),
+                Point3::new(0.0, 1.0, 0.0),
+                Point3::new(0.0, 0.0, 0.0),
+            ],
+            vec![[0, 1, 2]],
+        )
+        .with_normals(vec![Vector3::new(1.0, 1.0, 0.0); 3]);
+        let matrix = Matrix4::new_nonuniform_scaling(&Vector3::new(1.0, 2.0, 1.0));
+        let transformed = matrix * &mesh;
+        let edge = transformed.get_vertices()[1] - transformed.get_vertices()[0];
+
+        assert_eq!(transformed.get_vertices()[1], Point3::new(0.0, 2.0, 0.0));
+        for n in transformed.get_normals().unwrap() {
+            assert!(n.dot(&edge).abs() <= 1e-6);
+        }
+        assert_eq!(
+            Transform3::from_matrix_unchecked(matrix) * &mesh,
+            transformed
+        );
+        assert_eq!(
+            matrix.fixed_slice::<U3, U3>(0, 0).into_owned() * &mesh,
+            transformed
+        );
+    }
+
+    #[test]
+    fn transformed_mesh_is_intersectable() {
+        let mesh = Translation3::new(0.0, 0.0, 5.0) * square();
+        let ray = Ray::new(Point3::new(0.75, 0.25, 0.0), Vector3::new(0.0, 0.0, 1.0));
+        let hit = mesh.intersects(&ray).unwrap();
+        assert_eq!(hit.point, Point3::new(0.75, 0.25, 5.0));
+    }
+}
@@ -1,2 +1,11 @@
+mod mesh;
+pub use mesh::Mesh;
+
+mod primitive;
+pub use primitive::Primitive;
+
+mod sphere;
+pub use sphere::Sphere;
+
 mod triangle;
 pub use triangle::Triangle;
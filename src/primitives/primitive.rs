@@ -0,0 +1,111 @@
+use crate::{
+    primitives::{Mesh, Sphere, Triangle},
+    ray::RayTraceable,
+    Aabb, Intersection, Material, Point2, Point3, Ray, Scalar,
+};
+
+/// Any of the ray traceable primitives, which can be stored together
+#[derive(Debug, PartialEq, Clone)]
+pub enum Primitive {
+    Triangle(Triangle),
+    Sphere(Sphere),
+    Mesh(Mesh),
+}
+
+impl Primitive {
+    /// Returns material of the face, if primitive defines its own materials
+    pub fn get_face_material(&self, face: usize) -> Option<&Material> {
+        match self {
+            Primitive::Mesh(mesh) => mesh.get_face_material(face),
+            _ => None,
+        }
+    }
+
+    fn as_traceable(&self) -> &dyn RayTraceable {
+        match self {
+            Primitive::Triangle(triangle) => triangle,
+            Primitive::Sphere(sphere) => sphere,
+            Primitive::Mesh(mesh) => mesh,
+        }
+    }
+}
+
+impl RayTraceable for Primitive {
+    fn get_size(&self) -> Scalar {
+        self.as_traceable().get_size()
+    }
+
+    fn get_bounds(&self) -> Aabb {
+        self.as_traceable().get_bounds()
+    }
+
+    fn intersects(&self, ray: &Ray) -> Option<Intersection> {
+        self.as_traceable().intersects(ray)
+    }
+
+    fn local_2d_coordinates(&self, point: &Point3) -> Point2 {
+        self.as_traceable().local_2d_coordinates(point)
+    }
+}
+
+impl From<Triangle> for Primitive {
+    fn from(triangle: Triangle) -> Self {
+        Primitive::Triangle(triangle)
+    }
+}
+
+impl From<Sphere> for Primitive {
+    fn from(sphere: Sphere) -> Self {
+        Primitive::Sphere(sphere)
+    }
+}
+
+impl From<Mesh> for Primitive {
+    fn from(mesh: Mesh) -> Self {
+        Primitive::Mesh(mesh)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Vector3;
+
+    #[test]
+    fn primitive_behaves_like_wrapped_triangle() {
+        let triangle = Triangle::new([
+            Point3::new(1.0, -1.0, 0.0),
+            Point3::new(0.0, 1.0, 0.0),
+            Point3::new(-1.0, -1.0, 0.0),
+        ]);
+        let primitive = Primitive::from(triangle);
+        let ray = Ray::new(Point3::new(0.0, 0.0, -1.0), Vector3::new(0.0, 0.0, 1.0));
+        let point = Point3::new(0.25, 0.0, 0.0);
+
+        assert_eq!(primitive, Primitive::Triangle(triangle));
+        assert_eq!(primitive.get_size(), triangle.get_size());
+        assert_eq!(primitive.get_bounds(), triangle.get_bounds());
+        assert_eq!(primitive.intersects(&ray), triangle.intersects(&ray));
+        assert_eq!(
+            primitive.local_2d_coordinates(&point),
+            triangle.local_2d_coordinates(&point)
+        );
+    }
+
+    #[test]
+    fn primitive_behaves_like_wrapped_sphere() {
+        let sphere = Sphere::new(Point3::new(0.0, 0.0, 1.0), 0.5);
+        let primitive = Primitive::from(sphere);
+        let ray = Ray::new(Point3::new(0.0, 0.0, -1.0), Vector3::new(0.0, 0.0, 1.0));
+        let point = Point3::new(0.5, 0.0, 1.0);
+
+        assert_eq!(primitive, Primitive::Sphere(sphere));
+        assert_eq!(primitive.get_size(), sphere.get_size());
+        assert_eq!(primitive.get_bounds(), sphere.get_bounds());
+        assert_eq!(primitive.intersects(&ray), sphere.intersects(&ray));
+        assert_eq!(
+            primitive.local_2d_coordinates(&point),
+            sphere.local_2d_coordinates(&point)
+        );
+    }
+}
@@ -0,0 +1,373 @@
+use auto_ops::*;
+use nalgebra::{Unit, U3};
+
+use crate::{
+    ray::RayTraceable, Aabb, Intersection, Isometry3, Matrix3, Matrix4, Point2, Point3, Ray,
+    Rotation3, Scalar, Similarity3, Transform3, Translation3, Vector3,
+};
+
+/// A sphere primitive
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct Sphere {
+    center: Point3,
+    radius: Scalar,
+}
+
+impl Sphere {
+    pub fn new(center: Point3, radius: Scalar) -> Self {
+        Self { center, radius }
+    }
+
+    pub fn get_center(&self) -> &Point3 {
+        &self.center
+    }
+
+    pub fn get_radius(&self) -> Scalar {
+        self.radius
+    }
+
+    /// Returns outward normal vector at point on the sphere
+    pub fn get_normal(&self, point: &Point3) -> Unit<Vector3> {
+        Unit::new_normalize(point - self.center)
+    }
+
+    /// Transforms sphere, if the transform consists of rotations, reflections,
+    /// uniform scaling and translation. Returns `None` for other transforms,
+    /// as they turn spheres into ellipsoids.
+    pub fn try_transform(&self, transform: &Transform3) -> Option<Self> {
+        self.try_transformed(
+            transform * self.center,
+            &transform.matrix().fixed_slice::<U3, U3>(0, 0).into_owned(),
+        )
+    }
+
+    /// Creates sphere with given center, which is this sphere transformed by a linear map
+    fn try_transformed(&self, center: Point3, linear: &Matrix3) -> Option<Self> {
+        let singular_values = linear.singular_values();
+        let scaling = singular_values.max();
+        if singular_values.min() < scaling * (1.0 - 1e-4) {
+            return None;
+        }
+        Some(Self::new(center, self.radius * scaling))
+    }
+
+    /// Panics for maps other than rotations, reflections and uniform scaling
+    fn transformed(&self, center: Point3, linear: &Matrix3) -> Self {
+        self.try_transformed(center, linear)
+            .expect("Sphere can be scaled only uniformly")
+    }
+}
+
+impl RayTraceable for Sphere {
+    fn get_size(&self) -> Scalar {
+        4.0 * std::f32::consts::PI * self.radius * self.radius
+    }
+
+    fn get_bounds(&self) -> Aabb {
+        Aabb::new(self.center, self.center).padded(self.radius)
+    }
+
+    fn intersects(&self, ray: &Ray) -> Option<Intersection> {
+        // Solves |origin + t * direction - center|^2 = radius^2
+        let oc = ray.origin - self.center;
+        let half_b = oc.dot(&ray.direction);
+        let c = oc.norm_squared() - self.radius * self.radius;
+        let discriminant = half_b * half_b - c;
+        if discriminant < 0.0 {
+            return None;
+        }
+        // Numerically stable roots, see https://people.csail.mit.edu/bkph/articles/Quadratics.pdf
+        let q = -half_b - half_b.signum() * discriminant.sqrt();
+        let (t0, t1) = if q == 0.0 { (0.0, 0.0) } else { (c / q, q) };
+        let (t0, t1) = if t0 <= t1 { (t0, t1) } else { (t1, t0) };
+        let t = if ray.contains(t0) {
+            t0
+        } else if ray.contains(t1) {
+            t1
+        } else {
+            return None;
+        };
+
+        let point = ray.at(t);
+        let normal = self.get_normal(&point);
+        Some(Intersection {
+            t,
+            point,
+            normal,
+            shading_normal: normal,
+            uv: self.local_2d_coordinates(&point),
+            front_face: ray.direction.dot(&normal) < 0.0,
+            face: 0,
+        })
+    }
+
+    fn local_2d_coordinates(&self, point: &Point3) -> Point2 {
+        // Longitude measured around y axis and latitude from the bottom pole
+        let direction = self.get_normal(point);
+        let theta = (-direction.y).clamp(-1.0, 1.0).acos();
+        let phi = (-direction.z).atan2(direction.x) + std::f32::consts::PI;
+        Point2::new(
+            phi / (2.0 * std::f32::consts::PI),
+            theta / std::f32::consts::PI,
+        )
+    }
+}
+
+impl_op_ex!(*|a: &Matrix3, b: &Sphere| -> Sphere { b.transformed(a * b.center, a) });
+
+impl_op_ex!(*|a: &Rotation3, b: &Sphere| -> Sphere { Sphere::new(a * b.center, b.radius) });
+
+impl_op_ex!(*|a: &Translation3, b: &Sphere| -> Sphere { Sphere::new(a * b.center, b.radius) });
+
+impl_op_ex!(*|a: &Isometry3, b: &Sphere| -> Sphere { Sphere::new(a * b.center, b.radius) });
+
+impl_op_ex!(
+    *|a: &nalgebra::Isometry<Scalar, nalgebra::U3, Rotation3>, b: &Sphere| -> Sphere {
+        Sphere::new(a * b.center, b.radius)
+    }
+);
+
+impl_op_ex!(*|a: &Similarity3, b: &Sphere| -> Sphere {
+    Sphere::new(a * b.center, b.radius * a.scaling().abs())
+});
+
+impl_op_ex!(*|a: &Transform3, b: &Sphere| -> Sphere {
+    b.try_transform(a)
+        .expect("Sphere can be scaled only uniformly")
+});
+
+impl_op_ex!(*|a: &Matrix4, b: &Sphere| -> Sphere {
+    b.transformed(
+        a.transform_point(&b.center),
+        &a.fixed_slice::<U3, U3>(0, 0).into_owned(),
+    )
+});
+
+#[allow(clippy::op_ref)]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Quaternion;
+
+    fn assert_close(a: Scalar, b: Scalar) {
+        assert!((a - b).abs() <= 1e-5, "{} != {}", a, b);
+    }
+
+    #[test]
+    fn sphere_creation() {
+        let sphere = Sphere::new(Point3::new(1.0, 2.0, 3.0), 0.5);
+        assert_eq!(sphere.get_center(), &Point3::new(1.0, 2.0, 3.0));
+        assert_eq!(sphere.get_radius(), 0.5);
+    }
+
+    #[test]
+    fn sphere_size_is_equal_to_its_surface_area() {
+        let sphere = Sphere::new(Point3::new(1.0, 2.0, 3.0), 2.0);
+        assert_close(sphere.get_size(), 16.0 * std::f32::consts::PI);
+    }
+
+    #[test]
+    fn sphere_bounds() {
+        let sphere = Sphere::new(Point3::new(1.0, 2.0, 3.0), 0.5);
+        assert_eq!(
+            sphere.get_bounds(),
+            Aabb::new(Point3::new(0.5, 1.5, 2.5), Point3::new(1.5, 2.5, 3.5))
+        );
+    }
+
+    #[test]
+    fn sphere_normal_points_outwards() {
+        let sphere = Sphere::new(Point3::new(1.0, 2.0, 3.0), 0.5);
+        assert_eq!(
+            sphere.get_normal(&Point3::new(1.0, 2.0, 2.5)),
+            -Vector3::z_axis()
+        );
+    }
+
+    #[test]
+    fn sphere_does_not_intersect_missing_ray() {
+        let sphere = Sphere::new(Point3::new(0.0, 0.0, 0.0), 1.0);
+        let ray = Ray::new(Point3::new(0.0, 1.5, -5.0), Vector3::new(0.0, 0.0, 1.0));
+        assert_eq!(sphere.intersects(&ray), None);
+    }
+
+    #[test]
+    fn sphere_does_not_intersect_ray_starting_behind_sphere() {
+        let sphere = Sphere::new(Point3::new(0.0, 0.0, 0.0), 1.0);
+        let ray = Ray::new(Point3::new(0.0, 0.0, 5.0), Vector3::new(0.0, 0.0, 1.0));
+        assert_eq!(sphere.intersects(&ray), None);
+    }
+
+    #[test]
+    fn sphere_intersects_ray_from_outside() {
+        let sphere = Sphere::new(Point3::new(0.0, 0.0, 0.0), 1.0);
+        let ray = Ray::new(Point3::new(0.0, 0.0, -5.0), Vector3::new(0.0, 0.0, 1.0));
+        let hit = sphere.intersects(&ray).unwrap();
+        assert_close(hit.t, 4.0);
+        assert_eq!(hit.point, Point3::new(0.0, 0.0, -1.0));
+        assert_eq!(hit.normal, -Vector3::z_axis());
+        assert!(hit.front_face);
+    }
+
+    #[test]
+    fn sphere_intersects_ray_from_inside() {
+        let sphere = Sphere::new(Point3::new(0.0, 0.0, 0.0), 1.0);
+        let ray = Ray::new(Point3::new(0.0, 0.0, 0.0), Vector3::new(0.0, 0.0, 1.0));
+        let hit = sphere.intersects(&ray).unwrap();
+        assert_close(hit.t, 1.0);
+        assert_eq!(hit.normal, Vector3::z_axis());
+        assert!(!hit.front_face);
+        assert_eq!(hit.facing_normal(), -Vector3::z_axis());
+    }
+
+    #[test]
+    fn sphere_intersection_respects_ray_bounds() {
+        let sphere = Sphere::new(Point3::new(0.0, 0.0, 0.0), 1.0);
+        let origin = Point3::new(0.0, 0.0, -5.0);
+        let direction = Vector3::new(0.0, 0.0, 1.0);
+        let far_hit = sphere
+            .intersects(&Ray::new_bounded(origin, direction, 4.5, 10.0))
+            .unwrap();
+        assert_close(far_hit.t, 6.0);
+        assert_eq!(
+            sphere.intersects(&Ray::new_bounded(origin, direction, 0.0, 3.5)),
+            None
+        );
+        assert_eq!(
+            sphere.intersects(&Ray::new_bounded(origin, direction, 6.5, 10.0)),
+            None
+        );
+    }
+
+    #[test]
+    fn sphere_2d_coordinates_are_spherical() {
+        let sphere = Sphere::new(Point3::new(1.0, 1.0, 1.0), 2.0);
+        let bottom = sphere.local_2d_coordinates(&Point3::new(1.0, -1.0, 1.0));
+        let top = sphere.local_2d_coordinates(&Point3::new(1.0, 3.0, 1.0));
+        let equator = sphere.local_2d_coordinates(&Point3::new(-1.0, 1.0, 1.0));
+        assert_close(bottom.y, 0.0);
+        assert_close(top.y, 1.0);
+        assert_close(equator.x, 0.0);
+        assert_close(equator.y, 0.5);
+        let equator = sphere.local_2d_coordinates(&Point3::new(3.0, 1.0, 1.0));
+        assert_close(equator.x, 0.5);
+    }
+
+    #[test]
+    fn sphere_intersection_uv_are_local_2d_coordinates() {
+        let sphere = Sphere::new(Point3::new(0.0, 0.0, 0.0), 1.0);
+        let ray = Ray::new(Point3::new(0.3, 0.2, -5.0), Vector3::new(0.0, 0.0, 1.0));
+        let hit = sphere.intersects(&ray).unwrap();
+        assert_eq!(hit.uv, sphere.local_2d_coordinates(&hit.point));
+    }
+
+    #[test]
+    fn sphere_can_be_rotated() {
+        let sphere = Sphere::new(Point3::new(1.0, 2.0, 3.0), 0.5);
+        let rotation = Rotation3::new(Vector3::new(1.57, 0.0, -0.75));
+        let expected = Sphere::new(rotation * Point3::new(1.0, 2.0, 3.0), 0.5);
+
+        assert_eq!(rotation * sphere, expected);
+        assert_eq!(&rotation * sphere, expected);
+        assert_eq!(rotation * &sphere, expected);
+        assert_eq!(&rotation * &sphere, expected);
+    }
+
+    #[test]
+    fn sphere_can_be_translated() {
+        let sphere = Sphere::new(Point3::new(1.0, 2.0, 3.0), 0.5);
+        let translation = Translation3::new(-1.0, 2.5, 0.0);
+        let expected = Sphere::new(Point3::new(0.0, 4.5, 3.0), 0.5);
+
+        assert_eq!(translation * sphere, expected);
+        assert_eq!(&translation * sphere, expected);
+        assert_eq!(translation * &sphere, expected);
+        assert_eq!(&translation * &sphere, expected);
+    }
+
+    #[test]
+    fn sphere_can_be_rotated_and_translated() {
+        let sphere = Sphere::new(Point3::new(1.0, 2.0, 3.0), 0.5);
+        let isometry = Isometry3::new(Vector3::new(-1.0, 2.5, 0.0), Vector3::new(1.57, 0.0, -0.75));
+        let expected = Sphere::new(isometry * Point3::new(1.0, 2.0, 3.0), 0.5);
+
+        assert_eq!(isometry * sphere, expected);
+        assert_eq!(&isometry * sphere, expected);
+        assert_eq!(isometry * &sphere, expected);
+        assert_eq!(&isometry * &sphere, expected);
+    }
+
+    #[test]
+    fn sphere_can_be_transformed_into_similar_sphere() {
+        let sphere = Sphere::new(Point3::new(1.0, 2.0, 3.0), 0.5);
+        let translation = Translation3::new(-1.0, 2.5, 0.0);
+        let rotation = Unit::new_normalize(Quaternion::new(1.75, 0.0, 1.0, 2.0));
+        let similarity = Similarity3::from_parts(translation, rotation, 2.0);
+        let expected = Sphere::new(similarity * Point3::new(1.0, 2.0, 3.0), 1.0);
+
+        assert_eq!(similarity * sphere, expected);
+        assert_eq!(&similarity * sphere, expected);
+        assert_eq!(similarity * &sphere, expected);
+        assert_eq!(&similarity * &sphere, expected);
+    }
+
+    #[test]
+    fn sphere_can_be_multiplied_by_3d_matrix() {
+        let sphere = Sphere::new(Point3::new(1.0, 2.0, 3.0), 0.5);
+        let mat = Matrix3::from_diagonal(&Vector3::new(3.0, -3.0, 3.0));
+        let transformed = mat * sphere;
+
+        assert_eq!(transformed.get_center(), &Point3::new(3.0, -6.0, 9.0));
+        assert_close(transformed.get_radius(), 1.5);
+        assert_eq!(&mat * sphere, transformed);
+        assert_eq!(mat * &sphere, transformed);
+        assert_eq!(&mat * &sphere, transformed);
+    }
+
+    #[test]
+    #[should_panic(expected = "uniformly")]
+    fn sphere_cannot_be_scaled_non_uniformly() {
+        let sphere = Sphere::new(Point3::new(1.0, 2.0, 3.0), 0.5);
+        let _ = Matrix3::from_diagonal(&Vector3::new(1.0, 3.0, 2.0)) * sphere;
+    }
+
+    #[test]
+    fn sphere_cannot_be_sheared() {
+        let sphere = Sphere::new(Point3::new(1.0, 2.0, 3.0), 0.5);
+        let mut matrix = Matrix4::identity();
+        matrix[(0, 1)] = 0.5;
+        let transform = Transform3::from_matrix_unchecked(matrix);
+        assert_eq!(sphere.try_transform(&transform), None);
+        let rotation = Transform3::from_matrix_unchecked(
+            Rotation3::new(Vector3::new(0.0, 1.0, 0.0)).to_homogeneous(),
+        );
+        assert_eq!(sphere.try_transform(&rotation), Some(rotation * sphere));
+    }
+
+    #[test]
+    fn sphere_can_be_transformed() {
+        let sphere = Sphere::new(Point3::new(1.0, 2.0, 3.0), 0.5);
+        let transform = Transform3::from_matrix_unchecked(Matrix4::new_scaling(2.0));
+        let transformed = transform * sphere;
+
+        assert_eq!(transformed.get_center(), &Point3::new(2.0, 4.0, 6.0));
+        assert_close(transformed.get_radius(), 1.0);
+        assert_eq!(&transform * sphere, transformed);
+        assert_eq!(transform * &sphere, transformed);
+        assert_eq!(&transform * &sphere, transformed);
+    }
+
+    #[test]
+    fn sphere_can_be_multiplied_by_4d_matrix() {
+        let sphere = Sphere::new(Point3::new(1.0, 2.0, 3.0), 0.5);
+        let matrix =
+            Matrix4::new_translation(&Vector3::new(1.0, 0.0, 0.0)) * Matrix4::new_scaling(2.0);
+        let transformed = matrix * sphere;
+
+        assert_eq!(transformed.get_center(), &Point3::new(3.0, 4.0, 6.0));
+        assert_close(transformed.get_radius(), 1.0);
+        assert_eq!(&matrix * sphere, transformed);
+        assert_eq!(matrix * &sphere, transformed);
+        assert_eq!(&matrix * &sphere, transformed);
+    }
+}
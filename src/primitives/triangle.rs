@@ -2,10 +2,20 @@ use auto_ops::*;
 use nalgebra::Unit;
 
 use crate::{
-    ray::RayTraceable, Isometry3, Matrix3, Matrix4, Point2, Point3, Ray, Rotation3, Scalar,
-    Similarity3, Transform3, Translation3, Vector3,
+    ray::RayTraceable, Aabb, Intersection, Isometry3, Matrix3, Matrix4, Point2, Point3, Ray,
+    Rotation3, Scalar, Similarity3, Transform3, Translation3, Vector3,
 };
 
+/// Ray parameter and barycentric coordinates of ray and triangle intersection
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub(crate) struct TriangleHit {
+    pub t: Scalar,
+    /// Barycentric weights of second and third vertex
+    pub uv: Point2,
+    /// True if ray travels against the normal of the triangle
+    pub front_face: bool,
+}
+
 /// A triangle primitive
 #[derive(Debug, PartialEq, Copy, Clone)]
 pub struct Triangle {
@@ -30,50 +40,75 @@ impl Triangle {
         self.normal = Triangle::calculate_normal(self.vertices);
     }
 
-    fn calculate_normal(vertices: [Point3; 3]) -> Unit<Vector3> {
-        let e1 = vertices[1] - vertices[0];
-        let e2 = vertices[2] - vertices[0];
-        Unit::new_normalize(e1.cross(&e2))
+    /// Returns normal vector to the triangle
+    pub fn get_normal(&self) -> Unit<Vector3> {
+        self.normal
     }
 
-    fn doubled_area_of(vertices: [Point3; 3]) -> Scalar {
+    /// Computes intersection of ray with triangle spanned by vertices.
+    /// See https://www.scratchapixel.com/lessons/3d-basic-rendering/ray-tracing-rendering-a-triangle/moller-trumbore-ray-triangle-intersection
+    pub(crate) fn intersect_vertices(vertices: [&Point3; 3], ray: &Ray) -> Option<TriangleHit> {
         let v0v1 = vertices[1] - vertices[0];
         let v0v2 = vertices[2] - vertices[0];
-        v0v1.cross(&v0v2).norm()
-    }
-}
-
-impl RayTraceable for Triangle {
-    fn get_normal(&self) -> Unit<Vector3> {
-        self.normal
-    }
-
-    fn get_size(&self) -> Scalar {
-        Triangle::doubled_area_of(self.vertices) * 0.5
-    }
-
-    fn intersects(&self, ray: &Ray) -> Option<Point3> {
-        let v0v1 = self.get_v(1) - self.get_v(0);
-        let v0v2 = self.get_v(2) - self.get_v(0);
         let pvec = ray.direction.cross(&v0v2);
         let determinant = v0v1.dot(&pvec);
         if determinant.abs() <= Scalar::EPSILON {
             return None;
         }
         let inv_det = 1.0 / determinant;
-        let tvec = ray.origin - self.get_v(0);
+        let tvec = ray.origin - vertices[0];
         let u = tvec.dot(&pvec) * inv_det;
         let qvec = tvec.cross(&v0v1);
         let v = ray.direction.dot(&qvec) * inv_det;
-        if u < 0.0 || u > 1.0 || v < 0.0 || u + v > 1.0 {
+        if !(0.0..=1.0).contains(&u) || v < 0.0 || u + v > 1.0 {
             return None;
         }
 
         let t = v0v2.dot(&qvec) * inv_det;
-        if t < 0.0 {
+        if !ray.contains(t) {
             return None;
         }
-        Some(ray.origin + t * ray.direction.into_inner())
+        Some(TriangleHit {
+            t,
+            uv: Point2::new(u, v),
+            // Determinant is positive, when ray travels against the normal
+            front_face: determinant > 0.0,
+        })
+    }
+
+    pub(crate) fn calculate_normal(vertices: [Point3; 3]) -> Unit<Vector3> {
+        let e1 = vertices[1] - vertices[0];
+        let e2 = vertices[2] - vertices[0];
+        Unit::new_normalize(e1.cross(&e2))
+    }
+
+    pub(crate) fn doubled_area_of(vertices: [Point3; 3]) -> Scalar {
+        let v0v1 = vertices[1] - vertices[0];
+        let v0v2 = vertices[2] - vertices[0];
+        v0v1.cross(&v0v2).norm()
+    }
+}
+
+impl RayTraceable for Triangle {
+    fn get_size(&self) -> Scalar {
+        Triangle::doubled_area_of(self.vertices) * 0.5
+    }
+
+    fn get_bounds(&self) -> Aabb {
+        Aabb::from_points(&self.vertices)
+    }
+
+    fn intersects(&self, ray: &Ray) -> Option<Intersection> {
+        let hit = Triangle::intersect_vertices([self.get_v(0), self.get_v(1), self.get_v(2)], ray)?;
+        Some(Intersection {
+            t: hit.t,
+            point: ray.at(hit.t),
+            normal: self.normal,
+            shading_normal: self.normal,
+            uv: hit.uv,
+            front_face: hit.front_face,
+            face: 0,
+        })
     }
 
     fn local_2d_coordinates(&self, point: &Point3) -> Point2 {
@@ -154,7 +189,7 @@ mod tests {
             Point3::new(0.5, 0.0, 0.0),
         ]);
 
-        assert!((tri.get_size() - 2.0) < f32::EPSILON);
+        assert!((tri.get_size() - 2.0).abs() < f32::EPSILON);
     }
 
     #[test]
@@ -179,6 +214,28 @@ mod tests {
         assert_eq!(tri.intersects(&ray), None);
     }
 
+    #[test]
+    fn triangle_does_not_intersect_ray_outside_ray_bounds() {
+        let tri = Triangle::new([
+            Point3::new(0.0, 1.0, 0.0),
+            Point3::new(-0.5, 0.0, 0.0),
+            Point3::new(0.5, 0.0, 0.0),
+        ]);
+        let origin = Point3::new(0.0, 0.5, -1.0);
+        let direction = Vector3::new(0.0, 0.0, 1.0);
+        assert_eq!(
+            tri.intersects(&Ray::new_bounded(origin, direction, 0.0, 0.5)),
+            None
+        );
+        assert_eq!(
+            tri.intersects(&Ray::new_bounded(origin, direction, 1.5, 2.0)),
+            None
+        );
+        assert!(tri
+            .intersects(&Ray::new_bounded(origin, direction, 0.5, 1.5))
+            .is_some());
+    }
+
     #[test]
     fn triangle_does_not_intersect_ray_hitting_triangle_plane_but_not_the_triangle() {
         let tri = Triangle::new([
@@ -198,7 +255,48 @@ mod tests {
             Point3::new(0.5, 0.0, 0.0),
         ]);
         let ray = Ray::new(Point3::new(0.0, 0.5, -1.0), Vector3::new(0.0, 0.0, 1.0));
-        assert_eq!(tri.intersects(&ray), Some(Point3::new(0.0, 0.5, 0.0)));
+        assert_eq!(
+            tri.intersects(&ray),
+            Some(Intersection {
+                t: 1.0,
+                point: Point3::new(0.0, 0.5, 0.0),
+                normal: tri.get_normal(),
+                shading_normal: tri.get_normal(),
+                uv: Point2::new(0.25, 0.25),
+                front_face: false,
+                face: 0,
+            })
+        );
+    }
+
+    #[test]
+    fn triangle_intersection_tells_which_side_was_hit() {
+        let tri = Triangle::new([
+            Point3::new(0.0, 1.0, 0.0),
+            Point3::new(-0.5, 0.0, 0.0),
+            Point3::new(0.5, 0.0, 0.0),
+        ]);
+        let ray = Ray::new(Point3::new(0.0, 0.5, 1.0), Vector3::new(0.0, 0.0, -1.0));
+        let hit = tri.intersects(&ray).unwrap();
+        assert!(hit.front_face);
+        assert_eq!(hit.facing_normal(), tri.get_normal());
+
+        let ray = Ray::new(Point3::new(0.0, 0.5, -1.0), Vector3::new(0.0, 0.0, 1.0));
+        let hit = tri.intersects(&ray).unwrap();
+        assert!(!hit.front_face);
+        assert_eq!(hit.facing_normal(), -tri.get_normal());
+    }
+
+    #[test]
+    fn triangle_intersection_uv_are_local_2d_coordinates() {
+        let tri = Triangle::new([
+            Point3::new(1.0, 0.0, 0.0),
+            Point3::new(0.0, 1.0, 0.0),
+            Point3::new(-1.0, 0.0, 0.0),
+        ]);
+        let ray = Ray::new(Point3::new(-0.5, 0.5, 1.0), Vector3::new(0.0, 0.0, -1.0));
+        let hit = tri.intersects(&ray).unwrap();
+        assert_eq!(hit.uv, tri.local_2d_coordinates(&hit.point));
     }
 
     #[test]
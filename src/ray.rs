@@ -2,37 +2,132 @@ use auto_ops::*;
 use nalgebra::Unit;
 
 use crate::{
-    Isometry3, Matrix3, Matrix4, Point2, Point3, Rotation3, Scalar, Similarity3, Transform3,
+    Aabb, Isometry3, Matrix3, Matrix4, Point2, Point3, Rotation3, Scalar, Similarity3, Transform3,
     Translation3, Vector3,
 };
 
+/// Distance relative to point coordinates, by which rays leaving
+/// a surface are moved away from it to avoid self intersections.
+const SURFACE_OFFSET: Scalar = 256.0 * Scalar::EPSILON;
+
 /// 3D ray class for ray tracing.
+/// Only points with ray parameter in `[t_min, t_max]` are considered
+/// to lie on the ray.
 #[derive(Debug, PartialEq, Copy, Clone)]
 pub struct Ray {
     pub origin: Point3,
     pub direction: Unit<Vector3>,
+    pub t_min: Scalar,
+    pub t_max: Scalar,
 }
 
 impl Ray {
+    /// Creates ray starting at origin and going to infinity
     pub fn new(origin: Point3, direction: Vector3) -> Self {
+        Self::new_bounded(origin, direction, 0.0, Scalar::INFINITY)
+    }
+
+    /// Creates ray with ray parameter limited to `[t_min, t_max]`
+    pub fn new_bounded(origin: Point3, direction: Vector3, t_min: Scalar, t_max: Scalar) -> Self {
         Self {
             origin,
             direction: Unit::new_normalize(direction),
+            t_min,
+            t_max,
+        }
+    }
+
+    /// Creates ray leaving surface at point with given normal.
+    /// Ray origin is moved off the surface on the side direction points to,
+    /// by a distance proportional to the magnitude of point coordinates.
+    pub fn leaving_surface(point: &Point3, normal: &Unit<Vector3>, direction: Vector3) -> Self {
+        let offset = SURFACE_OFFSET * point.coords.amax().max(1.0);
+        let offset = if direction.dot(normal) < 0.0 {
+            -offset
+        } else {
+            offset
+        };
+        Self::new(point + offset * normal.into_inner(), direction)
+    }
+
+    /// Creates ray starting at one point and ending just before the other.
+    /// It can be used to check visibility between two points.
+    /// Ray between points closer than the surface offset contains only its origin.
+    pub fn between(from: &Point3, to: &Point3) -> Self {
+        let direction = to - from;
+        let distance = direction.norm();
+        let t_max = distance - SURFACE_OFFSET * to.coords.amax().max(1.0);
+        Self::new_bounded(*from, direction, 0.0, t_max.max(0.0))
+    }
+
+    /// Checks if ray parameter lies within ray bounds
+    pub fn contains(&self, t: Scalar) -> bool {
+        self.t_min <= t && t <= self.t_max
+    }
+
+    /// Returns point at ray parameter
+    pub fn at(&self, t: Scalar) -> Point3 {
+        self.origin + t * self.direction.into_inner()
+    }
+
+    /// Creates ray with transformed origin and direction. Bounds are scaled,
+    /// so that they still describe the same part of the transformed ray.
+    fn transformed(&self, origin: Point3, direction: Vector3) -> Self {
+        let (direction, scale) = Unit::new_and_get(direction);
+        Self {
+            origin,
+            direction,
+            t_min: self.t_min * scale,
+            t_max: self.t_max * scale,
+        }
+    }
+}
+
+/// Description of ray intersection with ray traceable object
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct Intersection {
+    /// Ray parameter of the intersection. As ray direction is normalized,
+    /// it is also the distance from ray's origin.
+    pub t: Scalar,
+    /// Point of intersection
+    pub point: Point3,
+    /// Geometric normal of the surface at the point of intersection
+    pub normal: Unit<Vector3>,
+    /// Normal used for shading, e.g. interpolated from vertex normals.
+    /// It points to the same side of the surface as geometric normal.
+    pub shading_normal: Unit<Vector3>,
+    /// Local 2D coordinates of the point of intersection,
+    /// the same as returned by `RayTraceable::local_2d_coordinates`
+    pub uv: Point2,
+    /// True if ray hit the side of the surface the normal points to
+    pub front_face: bool,
+    /// Index of the face, which was hit, for objects consisting of many faces.
+    /// Zero for single faced objects.
+    pub face: usize,
+}
+
+impl Intersection {
+    /// Returns normal flipped to face the side of the surface, which was hit
+    pub fn facing_normal(&self) -> Unit<Vector3> {
+        if self.front_face {
+            self.normal
+        } else {
+            -self.normal
         }
     }
 }
 
 /// Trait for all types, which are ray traceable in 3D
 pub trait RayTraceable {
-    /// Returns normal vector to the traceable object
-    fn get_normal(&self) -> Unit<Vector3>;
-
     /// Returns size of the traceable object
     fn get_size(&self) -> Scalar;
 
-    /// Computes point of intersection of Self with ray.
+    /// Returns axis aligned box bounding the traceable object
+    fn get_bounds(&self) -> Aabb;
+
+    /// Computes intersection of Self with ray.
     /// If there is no intersection, it returns None.
-    fn intersects(&self, ray: &Ray) -> Option<Point3>;
+    fn intersects(&self, ray: &Ray) -> Option<Intersection>;
 
     /// Computes 2D local coordinates of 3D point inside ray traceable primitive.
     /// It can be used for example as a texture coordinates.
@@ -40,61 +135,43 @@ pub trait RayTraceable {
 }
 
 impl_op_ex!(*|a: &Matrix3, b: &Ray| -> Ray {
-    Ray {
-        origin: a * b.origin,
-        direction: Unit::new_normalize(a * b.direction.into_inner()),
-    }
+    b.transformed(a * b.origin, a * b.direction.into_inner())
 });
 
 impl_op_ex!(*|a: &Rotation3, b: &Ray| -> Ray {
-    Ray {
-        origin: a * b.origin,
-        direction: Unit::new_normalize(a * b.direction.into_inner()),
-    }
+    b.transformed(a * b.origin, a * b.direction.into_inner())
 });
 
 impl_op_ex!(*|a: &Translation3, b: &Ray| -> Ray {
     Ray {
         origin: a * b.origin,
-        direction: b.direction,
+        ..*b
     }
 });
 
 impl_op_ex!(*|a: &Isometry3, b: &Ray| -> Ray {
-    Ray {
-        origin: a * b.origin,
-        direction: Unit::new_normalize(a * b.direction.into_inner()),
-    }
+    b.transformed(a * b.origin, a * b.direction.into_inner())
 });
 
 impl_op_ex!(
     *|a: &nalgebra::Isometry<Scalar, nalgebra::U3, Rotation3>, b: &Ray| -> Ray {
-        Ray {
-            origin: a * b.origin,
-            direction: Unit::new_normalize(a * b.direction.into_inner()),
-        }
+        b.transformed(a * b.origin, a * b.direction.into_inner())
     }
 );
 
 impl_op_ex!(*|a: &Similarity3, b: &Ray| -> Ray {
-    Ray {
-        origin: a * b.origin,
-        direction: Unit::new_normalize(a * b.direction.into_inner()),
-    }
+    b.transformed(a * b.origin, a * b.direction.into_inner())
 });
 
 impl_op_ex!(*|a: &Transform3, b: &Ray| -> Ray {
-    Ray {
-        origin: a * b.origin,
-        direction: Unit::new_normalize(a * b.direction.into_inner()),
-    }
+    b.transformed(a * b.origin, a * b.direction.into_inner())
 });
 
 impl_op_ex!(*|a: &Matrix4, b: &Ray| -> Ray {
-    Ray {
-        origin: a.transform_point(&b.origin),
-        direction: Unit::new_normalize(a.transform_vector(&b.direction)),
-    }
+    b.transformed(
+        a.transform_point(&b.origin),
+        a.transform_vector(&b.direction),
+    )
 });
 
 #[allow(clippy::op_ref)]
@@ -103,21 +180,105 @@ mod tests {
     use super::*;
     use crate::Quaternion;
 
+    const T_MIN: Scalar = 0.5;
+    const T_MAX: Scalar = 10.0;
+
+    fn expected_ray(origin: Point3, direction: Vector3) -> Ray {
+        let scale = direction.norm();
+        Ray {
+            origin,
+            direction: Unit::new_normalize(direction),
+            t_min: T_MIN * scale,
+            t_max: T_MAX * scale,
+        }
+    }
+
+    #[test]
+    fn ray_is_unbounded_by_default() {
+        let ray = Ray::new(Point3::new(1.0, 2.0, -7.0), Vector3::new(0.0, 0.0, 2.0));
+        assert_eq!(ray.direction.into_inner(), Vector3::new(0.0, 0.0, 1.0));
+        assert!(ray.contains(0.0));
+        assert!(ray.contains(Scalar::MAX));
+        assert!(!ray.contains(-Scalar::EPSILON));
+        assert_eq!(ray.at(2.0), Point3::new(1.0, 2.0, -5.0));
+    }
+
+    #[test]
+    fn bounded_ray_contains_only_its_range() {
+        let ray = Ray::new_bounded(
+            Point3::new(0.0, 0.0, 0.0),
+            Vector3::new(1.0, 0.0, 0.0),
+            T_MIN,
+            T_MAX,
+        );
+        assert!(!ray.contains(0.25));
+        assert!(ray.contains(T_MIN));
+        assert!(ray.contains(5.0));
+        assert!(ray.contains(T_MAX));
+        assert!(!ray.contains(10.5));
+    }
+
+    #[test]
+    fn ray_between_points_ends_before_target() {
+        let from = Point3::new(1.0, 0.0, 0.0);
+        let to = Point3::new(1.0, 0.0, 4.0);
+        let ray = Ray::between(&from, &to);
+        assert_eq!(ray.origin, from);
+        assert_eq!(ray.direction.into_inner(), Vector3::new(0.0, 0.0, 1.0));
+        assert!(ray.contains(3.99));
+        assert!(!ray.contains(4.0));
+    }
+
+    #[test]
+    fn ray_between_close_points_is_not_degenerate() {
+        let from = Point3::new(100.0, 0.0, 0.0);
+        let to = Point3::new(100.0, 0.0, 1e-4);
+        let ray = Ray::between(&from, &to);
+        assert!(ray.t_min <= ray.t_max);
+        assert!(ray.contains(0.0));
+        assert!(!ray.contains(1e-4));
+    }
+
+    #[test]
+    fn ray_leaving_surface_starts_above_it_on_direction_side() {
+        let point = Point3::new(1000.0, 0.0, 0.0);
+        let normal = Vector3::x_axis();
+        let ray = Ray::leaving_surface(&point, &normal, Vector3::new(1.0, 1.0, 0.0));
+        assert!(ray.origin.x > point.x);
+        let ray = Ray::leaving_surface(&point, &normal, Vector3::new(-1.0, 1.0, 0.0));
+        assert!(ray.origin.x < point.x);
+    }
+
+    #[test]
+    fn ray_bounds_are_scaled_with_ray() {
+        let ray = Ray::new_bounded(
+            Point3::new(0.0, 0.0, 0.0),
+            Vector3::new(1.0, 0.0, 0.0),
+            T_MIN,
+            T_MAX,
+        );
+        let scaled = Similarity3::from_scaling(2.0) * ray;
+        assert_eq!(scaled.at(scaled.t_min), Point3::new(1.0, 0.0, 0.0));
+        assert_eq!(scaled.at(scaled.t_max), Point3::new(20.0, 0.0, 0.0));
+    }
+
     #[test]
     fn ray_can_be_multiplied_by_3d_matrix() {
         let origin = Point3::new(1.0, 2.0, -7.0);
         let direction = Unit::new_normalize(Vector3::new(1.0, 1.0, 1.0));
-        let ray = Ray { origin, direction };
+        let ray = Ray {
+            origin,
+            direction,
+            t_min: T_MIN,
+            t_max: T_MAX,
+        };
         #[rustfmt::skip]
         let mat = Matrix3::new(
             1.0, 2.0, 3.0,
             3.0, 1.5, -7.0,
             -std::f32::consts::PI, 1.57, -3.0
         );
-        let expected = Ray {
-            origin: mat * origin,
-            direction: Unit::new_normalize(mat * direction.into_inner()),
-        };
+        let expected = expected_ray(mat * origin, mat * direction.into_inner());
 
         assert_eq!(mat * ray, expected);
         assert_eq!(&mat * ray, expected);
@@ -129,12 +290,14 @@ mod tests {
     fn ray_can_be_rotated() {
         let origin = Point3::new(1.0, 2.0, -7.0);
         let direction = Unit::new_normalize(Vector3::new(1.0, 1.0, 1.0));
-        let ray = Ray { origin, direction };
-        let rotation = Rotation3::new(Vector3::new(1.57, 0.0, -0.75));
-        let expected = Ray {
-            origin: rotation * origin,
-            direction: Unit::new_normalize(rotation * direction.into_inner()),
+        let ray = Ray {
+            origin,
+            direction,
+            t_min: T_MIN,
+            t_max: T_MAX,
         };
+        let rotation = Rotation3::new(Vector3::new(1.57, 0.0, -0.75));
+        let expected = expected_ray(rotation * origin, rotation * direction.into_inner());
 
         assert_eq!(rotation * ray, expected);
         assert_eq!(&rotation * ray, expected);
@@ -146,11 +309,16 @@ mod tests {
     fn ray_can_be_translated() {
         let origin = Point3::new(1.0, 2.0, -7.0);
         let direction = Unit::new_normalize(Vector3::new(1.0, 1.0, 1.0));
-        let ray = Ray { origin, direction };
+        let ray = Ray {
+            origin,
+            direction,
+            t_min: T_MIN,
+            t_max: T_MAX,
+        };
         let translation = Translation3::new(-1.0, 2.5, 0.0);
         let expected = Ray {
             origin: translation * origin,
-            direction,
+            ..ray
         };
 
         assert_eq!(translation * ray, expected);
@@ -163,13 +331,18 @@ mod tests {
     fn ray_can_be_rotated_and_translated_by_two_transforms() {
         let origin = Point3::new(1.0, 2.0, -7.0);
         let direction = Unit::new_normalize(Vector3::new(1.0, 1.0, 1.0));
-        let ray = Ray { origin, direction };
+        let ray = Ray {
+            origin,
+            direction,
+            t_min: T_MIN,
+            t_max: T_MAX,
+        };
         let rotation = Rotation3::new(Vector3::new(1.57, 0.0, -0.75));
         let translation = Translation3::new(-1.0, 2.5, 0.0);
-        let expected = Ray {
-            origin: rotation * translation * origin,
-            direction: Unit::new_normalize(rotation * direction.into_inner()),
-        };
+        let expected = expected_ray(
+            rotation * translation * origin,
+            rotation * direction.into_inner(),
+        );
 
         assert_eq!(rotation * translation * ray, expected);
         assert_eq!(rotation * translation * &ray, expected);
@@ -179,12 +352,14 @@ mod tests {
     fn ray_can_be_rotated_and_translated() {
         let origin = Point3::new(1.0, 2.0, -7.0);
         let direction = Unit::new_normalize(Vector3::new(1.0, 1.0, 1.0));
-        let ray = Ray { origin, direction };
-        let isometry = Isometry3::new(Vector3::new(-1.0, 2.5, 0.0), Vector3::new(1.57, 0.0, -0.75));
-        let expected = Ray {
-            origin: isometry * origin,
-            direction: Unit::new_normalize(isometry * direction.into_inner()),
+        let ray = Ray {
+            origin,
+            direction,
+            t_min: T_MIN,
+            t_max: T_MAX,
         };
+        let isometry = Isometry3::new(Vector3::new(-1.0, 2.5, 0.0), Vector3::new(1.57, 0.0, -0.75));
+        let expected = expected_ray(isometry * origin, isometry * direction.into_inner());
 
         assert_eq!(isometry * ray, expected);
         assert_eq!(&isometry * ray, expected);
@@ -196,14 +371,16 @@ mod tests {
     fn ray_can_be_transformed_into_similar_ray() {
         let origin = Point3::new(1.0, 2.0, -7.0);
         let direction = Unit::new_normalize(Vector3::new(1.0, 1.0, 1.0));
-        let ray = Ray { origin, direction };
+        let ray = Ray {
+            origin,
+            direction,
+            t_min: T_MIN,
+            t_max: T_MAX,
+        };
         let translation = Translation3::new(-1.0, 2.5, 0.0);
         let rotation = Unit::new_normalize(Quaternion::new(1.75, 0.0, 1.0, 2.0));
         let similarity = Similarity3::from_parts(translation, rotation, 2.0);
-        let expected = Ray {
-            origin: similarity * origin,
-            direction: Unit::new_normalize(similarity * direction.into_inner()),
-        };
+        let expected = expected_ray(similarity * origin, similarity * direction.into_inner());
 
         assert_eq!(similarity * ray, expected);
         assert_eq!(&similarity * ray, expected);
@@ -215,7 +392,12 @@ mod tests {
     fn ray_can_be_transformed() {
         let origin = Point3::new(1.0, 2.0, -7.0);
         let direction = Unit::new_normalize(Vector3::new(1.0, 1.0, 1.0));
-        let ray = Ray { origin, direction };
+        let ray = Ray {
+            origin,
+            direction,
+            t_min: T_MIN,
+            t_max: T_MAX,
+        };
         #[rustfmt::skip]
         let transform = Transform3::from_matrix_unchecked(Matrix4::new(
             1.0, 2.0, 3.0, 0.0,
@@ -223,10 +405,7 @@ mod tests {
             -std::f32::consts::PI, 1.57, -3.0, 0.0,
             0.0, 1.57, 0.0, 1.0,
         ));
-        let expected = Ray {
-            origin: transform * origin,
-            direction: Unit::new_normalize(transform * direction.into_inner()),
-        };
+        let expected = expected_ray(transform * origin, transform * direction.into_inner());
 
         assert_eq!(transform * ray, expected);
         assert_eq!(&transform * ray, expected);
@@ -238,13 +417,18 @@ mod tests {
     fn ray_can_be_multiplied_by_4d_matrix() {
         let origin = Point3::new(1.0, 2.0, -7.0);
         let direction = Unit::new_normalize(Vector3::new(1.0, 1.0, 1.0));
-        let ray = Ray { origin, direction };
+        let ray = Ray {
+            origin,
+            direction,
+            t_min: T_MIN,
+            t_max: T_MAX,
+        };
         #[rustfmt::skip]
         let matrix = Matrix4::new_scaling(2.0);
-        let expected = Ray {
-            origin: matrix.transform_point(&origin),
-            direction: Unit::new_normalize(matrix.transform_vector(&direction)),
-        };
+        let expected = expected_ray(
+            matrix.transform_point(&origin),
+            matrix.transform_vector(&direction),
+        );
 
         assert_eq!(matrix * ray, expected);
         assert_eq!(&matrix * ray, expected);
@@ -0,0 +1,138 @@
+use image::{ImageBuffer, Rgb, RgbImage};
+
+use crate::{renderer::Tile, Colour, ScreenPoint};
+
+/// Floating point framebuffer, which stores colours of rendered pixels row by row
+#[derive(Debug, PartialEq, Clone)]
+pub struct Film {
+    width: u32,
+    height: u32,
+    pixels: Vec<Colour>,
+}
+
+impl Film {
+    /// Creates black film
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            width,
+            height,
+            pixels: vec![Colour::default(); width as usize * height as usize],
+        }
+    }
+
+    pub fn get_width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn get_height(&self) -> u32 {
+        self.height
+    }
+
+    /// Returns all pixels row by row
+    pub fn get_pixels(&self) -> &[Colour] {
+        &self.pixels
+    }
+
+    pub fn get_pixel(&self, point: ScreenPoint) -> &Colour {
+        &self.pixels[self.index(point)]
+    }
+
+    pub fn set_pixel(&mut self, point: ScreenPoint, colour: Colour) {
+        let index = self.index(point);
+        self.pixels[index] = colour;
+    }
+
+    /// Stores colours of tile pixels given row by row
+    pub fn write_tile(&mut self, tile: &Tile, colours: &[Colour]) {
+        assert_eq!(
+            colours.len(),
+            tile.width as usize * tile.height as usize,
+            "Colours do not match tile size"
+        );
+        for (point, colour) in tile.points().zip(colours) {
+            self.set_pixel(point, *colour);
+        }
+    }
+
+    /// Converts film to 8 bit image. Too bright colours are scaled down.
+    pub fn to_rgb_image(&self) -> RgbImage {
+        ImageBuffer::from_fn(self.width, self.height, |x, y| {
+            (*self.get_pixel(ScreenPoint::new(x, y))).into()
+        })
+    }
+
+    /// Converts film to floating point image without any clamping
+    pub fn to_rgb_f32_image(&self) -> ImageBuffer<Rgb<f32>, Vec<f32>> {
+        ImageBuffer::from_fn(self.width, self.height, |x, y| {
+            let colour = self.get_pixel(ScreenPoint::new(x, y));
+            Rgb([colour.red, colour.green, colour.blue])
+        })
+    }
+
+    fn index(&self, point: ScreenPoint) -> usize {
+        assert!(
+            point.x < self.width && point.y < self.height,
+            "Point outside of film"
+        );
+        point.y as usize * self.width as usize + point.x as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn new_film_is_black() {
+        let film = Film::new(3, 2);
+        assert_eq!(film.get_width(), 3);
+        assert_eq!(film.get_height(), 2);
+        assert_eq!(film.get_pixels(), &[Colour::default(); 6]);
+    }
+
+    #[test]
+    fn tile_is_written_row_by_row() {
+        let mut film = Film::new(4, 3);
+        let tile = Tile {
+            x: 1,
+            y: 1,
+            width: 2,
+            height: 2,
+        };
+        let colours: Vec<Colour> = (1..=4)
+            .map(|i| Colour {
+                red: i as f32,
+                ..Default::default()
+            })
+            .collect();
+        film.write_tile(&tile, &colours);
+        assert_eq!(film.get_pixel(ScreenPoint::new(1, 1)).red, 1.0);
+        assert_eq!(film.get_pixel(ScreenPoint::new(2, 1)).red, 2.0);
+        assert_eq!(film.get_pixel(ScreenPoint::new(1, 2)).red, 3.0);
+        assert_eq!(film.get_pixel(ScreenPoint::new(2, 2)).red, 4.0);
+        assert_eq!(film.get_pixel(ScreenPoint::new(0, 0)).red, 0.0);
+        assert_eq!(film.get_pixel(ScreenPoint::new(3, 2)).red, 0.0);
+    }
+
+    #[test]
+    fn film_converts_to_images() {
+        let mut film = Film::new(2, 1);
+        #[rustfmt::skip]
+        film.set_pixel(ScreenPoint::new(0, 0), Colour {red: 1.0, green: 0.5, blue: 0.0});
+        #[rustfmt::skip]
+        film.set_pixel(ScreenPoint::new(1, 0), Colour {red: 4.0, green: 2.0, blue: 0.0});
+
+        let image_data = film.to_rgb_image();
+        assert_eq!(*image_data.get_pixel(0, 0), Rgb([255, 127, 0]));
+        assert_eq!(*image_data.get_pixel(1, 0), Rgb([255, 127, 0]));
+
+        let hdr_data = film.to_rgb_f32_image();
+        assert_eq!(*hdr_data.get_pixel(1, 0), Rgb([4.0, 2.0, 0.0]));
+    }
+
+    #[test]
+    #[should_panic]
+    fn pixel_outside_film_panics() {
+        Film::new(2, 2).get_pixel(ScreenPoint::new(2, 0));
+    }
+}
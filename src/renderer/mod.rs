@@ -0,0 +1,6 @@
+mod film;
+pub use film::Film;
+
+#[allow(clippy::module_inception)]
+mod renderer;
+pub use renderer::{Cancelled, Progress, Renderer, Tile};
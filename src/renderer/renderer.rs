@@ -0,0 +1,454 @@
+use std::fmt;
+use std::num::NonZeroUsize;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+use rand::{rngs::StdRng, SeedableRng};
+
+use crate::{
+    integrator::{BeamTracer, Integrator, PathTracer},
+    renderer::Film,
+    Colour, Isometry3, Scalar, Scene, ScreenPoint, Viewport,
+};
+
+/// Rectangular part of the image rendered by one thread at a time
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub struct Tile {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// Progress of rendering reported after every finished tile
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub struct Progress {
+    /// Tile, which has just been rendered
+    pub tile: Tile,
+    pub rendered_tiles: usize,
+    pub total_tiles: usize,
+}
+
+/// Rendering was cancelled before all tiles were rendered
+#[derive(Debug, PartialEq, Clone)]
+pub struct Cancelled {
+    /// Film with tiles rendered before cancellation
+    pub film: Film,
+}
+
+/// Renders scene seen through viewport from camera placed in the world.
+/// Image is split into tiles, which are rendered in parallel. Every pixel
+/// draws random numbers from its own generator seeded from the renderer seed
+/// and pixel position, so the result does not depend on the number of threads.
+#[derive(Debug, Clone)]
+pub struct Renderer<'a> {
+    scene: &'a Scene,
+    viewport: &'a Viewport,
+    camera: Isometry3,
+    threads: usize,
+    tile_size: u32,
+    seed: u64,
+    integrator: &'a dyn Integrator,
+}
+
+impl Tile {
+    /// Iterates over points of the tile row by row
+    pub fn points(&self) -> impl Iterator<Item = ScreenPoint> {
+        let tile = *self;
+        (tile.y..tile.y + tile.height)
+            .flat_map(move |y| (tile.x..tile.x + tile.width).map(move |x| ScreenPoint::new(x, y)))
+    }
+}
+
+impl Progress {
+    /// Returns part of the image, which is already rendered
+    pub fn get_fraction(&self) -> Scalar {
+        self.rendered_tiles as Scalar / self.total_tiles as Scalar
+    }
+}
+
+impl fmt::Display for Cancelled {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "rendering cancelled")
+    }
+}
+
+impl std::error::Error for Cancelled {}
+
+impl<'a> Renderer<'a> {
+    pub const DEFAULT_TILE_SIZE: u32 = 32;
+    const DEFAULT_INTEGRATOR: BeamTracer =
+        BeamTracer::new(Some(PathTracer::DEFAULT_ROULETTE_DEPTH));
+
+    /// Creates renderer using all available cores.
+    /// `camera` transforms rays from camera space to world space.
+    pub fn new(scene: &'a Scene, viewport: &'a Viewport, camera: Isometry3) -> Self {
+        Self {
+            scene,
+            viewport,
+            camera,
+            threads: std::thread::available_parallelism().map_or(1, NonZeroUsize::get),
+            tile_size: Self::DEFAULT_TILE_SIZE,
+            seed: 0,
+            integrator: &Self::DEFAULT_INTEGRATOR,
+        }
+    }
+
+    /// Sets number of rendering threads
+    pub fn with_threads(self, threads: NonZeroUsize) -> Self {
+        Self {
+            threads: threads.get(),
+            ..self
+        }
+    }
+
+    /// Sets length of the tile side in pixels
+    pub fn with_tile_size(self, tile_size: u32) -> Self {
+        assert!(tile_size > 0, "Tile size has to be positive");
+        Self { tile_size, ..self }
+    }
+
+    /// Sets seed of random number generators
+    pub fn with_seed(self, seed: u64) -> Self {
+        Self { seed, ..self }
+    }
+
+    /// Sets algorithm computing colours of camera rays, `BeamTracer` by default
+    pub fn with_integrator(self, integrator: &'a dyn Integrator) -> Self {
+        Self { integrator, ..self }
+    }
+
+    pub fn get_threads(&self) -> usize {
+        self.threads
+    }
+
+    pub fn get_seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn get_width(&self) -> u32 {
+        self.viewport.get_width() as u32
+    }
+
+    pub fn get_height(&self) -> u32 {
+        self.viewport.get_height() as u32
+    }
+
+    /// Splits image into tiles row by row. Tiles at the right and bottom
+    /// edges are smaller, if image size is not a multiple of tile size.
+    pub fn get_tiles(&self) -> Vec<Tile> {
+        let (width, height, size) = (self.get_width(), self.get_height(), self.tile_size);
+        (0..height)
+            .step_by(size as usize)
+            .flat_map(|y| {
+                (0..width).step_by(size as usize).map(move |x| Tile {
+                    x,
+                    y,
+                    width: size.min(width - x),
+                    height: size.min(height - y),
+                })
+            })
+            .collect()
+    }
+
+    /// Computes colour of the pixel averaging all rays cast through it
+    pub fn render_pixel(&self, point: ScreenPoint) -> Colour {
+        let mut rng = StdRng::seed_from_u64(self.pixel_seed(point));
+        self.viewport
+            .cast_ray(point, &mut rng)
+            .map(|ray| self.camera * ray)
+            .map(|ray| self.integrator.trace(self.scene, &ray, &mut rng))
+            .fold(Colour::default(), |acc, x| acc + x)
+            / self.viewport.get_rays_count() as Scalar
+    }
+
+    /// Computes colours of tile pixels row by row
+    pub fn render_tile(&self, tile: &Tile) -> Vec<Colour> {
+        tile.points()
+            .map(|point| self.render_pixel(point))
+            .collect()
+    }
+
+    /// Renders whole image
+    pub fn render(&self) -> Film {
+        let never_cancelled = AtomicBool::new(false);
+        match self.render_with(|_| {}, &never_cancelled) {
+            Ok(film) => film,
+            Err(Cancelled { film }) => film,
+        }
+    }
+
+    /// Renders whole image calling `on_progress` after every tile.
+    /// Threads take tiles from a shared queue and stop taking them, once
+    /// `cancel` is set. Tiles being rendered at that moment are finished.
+    pub fn render_with<F>(&self, on_progress: F, cancel: &AtomicBool) -> Result<Film, Cancelled>
+    where
+        F: Fn(Progress) + Sync,
+    {
+        let tiles = self.get_tiles();
+        let next_tile = AtomicUsize::new(0);
+        let rendered_tiles = AtomicUsize::new(0);
+        let film = Mutex::new(Film::new(self.get_width(), self.get_height()));
+        std::thread::scope(|scope| {
+            for _ in 0..self.threads.min(tiles.len()) {
+                scope.spawn(|| {
+                    while !cancel.load(Ordering::Relaxed) {
+                        let tile = match tiles.get(next_tile.fetch_add(1, Ordering::Relaxed)) {
+                            Some(tile) => tile,
+                            None => break,
+                        };
+                        let colours = self.render_tile(tile);
+                        film.lock().unwrap().write_tile(tile, &colours);
+                        on_progress(Progress {
+                            tile: *tile,
+                            rendered_tiles: rendered_tiles.fetch_add(1, Ordering::Relaxed) + 1,
+                            total_tiles: tiles.len(),
+                        });
+                    }
+                });
+            }
+        });
+        let film = film.into_inner().unwrap();
+        if rendered_tiles.into_inner() < tiles.len() {
+            Err(Cancelled { film })
+        } else {
+            Ok(film)
+        }
+    }
+
+    /// Mixes renderer seed with pixel position, so that neighbouring pixels
+    /// get unrelated random streams
+    fn pixel_seed(&self, point: ScreenPoint) -> u64 {
+        let position = (u64::from(point.y) << 32) | u64::from(point.x);
+        split_mix(self.seed ^ split_mix(position))
+    }
+}
+
+/// SplitMix64 finalizer, a bijective hash of 64 bit values
+fn split_mix(value: u64) -> u64 {
+    let mut z = value.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        integrator::{AmbientOcclusion, DebugIntegrator, PathTracer, WhittedTracer},
+        primitives::Triangle,
+        Material, Point3, UniformSampler, Vector3,
+    };
+
+    fn scene() -> Scene {
+        let mut scene = Scene::new(
+            Material::diffuse(Colour {
+                red: 0.5,
+                green: 0.5,
+                blue: 0.5,
+            }),
+            2,
+            3,
+        );
+        let triangle = Triangle::new([
+            Point3::new(1.0, -1.0, 0.0),
+            Point3::new(0.0, 1.0, 0.0),
+            Point3::new(-1.0, -1.0, 0.0),
+        ]);
+        scene.add(
+            triangle,
+            Material::diffuse(Colour {
+                red: 1.0,
+                green: 0.2,
+                blue: 0.0,
+            }),
+        );
+        scene
+    }
+
+    fn camera() -> Isometry3 {
+        Isometry3::look_at_rh(
+            &Point3::new(0.0, 0.0, 3.0),
+            &Point3::origin(),
+            &Vector3::y(),
+        )
+        .inverse()
+    }
+
+    fn threads(count: usize) -> NonZeroUsize {
+        NonZeroUsize::new(count).unwrap()
+    }
+
+    #[test]
+    fn tiles_cover_image_once() {
+        let scene = scene();
+        let viewport = Viewport::new(23, 10, 1.0, 0.1, 100.0, 1);
+        let renderer = Renderer::new(&scene, &viewport, camera()).with_tile_size(8);
+        let tiles = renderer.get_tiles();
+        assert_eq!(tiles.len(), 6);
+        assert_eq!(
+            tiles[5],
+            Tile {
+                x: 16,
+                y: 8,
+                width: 7,
+                height: 2
+            }
+        );
+        let mut covered = vec![0; 23 * 10];
+        for point in tiles.iter().flat_map(Tile::points) {
+            covered[(point.y * 23 + point.x) as usize] += 1;
+        }
+        assert!(covered.iter().all(|&count| count == 1));
+    }
+
+    #[test]
+    fn render_matches_pixel_by_pixel_rendering() {
+        let scene = scene();
+        let viewport = Viewport::new(12, 9, 1.0, 0.1, 100.0, 2);
+        let renderer = Renderer::new(&scene, &viewport, camera())
+            .with_tile_size(5)
+            .with_threads(threads(3));
+        let film = renderer.render();
+        for (i, pixel) in film.get_pixels().iter().enumerate() {
+            let (x, y) = (i as u32 % 12, i as u32 / 12);
+            assert_eq!(*pixel, renderer.render_pixel(ScreenPoint::new(x, y)));
+        }
+    }
+
+    #[test]
+    fn render_is_deterministic_for_any_thread_count() {
+        let scene = scene();
+        let viewport = Viewport::new(16, 12, 1.0, 0.1, 100.0, 2);
+        let render = |threads_count, seed| {
+            Renderer::new(&scene, &viewport, camera())
+                .with_tile_size(4)
+                .with_threads(threads(threads_count))
+                .with_seed(seed)
+                .render()
+        };
+        let reference = render(1, 7);
+        assert_eq!(render(1, 7), reference);
+        assert_eq!(render(4, 7), reference);
+        assert_eq!(render(16, 7), reference);
+    }
+
+    #[test]
+    fn renderer_uses_given_integrator() {
+        let scene = scene();
+        let viewport = Viewport::new(8, 6, 1.0, 0.1, 100.0, 2);
+        let path = PathTracer::default();
+        let depth = DebugIntegrator::Depth { max_distance: 1e3 };
+        let integrators: [&dyn Integrator; 4] =
+            [&path, &WhittedTracer, &AmbientOcclusion::default(), &depth];
+        for integrator in integrators {
+            let render = |threads_count| {
+                Renderer::new(&scene, &viewport, camera())
+                    .with_tile_size(3)
+                    .with_threads(threads(threads_count))
+                    .with_integrator(integrator)
+                    .render()
+            };
+            let film = render(1);
+            assert_eq!(render(4), film, "{:?}", integrator);
+            assert_ne!(
+                film,
+                Renderer::new(&scene, &viewport, camera()).render(),
+                "{:?}",
+                integrator
+            );
+        }
+    }
+
+    #[test]
+    fn seed_changes_random_samples() {
+        let scene = scene();
+        let viewport = Viewport::new(16, 12, 1.0, 0.1, 100.0, 2).with_sampler(UniformSampler);
+        let render = |seed| {
+            Renderer::new(&scene, &viewport, camera())
+                .with_seed(seed)
+                .render()
+        };
+        assert_eq!(render(3), render(3));
+        assert_ne!(render(3), render(4));
+    }
+
+    #[test]
+    fn progress_is_reported_for_every_tile() {
+        let scene = scene();
+        let viewport = Viewport::new(10, 10, 1.0, 0.1, 100.0, 1);
+        let renderer = Renderer::new(&scene, &viewport, camera())
+            .with_tile_size(3)
+            .with_threads(threads(4));
+        let reported = Mutex::new(Vec::new());
+        let film = renderer
+            .render_with(
+                |progress| reported.lock().unwrap().push(progress),
+                &AtomicBool::new(false),
+            )
+            .unwrap();
+        let mut reported = reported.into_inner().unwrap();
+        assert_eq!(film, renderer.render());
+        assert_eq!(reported.len(), 16);
+        reported.sort_by_key(|progress| progress.rendered_tiles);
+        assert!(reported
+            .iter()
+            .enumerate()
+            .all(|(i, progress)| progress.rendered_tiles == i + 1 && progress.total_tiles == 16));
+        assert_eq!(reported.last().unwrap().get_fraction(), 1.0);
+        let mut tiles: Vec<Tile> = reported.iter().map(|progress| progress.tile).collect();
+        tiles.sort_by_key(|tile| (tile.y, tile.x));
+        assert_eq!(tiles, renderer.get_tiles());
+    }
+
+    #[test]
+    fn rendering_can_be_cancelled() {
+        let scene = scene();
+        let viewport = Viewport::new(10, 10, 1.0, 0.1, 100.0, 1);
+        let renderer = Renderer::new(&scene, &viewport, camera())
+            .with_tile_size(2)
+            .with_threads(threads(1));
+        let cancel = AtomicBool::new(false);
+        let result = renderer.render_with(
+            |progress| {
+                if progress.rendered_tiles == 3 {
+                    cancel.store(true, Ordering::Relaxed);
+                }
+            },
+            &cancel,
+        );
+        let film = match result {
+            Err(Cancelled { film }) => film,
+            Ok(_) => panic!("rendering should be cancelled"),
+        };
+        let full = renderer.render();
+        // Only the first three tiles in the top row are rendered
+        for (i, pixel) in film.get_pixels().iter().enumerate() {
+            let (x, y) = (i % 10, i / 10);
+            if x < 6 && y < 2 {
+                assert_eq!(pixel, &full.get_pixels()[i]);
+            } else {
+                assert_eq!(pixel, &Colour::default());
+            }
+        }
+
+        let cancelled = AtomicBool::new(true);
+        assert!(renderer.render_with(|_| {}, &cancelled).is_err());
+    }
+
+    #[test]
+    fn pixels_get_different_seeds() {
+        let scene = scene();
+        let viewport = Viewport::new(64, 64, 1.0, 0.1, 100.0, 1);
+        let renderer = Renderer::new(&scene, &viewport, camera());
+        let other = renderer.clone().with_seed(1);
+        let mut seeds: Vec<u64> = (0..64)
+            .flat_map(|y| (0..64).map(move |x| ScreenPoint::new(x, y)))
+            .flat_map(|point| vec![renderer.pixel_seed(point), other.pixel_seed(point)])
+            .collect();
+        seeds.sort_unstable();
+        seeds.dedup();
+        assert_eq!(seeds.len(), 2 * 64 * 64);
+    }
+}
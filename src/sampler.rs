@@ -0,0 +1,293 @@
+use std::fmt;
+
+use rand::{Rng, RngCore};
+
+use crate::{Point2, Scalar};
+
+/// Generator of sample points in the unit square `[0, 1)²`,
+/// used to spread rays cast through a pixel
+pub trait Sampler: fmt::Debug + Send + Sync {
+    /// Returns `count` points for one pixel. Randomized samplers draw random
+    /// numbers from `rng`, so that every pixel gets a different pattern.
+    fn get_samples(&self, count: usize, rng: &mut dyn RngCore) -> Vec<Point2>;
+}
+
+/// Independent, uniformly distributed random points
+#[derive(Debug, PartialEq, Eq, Copy, Clone, Default)]
+pub struct UniformSampler;
+
+/// Points placed in cells of a grid covering the square, one per cell.
+/// If count is not a square number, the last row has fewer, wider cells
+/// and it is lower, so that all cells have the same area.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub struct StratifiedSampler {
+    /// Points are moved randomly within their cells instead of lying in their centers
+    pub jittered: bool,
+}
+
+/// Halton sequence in bases 2 and 3, randomly shifted modulo 1 for every pixel
+#[derive(Debug, PartialEq, Eq, Copy, Clone, Default)]
+pub struct HaltonSampler;
+
+/// The first two dimensions of Sobol' sequence, scrambled for every pixel
+/// by random digit flips, which keep their stratification properties
+#[derive(Debug, PartialEq, Eq, Copy, Clone, Default)]
+pub struct SobolSampler;
+
+/// Largest Scalar smaller than one, used to keep samples in `[0, 1)`
+const ONE_MINUS_EPSILON: Scalar = 1.0 - Scalar::EPSILON / 2.0;
+
+impl Sampler for UniformSampler {
+    fn get_samples(&self, count: usize, rng: &mut dyn RngCore) -> Vec<Point2> {
+        (0..count)
+            .map(|_| Point2::new(rng.gen(), rng.gen()))
+            .collect()
+    }
+}
+
+impl StratifiedSampler {
+    pub fn new(jittered: bool) -> Self {
+        Self { jittered }
+    }
+}
+
+impl Default for StratifiedSampler {
+    fn default() -> Self {
+        Self::new(true)
+    }
+}
+
+impl Sampler for StratifiedSampler {
+    fn get_samples(&self, count: usize, rng: &mut dyn RngCore) -> Vec<Point2> {
+        let columns = (count as Scalar).sqrt().ceil().max(1.0) as usize;
+        (0..count)
+            .map(|i| {
+                let (dx, dy) = if self.jittered {
+                    (rng.gen(), rng.gen())
+                } else {
+                    (0.5, 0.5)
+                };
+                // Height of a row is proportional to the number of its cells
+                let row_start = i - i % columns;
+                let cells = columns.min(count - row_start);
+                Point2::new(
+                    ((i - row_start) as Scalar + dx) / cells as Scalar,
+                    (row_start as Scalar + dy * cells as Scalar) / count as Scalar,
+                )
+            })
+            .map(|p| p.map(|c| c.min(ONE_MINUS_EPSILON)))
+            .collect()
+    }
+}
+
+impl HaltonSampler {
+    /// Returns `index`-th point of unshifted Halton sequence
+    pub fn get_point(index: u32) -> Point2 {
+        Point2::new(radical_inverse(2, index), radical_inverse(3, index))
+    }
+}
+
+impl Sampler for HaltonSampler {
+    fn get_samples(&self, count: usize, rng: &mut dyn RngCore) -> Vec<Point2> {
+        let shift = Point2::new(rng.gen::<Scalar>(), rng.gen::<Scalar>());
+        (0..count as u32)
+            .map(|i| {
+                let point = Self::get_point(i);
+                Point2::new(
+                    ((point.x + shift.x) % 1.0).min(ONE_MINUS_EPSILON),
+                    ((point.y + shift.y) % 1.0).min(ONE_MINUS_EPSILON),
+                )
+            })
+            .collect()
+    }
+}
+
+impl SobolSampler {
+    /// Returns `index`-th point of Sobol' sequence with digits flipped by `scramble`
+    pub fn get_point(index: u32, scramble: [u32; 2]) -> Point2 {
+        Point2::new(
+            to_unit(index.reverse_bits() ^ scramble[0]),
+            to_unit(sobol_second_dimension(index) ^ scramble[1]),
+        )
+    }
+}
+
+impl Sampler for SobolSampler {
+    fn get_samples(&self, count: usize, rng: &mut dyn RngCore) -> Vec<Point2> {
+        let scramble = [rng.next_u32(), rng.next_u32()];
+        (0..count as u32)
+            .map(|i| Self::get_point(i, scramble))
+            .collect()
+    }
+}
+
+/// Mirrors digits of `index` in given base around the radix point
+fn radical_inverse(base: u32, mut index: u32) -> Scalar {
+    let inverse_base = 1.0 / base as f64;
+    let mut factor = inverse_base;
+    let mut result = 0.0;
+    while index > 0 {
+        result += (index % base) as f64 * factor;
+        index /= base;
+        factor *= inverse_base;
+    }
+    (result as Scalar).min(ONE_MINUS_EPSILON)
+}
+
+/// Generator matrix of the second Sobol' dimension is the Pascal matrix modulo 2,
+/// which can be applied by xoring shifted columns
+fn sobol_second_dimension(mut index: u32) -> u32 {
+    let mut column: u32 = 1 << 31;
+    let mut result = 0;
+    while index > 0 {
+        if index & 1 == 1 {
+            result ^= column;
+        }
+        index >>= 1;
+        column ^= column >> 1;
+    }
+    result
+}
+
+/// Maps 32 bit fraction to `[0, 1)` keeping as many bits, as Scalar can represent
+fn to_unit(bits: u32) -> Scalar {
+    (bits >> 8) as Scalar / (1 << 24) as Scalar
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    fn samples(sampler: &dyn Sampler, count: usize, seed: u64) -> Vec<Point2> {
+        sampler.get_samples(count, &mut StdRng::seed_from_u64(seed))
+    }
+
+    /// Counts points in cells of `size`×`size` grid
+    fn histogram(points: &[Point2], size: usize) -> Vec<usize> {
+        let mut cells = vec![0; size * size];
+        for p in points {
+            let cell = (p.y * size as Scalar) as usize * size + (p.x * size as Scalar) as usize;
+            cells[cell] += 1;
+        }
+        cells
+    }
+
+    fn all_samplers() -> Vec<Box<dyn Sampler>> {
+        vec![
+            Box::new(UniformSampler),
+            Box::new(StratifiedSampler::new(true)),
+            Box::new(StratifiedSampler::new(false)),
+            Box::new(HaltonSampler),
+            Box::new(SobolSampler),
+        ]
+    }
+
+    #[test]
+    fn samples_lie_in_unit_square() {
+        for sampler in all_samplers() {
+            for count in [0, 1, 2, 7, 16, 100] {
+                let points = samples(sampler.as_ref(), count, 3);
+                assert_eq!(points.len(), count, "{:?}", sampler);
+                assert!(
+                    points
+                        .iter()
+                        .all(|p| (0.0..1.0).contains(&p.x) && (0.0..1.0).contains(&p.y)),
+                    "{:?}",
+                    sampler
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn samples_are_distinct() {
+        for sampler in all_samplers() {
+            let mut points = samples(sampler.as_ref(), 64, 5);
+            points.sort_by(|a, b| (a.x, a.y).partial_cmp(&(b.x, b.y)).unwrap());
+            points.dedup();
+            assert_eq!(points.len(), 64, "{:?}", sampler);
+        }
+    }
+
+    #[test]
+    fn uniform_samples_cover_square_evenly() {
+        let counts = histogram(&samples(&UniformSampler, 1600, 7), 4);
+        assert!(
+            counts.iter().all(|&c| (50..150).contains(&c)),
+            "{:?}",
+            counts
+        );
+    }
+
+    #[test]
+    fn stratified_samples_fill_every_cell() {
+        for jittered in [true, false] {
+            let counts = histogram(&samples(&StratifiedSampler::new(jittered), 16, 7), 4);
+            assert_eq!(counts, vec![1; 16]);
+        }
+        let centered = samples(&StratifiedSampler::new(false), 4, 0);
+        assert_eq!(centered[0], Point2::new(0.25, 0.25));
+        assert_eq!(centered[3], Point2::new(0.75, 0.75));
+    }
+
+    #[test]
+    fn stratified_cells_cover_square_for_any_count() {
+        // Cells have equal areas, so quadrants get equal shares of samples on average
+        for count in [2, 3, 5, 7, 10] {
+            let mut rng = StdRng::seed_from_u64(count as u64);
+            let points: Vec<_> = (0..4000)
+                .flat_map(|_| StratifiedSampler::new(true).get_samples(count, &mut rng))
+                .collect();
+            let counts = histogram(&points, 2);
+            let expected = points.len() / 4;
+            assert!(
+                counts.iter().all(|&c| c.abs_diff(expected) < expected / 20),
+                "{}: {:?}",
+                count,
+                counts
+            );
+        }
+        let centered = samples(&StratifiedSampler::new(false), 3, 0);
+        assert_eq!(centered[2], Point2::new(0.5, 5.0 / 6.0));
+    }
+
+    #[test]
+    fn low_discrepancy_samples_fill_every_cell() {
+        // The first 2^(2k) points of Sobol' sequence form a (0, 2k, 2)-net
+        for seed in 0..10 {
+            let counts = histogram(&samples(&SobolSampler, 64, seed), 8);
+            assert_eq!(counts, vec![1; 64]);
+        }
+        // Halton points are not a net, but they are close to one
+        for seed in 0..10 {
+            let counts = histogram(&samples(&HaltonSampler, 64, seed), 4);
+            assert!(counts.iter().all(|&c| (2..=6).contains(&c)), "{:?}", counts);
+        }
+    }
+
+    #[test]
+    fn randomized_samplers_differ_between_pixels() {
+        for sampler in all_samplers() {
+            if format!("{:?}", sampler).contains("jittered: false") {
+                continue;
+            }
+            assert_ne!(
+                samples(sampler.as_ref(), 8, 1),
+                samples(sampler.as_ref(), 8, 2),
+                "{:?}",
+                sampler
+            );
+        }
+    }
+
+    #[test]
+    fn known_sequence_points() {
+        assert_eq!(HaltonSampler::get_point(0), Point2::new(0.0, 0.0));
+        assert!((HaltonSampler::get_point(1) - Point2::new(0.5, 1.0 / 3.0)).norm() < 1e-6);
+        assert!((HaltonSampler::get_point(5) - Point2::new(0.625, 7.0 / 9.0)).norm() < 1e-6);
+        assert_eq!(SobolSampler::get_point(1, [0, 0]), Point2::new(0.5, 0.5));
+        assert_eq!(SobolSampler::get_point(2, [0, 0]), Point2::new(0.25, 0.75));
+        assert_eq!(SobolSampler::get_point(3, [0, 0]), Point2::new(0.75, 0.25));
+    }
+}
@@ -1,28 +1,39 @@
 use crate::{
-    primitives::Triangle, Colour, Material, Point3, Ray, RayTraceable, Rotation3, Scalar, Vector3,
+    bvh::Bvh,
+    environment::Environment,
+    integrator::{BeamTracer, Integrator},
+    lights::{AreaLights, Light, LightSample},
+    primitives::Primitive,
+    Colour, Intersection, Material, Point2, Ray, RayTraceable, Scalar, Vector3,
 };
-use nalgebra::{Reflection, Unit};
-use rand::prelude::*;
+use rand::RngCore;
+use std::sync::OnceLock;
 
 /// Helper struct describing hit result
 #[derive(Debug, PartialEq, Copy, Clone)]
 struct HitResult {
-    pub point: Point3,
+    pub intersection: Intersection,
     pub index: usize,
 }
 
-/// Helper struct describing trace result
-#[derive(Debug, PartialEq, Copy, Clone, Default)]
-struct TraceResult {
-    pub diffuse: Colour,
-    pub emission: Colour,
+/// Hit of any primitive in the scene together with its material
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct SceneHit<'a> {
+    pub intersection: Intersection,
+    pub material: &'a Material,
+    pub primitive: &'a Primitive,
+    pub primitive_size: Scalar,
 }
 
-/// Scene helper to organize and ray trace primitives of one type
-#[derive(Debug, PartialEq, Clone)]
+/// Scene helper to organize and ray trace primitives of one type.
+/// Bounding volume hierarchy over primitives and list of lights are built
+/// lazily on the first query and dropped whenever a primitive is added.
+#[derive(Debug, Clone)]
 struct PrimitivesWithMaterials<P: RayTraceable> {
     primitives: Vec<P>,
     materials: Vec<Material>,
+    bvh: OnceLock<Bvh>,
+    lights: OnceLock<AreaLights>,
 }
 
 /// Ray traceable scene
@@ -31,7 +42,9 @@ pub struct Scene {
     default_material: Material,
     recursion_depth: usize,
     beam_rays_count: usize,
-    triangles: PrimitivesWithMaterials<Triangle>,
+    primitives: PrimitivesWithMaterials<Primitive>,
+    lights: Vec<Light>,
+    environment: Option<Environment>,
 }
 
 impl Scene {
@@ -41,77 +54,93 @@ impl Scene {
             default_material,
             recursion_depth,
             beam_rays_count,
-            triangles: PrimitivesWithMaterials::new(),
+            primitives: PrimitivesWithMaterials::new(),
+            lights: Vec::new(),
+            environment: None,
         }
     }
 
-    /// Adds triangle to the scene
-    pub fn add_triangle(&mut self, triangle: Triangle, material: Material) {
-        self.triangles.add(triangle, material)
+    /// Returns material of the background, which is seen by rays leaving the scene
+    pub fn get_default_material(&self) -> &Material {
+        &self.default_material
     }
 
-    /// Traces ray emission
-    pub fn trace(&self, ray: &Ray) -> Colour {
-        self.trace_until(ray, 0).diffuse
+    /// Lights the scene with environment map or sky, which replaces emission of the default material
+    pub fn set_environment<E: Into<Environment>>(&mut self, environment: E) {
+        self.environment = Some(environment.into())
     }
 
-    fn trace_until(&self, ray: &Ray, step: usize) -> TraceResult {
-        let hit = self.closest_hit(ray);
-        if hit == None {
-            return TraceResult::from(self.default_material);
-        }
-        let hit = hit.unwrap();
-        let mut trace_result = TraceResult {
-            ..Default::default()
-        };
-        let material = self.triangles.get_material(hit.index);
-
-        if step < self.recursion_depth {
-            let reflected_ray = self.get_reflected_ray(&ray, &hit);
-            let primitive_size = self.triangles.get_primitive(hit.index).get_size();
-            for beam_ray in self.get_beam(reflected_ray, primitive_size) {
-                let tr = self.trace_until(&beam_ray, step + 1);
-                trace_result.add_light(&tr);
-            }
-            trace_result.emission /= self.beam_rays_count as Scalar;
-        } else {
-            trace_result = TraceResult::from(self.default_material);
+    pub fn get_environment(&self) -> Option<&Environment> {
+        self.environment.as_ref()
+    }
+
+    /// Returns radiance seen by rays leaving the scene in given direction
+    pub fn get_background(&self, direction: &Vector3) -> Colour {
+        match &self.environment {
+            Some(environment) => environment.get_radiance(direction),
+            None => self.default_material.emission,
         }
-        trace_result.apply_to(material)
     }
 
-    fn closest_hit(&self, ray: &Ray) -> Option<HitResult> {
-        self.triangles.closest_hit(ray)
+    pub fn get_recursion_depth(&self) -> usize {
+        self.recursion_depth
     }
 
-    fn get_reflected_ray(&self, ray: &Ray, hit: &HitResult) -> Ray {
-        let mut vector = ray.direction.into_inner().clone_owned();
-        let reflection = Reflection::new_containing_point(
-            self.triangles.get_primitive(hit.index).get_normal(),
-            &hit.point,
-        );
-        reflection.reflect(&mut vector);
-        let reflected_direction = Unit::new_normalize(vector);
-        Ray {
-            // Move ray origin away from target in order to avoid infinite self reflections
-            origin: hit.point + 2.0 * Scalar::EPSILON * reflected_direction.into_inner(),
-            direction: reflected_direction,
+    pub fn get_beam_rays_count(&self) -> usize {
+        self.beam_rays_count
+    }
+
+    /// Adds any primitive to the scene
+    pub fn add<P: Into<Primitive>>(&mut self, primitive: P, material: Material) {
+        self.primitives.add(primitive.into(), material)
+    }
+
+    /// Adds punctual light to the scene
+    pub fn add_light<L: Into<Light>>(&mut self, light: L) {
+        self.lights.push(light.into())
+    }
+
+    /// Returns punctual lights. Emissive primitives are not included.
+    pub fn get_lights(&self) -> &[Light] {
+        &self.lights
+    }
+
+    /// Traces ray emission with `BeamTracer` drawing random numbers from given generator.
+    /// The same generator state gives the same result.
+    pub fn trace(&self, ray: &Ray, rng: &mut dyn RngCore) -> Colour {
+        BeamTracer::default().trace(self, ray, rng)
+    }
+
+    /// Samples point on emissive triangles, including faces of meshes, with density
+    /// proportional to their area. `selector` and `u` should be uniformly distributed
+    /// in `[0, 1)` and the unit square. Emissive spheres are never sampled.
+    pub fn sample_light(&self, selector: Scalar, u: &Point2) -> Option<LightSample> {
+        self.primitives.get_area_lights().sample(selector, u)
+    }
+
+    /// Returns density with respect to solid angle, with which direction of the ray
+    /// towards the hit is generated by sampling a point with `sample_light`.
+    /// It is zero, if the hit surface is not one of the sampled lights.
+    pub fn get_light_pdf(&self, ray: &Ray, hit: &SceneHit<'_>) -> Scalar {
+        let is_light = match hit.primitive {
+            Primitive::Triangle(_) | Primitive::Mesh(_) => {
+                hit.material.emission != Colour::default()
+            }
+            Primitive::Sphere(_) => false,
+        };
+        let lights = self.primitives.get_area_lights();
+        let cosine = ray.direction.dot(&hit.intersection.normal).abs();
+        if !is_light || lights.is_empty() || cosine == 0.0 {
+            return 0.0;
         }
+        hit.intersection.t * hit.intersection.t / (cosine * lights.get_total_area())
     }
 
-    fn get_beam(&self, ray: Ray, primitive_size: Scalar) -> impl Iterator<Item = Ray> {
-        let rotation =
-            Rotation3::rotation_between(&Vector3::new(0.0, 0.0, 1.0), &ray.direction.into_inner())
-                .unwrap_or_else(Rotation3::identity);
-        let mut randomness = thread_rng();
-        (0..self.beam_rays_count)
-            .map(move |_| {
-                let r = randomness.gen_range(0.0..(0.005 * primitive_size));
-                let alpha = randomness.gen_range(0.0..(2.0 * std::f32::consts::PI));
-                Vector3::new(r * alpha.cos(), r * alpha.sin(), 0.0)
-            })
-            .map(move |circle_vec| rotation * circle_vec)
-            .map(move |v| Ray::new(ray.origin, ray.direction.into_inner() + v))
+    /// Finds the closest primitive hit by ray
+    pub fn closest_hit(&self, ray: &Ray) -> Option<SceneHit<'_>> {
+        self.primitives
+            .closest_hit(ray)
+            .map(|hit| self.primitives.describe_hit(&hit))
     }
 }
 
@@ -121,31 +150,40 @@ impl<P: RayTraceable> PrimitivesWithMaterials<P> {
         Self {
             primitives: Vec::new(),
             materials: Vec::new(),
+            bvh: OnceLock::new(),
+            lights: OnceLock::new(),
         }
     }
     /// Adds primitive with material and keeps indices synchronized
     pub fn add(&mut self, primitive: P, material: Material) {
         self.primitives.push(primitive);
         self.materials.push(material);
+        self.bvh = OnceLock::new();
+        self.lights = OnceLock::new();
     }
 
     /// Finds primitive closest to ray's origin
     pub fn closest_hit(&self, ray: &Ray) -> Option<HitResult> {
-        let hit = self
-            .primitives
-            .iter()
-            .enumerate()
-            .filter_map(|(i, t)| match t.intersects(&ray) {
-                Some(p) => Some((i, p)),
-                None => None,
-            })
-            .map(|(i, p)| (i, p, (p - ray.origin).norm()))
-            .filter(|(_, _, d)| !d.is_nan())
-            .min_by(|&(_, _, d1), &(_, _, d2)| d1.partial_cmp(&d2).unwrap())?;
+        let (index, intersection) = self.get_bvh().closest_hit(ray, |i, closest| {
+            // Primitives farther than the closest hit so far can be skipped
+            let ray = Ray {
+                t_max: closest,
+                ..*ray
+            };
+            let intersection = self.primitives[i].intersects(&ray)?;
+            Some((intersection.t, intersection))
+        })?;
 
         Some(HitResult {
-            point: hit.1,
-            index: hit.0,
+            intersection,
+            index,
+        })
+    }
+
+    fn get_bvh(&self) -> &Bvh {
+        self.bvh.get_or_init(|| {
+            let bounds: Vec<_> = self.primitives.iter().map(|p| p.get_bounds()).collect();
+            Bvh::new(&bounds)
         })
     }
 
@@ -158,42 +196,66 @@ impl<P: RayTraceable> PrimitivesWithMaterials<P> {
     }
 }
 
-impl TraceResult {
-    pub fn add_light(&mut self, other: &Self) {
-        self.emission += other.emission;
+impl PrimitivesWithMaterials<Primitive> {
+    /// Gathers information about hit primitive.
+    /// Materials defined by primitive faces take precedence.
+    fn describe_hit(&self, hit: &HitResult) -> SceneHit<'_> {
+        let primitive = self.get_primitive(hit.index);
+        SceneHit {
+            intersection: hit.intersection,
+            material: primitive
+                .get_face_material(hit.intersection.face)
+                .unwrap_or_else(|| self.get_material(hit.index)),
+            primitive,
+            primitive_size: primitive.get_size(),
+        }
     }
 
-    pub fn apply_to(&self, material: &Material) -> Self {
-        Self {
-            emission: material.emission + material.diffuse * self.emission,
-            diffuse: material.emission + material.diffuse * self.emission,
-        }
+    /// Collects triangles and faces of meshes with emissive materials
+    fn get_area_lights(&self) -> &AreaLights {
+        self.lights.get_or_init(|| {
+            let mut triangles = Vec::new();
+            for (primitive, material) in self.primitives.iter().zip(&self.materials) {
+                match primitive {
+                    Primitive::Triangle(triangle) => triangles.push((*triangle, material.emission)),
+                    Primitive::Mesh(mesh) => {
+                        triangles.extend((0..mesh.get_faces().len()).map(|face| {
+                            let emission =
+                                mesh.get_face_material(face).unwrap_or(material).emission;
+                            (mesh.get_triangle(face), emission)
+                        }))
+                    }
+                    Primitive::Sphere(_) => {}
+                }
+            }
+            triangles.retain(|(_, emission)| *emission != Colour::default());
+            AreaLights::new(triangles)
+        })
     }
 }
 
-impl From<Material> for TraceResult {
-    fn from(material: Material) -> Self {
-        Self {
-            emission: material.emission,
-            diffuse: material.diffuse,
-        }
+impl<P: RayTraceable + PartialEq> PartialEq for PrimitivesWithMaterials<P> {
+    fn eq(&self, other: &Self) -> bool {
+        // Acceleration structure and lights are derived data, so they are not compared
+        self.primitives == other.primitives && self.materials == other.materials
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::{Rotation3, Translation3, Vector3};
+    use crate::{
+        primitives::{Mesh, Sphere, Triangle},
+        Bsdf, Point3, Rotation3, Translation3, Vector3,
+    };
+    use rand::{rngs::StdRng, Rng, SeedableRng};
 
     #[test]
     fn tracing_scene_with_bouncing_rays_and_solid_triangles() {
+        #[rustfmt::skip]
         let mut scene = Scene::new(
-            Material {
-                #[rustfmt::skip]
-                diffuse: Colour {red: 0.0, green: 0.0, blue: 0.0,},
-                #[rustfmt::skip]
-                emission: Colour {red: 1.0, green: 1.0, blue: 1.0,},
-            },
+            Material::diffuse(Colour {red: 0.0, green: 0.0, blue: 0.0,})
+                .with_emission(Colour {red: 1.0, green: 1.0, blue: 1.0,}),
             2,
             1,
         );
@@ -206,21 +268,15 @@ mod tests {
 
         // Yellow triangle partially hidden by red triangle. Both rotated,
         // to get red 'shadow' on yellow triangle.
-        scene.add_triangle(
+        #[rustfmt::skip]
+        scene.add(
             rotation * triangle,
-            Material {
-                #[rustfmt::skip]
-                diffuse: Colour {red: 0.75, green: 1.0, blue: 0.0,},
-                ..Default::default()
-            },
+            Material::diffuse(Colour {red: 0.75, green: 1.0, blue: 0.0,}),
         );
-        scene.add_triangle(
+        #[rustfmt::skip]
+        scene.add(
             rotation * Translation3::new(0.5f32, 0.0, 2.0) * triangle,
-            Material {
-                #[rustfmt::skip]
-                diffuse: Colour {red: 1.0, green: 0.0, blue: 0.0,},
-                ..Default::default()
-            },
+            Material::diffuse(Colour {red: 1.0, green: 0.0, blue: 0.0,}),
         );
         // Ray into abyss
         let ray = Ray::new(
@@ -229,7 +285,7 @@ mod tests {
         );
         #[rustfmt::skip]
         assert_eq!(
-            Colour{red: 0.0, green: 0.0, blue: 0.0}, scene.trace(&ray),
+            Colour{red: 0.0, green: 0.0, blue: 0.0}, scene.trace(&ray, &mut StdRng::seed_from_u64(0)),
             "ray should not hit anything"
         );
         // Ray into yellow triangle
@@ -239,7 +295,7 @@ mod tests {
         );
         #[rustfmt::skip]
         assert_eq!(
-            Colour{red: 0.75, green: 1.0, blue: 0.0}, scene.trace(&ray),
+            Colour{red: 0.75, green: 1.0, blue: 0.0}, scene.trace(&ray, &mut StdRng::seed_from_u64(0)),
             "ray should hit yellow triangle"
         );
         // Ray into red triangle
@@ -249,14 +305,14 @@ mod tests {
         );
         #[rustfmt::skip]
         assert_eq!(
-            Colour{red: 1.0, green: 0.0, blue: 0.0}, scene.trace(&ray),
+            Colour{red: 1.0, green: 0.0, blue: 0.0}, scene.trace(&ray, &mut StdRng::seed_from_u64(0)),
             "ray should hit red triangle"
         );
         // Ray into red triangle "shadow" over yellow triangle
         let ray = Ray::new(Point3::new(0.0, 0.0, 5.0), Vector3::new(0.0, 0.0, -1.0));
         #[rustfmt::skip]
         assert_eq!(
-            Colour{red: 0.75, green: 0.0, blue: 0.0}, scene.trace(&ray),
+            Colour{red: 0.75, green: 0.0, blue: 0.0}, scene.trace(&ray, &mut StdRng::seed_from_u64(0)),
             "ray should hit yellow triangle on red triangle's shadow"
         );
     }
@@ -266,34 +322,28 @@ mod tests {
 
         #[test]
         fn tracing_empty_scene_yields_default_colour() {
+            #[rustfmt::skip]
             let scene = Scene::new(
-                Material {
-                    #[rustfmt::skip]
-                    diffuse: Colour {red: 1.0, green: 1.0, blue: 0.0,},
-                    #[rustfmt::skip]
-                    emission: Colour {red: 1.0, green: 1.0, blue: 1.0,},
-                },
+                Material::diffuse(Colour {red: 1.0, green: 1.0, blue: 0.0,})
+                    .with_emission(Colour {red: 1.0, green: 1.0, blue: 1.0,}),
                 0,
                 1,
             );
             let ray = Ray::new(Point3::new(0.0, 0.0, -1.0), Vector3::new(0.0, 0.0, 1.0));
             #[rustfmt::skip]
-            assert_eq!(Colour{red: 1.0, green: 1.0, blue: 0.0}, scene.trace(&ray));
+            assert_eq!(Colour{red: 1.0, green: 1.0, blue: 0.0}, scene.trace(&ray, &mut StdRng::seed_from_u64(0)));
         }
 
         #[test]
         fn tracing_with_miss_yields_default_colour() {
+            #[rustfmt::skip]
             let mut scene = Scene::new(
-                Material {
-                    #[rustfmt::skip]
-                    diffuse: Colour {red: 1.0, green: 1.0, blue: 0.0,},
-                    #[rustfmt::skip]
-                    emission: Colour {red: 1.0, green: 1.0, blue: 1.0,},
-                },
+                Material::diffuse(Colour {red: 1.0, green: 1.0, blue: 0.0,})
+                    .with_emission(Colour {red: 1.0, green: 1.0, blue: 1.0,}),
                 0,
                 1,
             );
-            scene.add_triangle(
+            scene.add(
                 Triangle::new([
                     Point3::new(2.0, 2.0, 0.0),
                     Point3::new(1.5, 2.5, 0.0),
@@ -307,83 +357,427 @@ mod tests {
             );
             let ray = Ray::new(Point3::new(0.0, 0.0, -1.0), Vector3::new(0.0, 0.0, 1.0));
             #[rustfmt::skip]
-            assert_eq!(Colour{red: 1.0, green: 1.0, blue: 0.0}, scene.trace(&ray));
+            assert_eq!(Colour{red: 1.0, green: 1.0, blue: 0.0}, scene.trace(&ray, &mut StdRng::seed_from_u64(0)));
         }
 
         #[test]
         fn tracing_with_hit_yields_hitted_primitive_colour() {
+            #[rustfmt::skip]
             let mut scene = Scene::new(
-                Material {
-                    #[rustfmt::skip]
-                    diffuse: Colour {red: 1.0, green: 1.0, blue: 0.0,},
-                    #[rustfmt::skip]
-                    emission: Colour {red: 1.0, green: 1.0, blue: 1.0,},
-                },
+                Material::diffuse(Colour {red: 1.0, green: 1.0, blue: 0.0,})
+                    .with_emission(Colour {red: 1.0, green: 1.0, blue: 1.0,}),
                 0,
                 1,
             );
-            scene.add_triangle(
+            #[rustfmt::skip]
+            scene.add(
                 Triangle::new([
                     Point3::new(1.0, -1.0, 0.0),
                     Point3::new(0.0, 1.0, 0.0),
                     Point3::new(-1.0, -1.0, 0.0),
                 ]),
-                Material {
-                    #[rustfmt::skip]
-                    diffuse: Colour {red: 0.0, green: 1.0, blue: 0.0,},
-                    ..Default::default()
-                },
+                Material::diffuse(Colour {red: 0.0, green: 1.0, blue: 0.0,}),
             );
             let ray = Ray::new(Point3::new(0.0, 0.0, -1.0), Vector3::new(0.0, 0.0, 1.0));
             #[rustfmt::skip]
-            assert_eq!(Colour{red: 0.0, green: 1.0, blue: 0.0}, scene.trace(&ray));
+            assert_eq!(Colour{red: 0.0, green: 1.0, blue: 0.0}, scene.trace(&ray, &mut StdRng::seed_from_u64(0)));
         }
 
         #[test]
         fn tracing_with_hit_yields_closest_hitted_primitive_colour() {
+            #[rustfmt::skip]
             let mut scene = Scene::new(
-                Material {
-                    #[rustfmt::skip]
-                    diffuse: Colour {red: 1.0, green: 1.0, blue: 0.0,},
-                    #[rustfmt::skip]
-                    emission: Colour {red: 1.0, green: 1.0, blue: 1.0,},
-                },
+                Material::diffuse(Colour {red: 1.0, green: 1.0, blue: 0.0,})
+                    .with_emission(Colour {red: 1.0, green: 1.0, blue: 1.0,}),
                 0,
                 1,
             );
-            scene.add_triangle(
+            #[rustfmt::skip]
+            scene.add(
                 Triangle::new([
                     Point3::new(1.0, -1.0, 1.1),
                     Point3::new(0.0, 1.0, 1.1),
                     Point3::new(-1.0, -1.0, 1.1),
                 ]),
-                Material {
-                    #[rustfmt::skip]
-                    diffuse: Colour {red: 1.0, green: 0.0, blue: 0.0,},
-                    ..Default::default()
-                },
+                Material::diffuse(Colour {red: 1.0, green: 0.0, blue: 0.0,}),
             );
-            scene.add_triangle(
+            #[rustfmt::skip]
+            scene.add(
                 Triangle::new([
                     Point3::new(1.0, -1.0, 1.0),
                     Point3::new(0.0, 1.0, 1.0),
                     Point3::new(-1.0, -1.0, 1.0),
                 ]),
+                Material::diffuse(Colour {red: 0.0, green: 1.0, blue: 0.0,}),
+            );
+            let ray = Ray::new(Point3::new(0.0, 0.0, -1.0), Vector3::new(0.0, 0.0, 1.0));
+            #[rustfmt::skip]
+            assert_eq!(Colour{red: 0.0, green: 1.0, blue: 0.0}, scene.trace(&ray, &mut StdRng::seed_from_u64(0)));
+        }
+    }
+
+    mod spheres_and_triangles {
+        use super::*;
+
+        fn scene_with_sphere_and_triangle_at(sphere_z: Scalar, triangle_z: Scalar) -> Scene {
+            let mut scene = Scene::new(
                 Material {
                     #[rustfmt::skip]
-                    diffuse: Colour {red: 0.0, green: 1.0, blue: 0.0,},
+                    emission: Colour {red: 1.0, green: 1.0, blue: 1.0,},
                     ..Default::default()
                 },
+                0,
+                1,
+            );
+            #[rustfmt::skip]
+            scene.add(
+                Sphere::new(Point3::new(0.0, 0.0, sphere_z), 0.5),
+                Material::diffuse(Colour {red: 1.0, green: 0.0, blue: 0.0,}),
             );
+            #[rustfmt::skip]
+            scene.add(
+                Triangle::new([
+                    Point3::new(1.0, -1.0, triangle_z),
+                    Point3::new(0.0, 1.0, triangle_z),
+                    Point3::new(-1.0, -1.0, triangle_z),
+                ]),
+                Material::diffuse(Colour {red: 0.0, green: 1.0, blue: 0.0,}),
+            );
+            scene
+        }
+
+        #[test]
+        fn sphere_in_front_of_triangle_is_hit() {
+            let scene = scene_with_sphere_and_triangle_at(1.0, 3.0);
             let ray = Ray::new(Point3::new(0.0, 0.0, -1.0), Vector3::new(0.0, 0.0, 1.0));
+            let hit = scene.closest_hit(&ray).unwrap();
+            assert_eq!(hit.intersection.point, Point3::new(0.0, 0.0, 0.5));
+            #[rustfmt::skip]
+            assert_eq!(Colour{red: 1.0, green: 0.0, blue: 0.0}, scene.trace(&ray, &mut StdRng::seed_from_u64(0)));
+        }
+
+        #[test]
+        fn triangle_in_front_of_sphere_is_hit() {
+            let scene = scene_with_sphere_and_triangle_at(3.0, 1.0);
+            let ray = Ray::new(Point3::new(0.0, 0.0, -1.0), Vector3::new(0.0, 0.0, 1.0));
+            let hit = scene.closest_hit(&ray).unwrap();
+            assert_eq!(hit.intersection.point, Point3::new(0.0, 0.0, 1.0));
+            #[rustfmt::skip]
+            assert_eq!(Colour{red: 0.0, green: 1.0, blue: 0.0}, scene.trace(&ray, &mut StdRng::seed_from_u64(0)));
+        }
+
+        #[test]
+        fn materials_stay_matched_with_interleaved_primitives() {
+            let mut scene = scene_with_sphere_and_triangle_at(1.0, 3.0);
+            for i in 0..10 {
+                let z = 5.0 + i as Scalar;
+                let colour = Colour {
+                    red: i as Scalar,
+                    green: 0.0,
+                    blue: 0.0,
+                };
+                if i % 2 == 0 {
+                    scene.add(
+                        Sphere::new(Point3::new(2.0, 0.0, z), 0.5),
+                        Material::diffuse(colour),
+                    );
+                } else {
+                    scene.add(
+                        Translation3::new(2.0, 0.0, z)
+                            * Triangle::new([
+                                Point3::new(1.0, -1.0, 0.0),
+                                Point3::new(0.0, 1.0, 0.0),
+                                Point3::new(-1.0, -1.0, 0.0),
+                            ]),
+                        Material::diffuse(colour),
+                    );
+                }
+            }
+            for i in 0..10 {
+                let origin = Point3::new(2.0, 0.0, 4.9 + i as Scalar);
+                let ray = Ray::new(origin, Vector3::new(0.0, 0.0, 1.0));
+                let hit = scene.closest_hit(&ray).unwrap();
+                assert_eq!(hit.material.get_albedo().red, i as Scalar);
+            }
+        }
+
+        #[test]
+        fn mesh_face_materials_take_precedence() {
+            let mut scene = scene_with_sphere_and_triangle_at(10.0, 10.0);
             #[rustfmt::skip]
-            assert_eq!(Colour{red: 0.0, green: 1.0, blue: 0.0}, scene.trace(&ray));
+            let blue = Material::diffuse(Colour {red: 0.0, green: 0.0, blue: 1.0,});
+            let mesh = Mesh::new(
+                vec![
+                    Point3::new(-1.0, -1.0, 1.0),
+                    Point3::new(1.0, -1.0, 1.0),
+                    Point3::new(1.0, 1.0, 1.0),
+                    Point3::new(-1.0, 1.0, 1.0),
+                ],
+                vec![[0, 1, 2], [0, 2, 3]],
+            );
+            scene.add(mesh.clone(), blue.clone());
+            scene.add(
+                Translation3::new(0.0, 0.0, -1.0)
+                    * mesh.with_face_materials(vec![blue.clone()], vec![0, 0]),
+                Material::default(),
+            );
+
+            let ray = Ray::new(Point3::new(0.5, -0.5, -1.0), Vector3::new(0.0, 0.0, 1.0));
+            let hit = scene.closest_hit(&ray).unwrap();
+            assert_eq!(hit.intersection.point, Point3::new(0.5, -0.5, 0.0));
+            assert_eq!(hit.material, &blue);
+            let ray = Ray::new(Point3::new(0.5, -0.5, 0.5), Vector3::new(0.0, 0.0, 1.0));
+            let hit = scene.closest_hit(&ray).unwrap();
+            assert_eq!(hit.intersection.point, Point3::new(0.5, -0.5, 1.0));
+            assert_eq!(hit.material, &blue);
+        }
+
+        #[test]
+        fn sphere_size_is_used_for_its_hits() {
+            let scene = scene_with_sphere_and_triangle_at(1.0, 3.0);
+            let ray = Ray::new(Point3::new(0.0, 0.0, -1.0), Vector3::new(0.0, 0.0, 1.0));
+            let hit = scene.closest_hit(&ray).unwrap();
+            assert_eq!(hit.primitive_size, std::f32::consts::PI);
+        }
+    }
+
+    mod lights {
+        use super::*;
+
+        fn emitter(value: Scalar) -> Material {
+            Material {
+                #[rustfmt::skip]
+                emission: Colour {red: value, green: value, blue: value,},
+                ..Default::default()
+            }
+        }
+
+        /// Emissive sphere, dark triangle, emissive triangle of area 2 at z = 5
+        /// and mesh at z = 1 with one emissive face of area 2
+        fn scene_with_lights() -> Scene {
+            let mut scene = Scene::new(Material::default(), 1, 1);
+            scene.add(Sphere::new(Point3::new(0.0, 0.0, 20.0), 1.0), emitter(1.0));
+            let triangle = Triangle::new([
+                Point3::new(-1.0, -1.0, 5.0),
+                Point3::new(1.0, -1.0, 5.0),
+                Point3::new(-1.0, 1.0, 5.0),
+            ]);
+            scene.add(
+                Translation3::new(0.0, 0.0, 1.0) * triangle,
+                Material::default(),
+            );
+            scene.add(triangle, emitter(2.0));
+            let mesh = Mesh::new(
+                vec![
+                    Point3::new(-1.0, -1.0, 1.0),
+                    Point3::new(1.0, -1.0, 1.0),
+                    Point3::new(1.0, 1.0, 1.0),
+                    Point3::new(-1.0, 1.0, 1.0),
+                ],
+                vec![[0, 1, 2], [0, 2, 3]],
+            )
+            .with_face_materials(vec![emitter(3.0), Material::default()], vec![0, 1]);
+            scene.add(mesh, emitter(4.0));
+            scene
+        }
+
+        #[test]
+        fn emissive_triangles_and_mesh_faces_are_sampled() {
+            let scene = scene_with_lights();
+            let mut rng = StdRng::seed_from_u64(0);
+            for _ in 0..1000 {
+                let light = scene
+                    .sample_light(rng.gen(), &Point2::new(rng.gen(), rng.gen()))
+                    .unwrap();
+                assert_eq!(light.pdf, 0.25);
+                if light.point.z == 5.0 {
+                    assert_eq!(light.emission, emitter(2.0).emission);
+                } else {
+                    assert_eq!(light.point.z, 1.0);
+                    // Only the face below the diagonal is emissive
+                    assert!(light.point.x >= light.point.y - 1e-6);
+                    assert_eq!(light.emission, emitter(3.0).emission);
+                }
+            }
+            let dark = Scene::new(emitter(1.0), 1, 1);
+            assert_eq!(dark.sample_light(0.5, &Point2::new(0.5, 0.5)), None);
+        }
+
+        #[test]
+        fn light_pdf_matches_sampled_direction() {
+            let scene = scene_with_lights();
+            let origin = Point3::new(0.3, 0.2, -2.0);
+            let mut rng = StdRng::seed_from_u64(1);
+            let mut visible = 0;
+            for _ in 0..100 {
+                let light = scene
+                    .sample_light(rng.gen(), &Point2::new(rng.gen(), rng.gen()))
+                    .unwrap();
+                let ray = Ray::new(origin, light.point - origin);
+                let hit = scene.closest_hit(&ray).unwrap();
+                if (hit.intersection.point - light.point).norm() > 1e-4 {
+                    // Light at z = 5 is hidden behind the mesh
+                    continue;
+                }
+                visible += 1;
+                let distance_squared = (light.point - origin).norm_squared();
+                let cosine = ray.direction.dot(&light.normal).abs();
+                let expected = light.pdf * distance_squared / cosine;
+                let pdf = scene.get_light_pdf(&ray, &hit);
+                assert!(
+                    (pdf - expected).abs() < 1e-3 * expected,
+                    "{} {}",
+                    pdf,
+                    expected
+                );
+            }
+            assert!(visible > 25);
+            // Dark face of the mesh and emissive sphere are not sampled
+            for ray in [
+                Ray::new(origin, Vector3::new(-0.5, 0.5, 3.0)),
+                Ray::new(Point3::new(0.0, 0.0, 10.0), Vector3::z()),
+            ] {
+                let hit = scene.closest_hit(&ray).unwrap();
+                assert_eq!(scene.get_light_pdf(&ray, &hit), 0.0);
+            }
+        }
+
+        #[test]
+        fn adding_primitive_updates_lights() {
+            let mut scene = Scene::new(Material::default(), 1, 1);
+            assert_eq!(scene.sample_light(0.5, &Point2::new(0.5, 0.5)), None);
+            scene.add(
+                Triangle::new([
+                    Point3::new(0.0, 0.0, 0.0),
+                    Point3::new(1.0, 0.0, 0.0),
+                    Point3::new(0.0, 1.0, 0.0),
+                ]),
+                emitter(1.0),
+            );
+            let light = scene.sample_light(0.5, &Point2::new(0.5, 0.5)).unwrap();
+            assert_eq!(light.pdf, 2.0);
         }
     }
 
     mod primitives_with_materials_tests {
         use super::*;
 
+        /// Reference implementation checking every primitive
+        fn brute_force_closest_hit(
+            primitives: &PrimitivesWithMaterials<Triangle>,
+            ray: &Ray,
+        ) -> Option<HitResult> {
+            primitives
+                .primitives
+                .iter()
+                .enumerate()
+                .filter_map(|(index, t)| {
+                    t.intersects(ray).map(|intersection| HitResult {
+                        intersection,
+                        index,
+                    })
+                })
+                .min_by(|h1, h2| h1.intersection.t.partial_cmp(&h2.intersection.t).unwrap())
+        }
+
+        fn random_point(rng: &mut StdRng, extent: Scalar) -> Point3 {
+            Point3::new(
+                rng.gen_range(-extent..extent),
+                rng.gen_range(-extent..extent),
+                rng.gen_range(-extent..extent),
+            )
+        }
+
+        fn random_triangle_soup(
+            rng: &mut StdRng,
+            count: usize,
+            triangle_size: Scalar,
+        ) -> PrimitivesWithMaterials<Triangle> {
+            let mut primitives = PrimitivesWithMaterials::new();
+            for _ in 0..count {
+                let centre = random_point(rng, 10.0);
+                primitives.add(
+                    Triangle::new([
+                        centre + random_point(rng, triangle_size).coords,
+                        centre + random_point(rng, triangle_size).coords,
+                        centre + random_point(rng, triangle_size).coords,
+                    ]),
+                    Material::default(),
+                );
+            }
+            primitives
+        }
+
+        fn assert_matches_brute_force(
+            primitives: &PrimitivesWithMaterials<Triangle>,
+            rng: &mut StdRng,
+        ) {
+            let mut hits = 0;
+            for _ in 0..500 {
+                let origin = random_point(rng, 15.0);
+                let target = random_point(rng, 5.0);
+                let ray = Ray::new(origin, target - origin);
+                let expected = brute_force_closest_hit(primitives, &ray);
+                assert_eq!(expected, primitives.closest_hit(&ray), "for {:?}", ray);
+                hits += expected.is_some() as usize;
+            }
+            assert!(hits > 0, "test rays should hit something");
+        }
+
+        #[test]
+        fn closest_hit_matches_brute_force_for_random_small_triangles() {
+            let mut rng = StdRng::seed_from_u64(7);
+            let primitives = random_triangle_soup(&mut rng, 1000, 0.5);
+            assert_matches_brute_force(&primitives, &mut rng);
+        }
+
+        #[test]
+        fn closest_hit_matches_brute_force_for_random_overlapping_triangles() {
+            let mut rng = StdRng::seed_from_u64(13);
+            let primitives = random_triangle_soup(&mut rng, 300, 5.0);
+            assert_matches_brute_force(&primitives, &mut rng);
+        }
+
+        #[test]
+        fn closest_hit_matches_brute_force_for_axis_aligned_triangles() {
+            let mut rng = StdRng::seed_from_u64(21);
+            let mut primitives = PrimitivesWithMaterials::new();
+            for _ in 0..500 {
+                let p = random_point(&mut rng, 10.0);
+                let (x, y, z) = (p.x.round(), p.y.round(), p.z.round());
+                primitives.add(
+                    Triangle::new([
+                        Point3::new(x, y, z),
+                        Point3::new(x + 1.0, y, z),
+                        Point3::new(x, y + 1.0, z),
+                    ]),
+                    Material::default(),
+                );
+            }
+            assert_matches_brute_force(&primitives, &mut rng);
+        }
+
+        #[test]
+        fn adding_primitive_invalidates_acceleration_structure() {
+            let mut primitives: PrimitivesWithMaterials<Triangle> = PrimitivesWithMaterials::new();
+            let ray = Ray::new(Point3::new(0.0, 0.0, -1.0), Vector3::new(0.0, 0.0, 1.0));
+            assert_eq!(None, primitives.closest_hit(&ray));
+            primitives.add(
+                Triangle::new([
+                    Point3::new(1.0, -1.0, 0.0),
+                    Point3::new(0.0, 1.0, 0.0),
+                    Point3::new(-1.0, -1.0, 0.0),
+                ]),
+                Material::default(),
+            );
+            assert_eq!(
+                Some((0, Point3::new(0.0, 0.0, 0.0))),
+                primitives
+                    .closest_hit(&ray)
+                    .map(|h| (h.index, h.intersection.point))
+            );
+        }
+
         #[test]
         fn test_getters() {
             let mut primitives: PrimitivesWithMaterials<Triangle> = PrimitivesWithMaterials::new();
@@ -472,11 +866,10 @@ mod tests {
             );
             let ray = Ray::new(Point3::new(0.0, 0.0, -1.0), Vector3::new(0.0, 0.0, 1.0));
             assert_eq!(
-                Some(HitResult {
-                    index: 0,
-                    point: Point3::new(0.0, 0.0, 0.0)
-                }),
-                primitives.closest_hit(&ray)
+                Some((0, Point3::new(0.0, 0.0, 0.0))),
+                primitives
+                    .closest_hit(&ray)
+                    .map(|h| (h.index, h.intersection.point))
             );
         }
 
@@ -509,61 +902,10 @@ mod tests {
             );
             let ray = Ray::new(Point3::new(0.0, 0.0, -1.0), Vector3::new(0.0, 0.0, 1.0));
             assert_eq!(
-                Some(HitResult {
-                    index: 1,
-                    point: Point3::new(0.0, 0.0, 1.0)
-                }),
-                primitives.closest_hit(&ray)
-            );
-        }
-    }
-
-    mod trace_result_tests {
-        use super::*;
-
-        #[test]
-        fn trace_result_can_be_created_from_material() {
-            let material = Material {
-                #[rustfmt::skip]
-                diffuse: Colour {red: 0.0, green: 0.0, blue: 0.0,},
-                #[rustfmt::skip]
-                emission: Colour {red: 1.0, green: 1.0, blue: 1.0,},
-            };
-            let trace_result = TraceResult::from(material);
-            assert_eq!(
-                trace_result,
-                TraceResult {
-                    #[rustfmt::skip]
-                    diffuse: Colour {red: 0.0, green: 0.0, blue: 0.0,},
-                    #[rustfmt::skip]
-                    emission: Colour {red: 1.0, green: 1.0, blue: 1.0,},
-                }
-            );
-        }
-
-        #[test]
-        fn trace_results_can_add_lights() {
-            let mut tr1 = TraceResult {
-                #[rustfmt::skip]
-                diffuse: Colour {red: 1.0, green: 0.0, blue: 0.0,},
-                #[rustfmt::skip]
-                emission: Colour {red: 1.0, green: 0.0, blue: 0.25,},
-            };
-            let tr2 = TraceResult {
-                #[rustfmt::skip]
-                diffuse: Colour {red: 0.0, green: 1.0, blue: 0.0,},
-                #[rustfmt::skip]
-                emission: Colour {red: 0.0, green: 1.0, blue: 0.35,},
-            };
-            tr1.add_light(&tr2);
-            assert_eq!(
-                tr1,
-                TraceResult {
-                    #[rustfmt::skip]
-                    diffuse: Colour {red: 1.0, green: 0.0, blue: 0.0,},
-                    #[rustfmt::skip]
-                    emission: Colour {red: 1.0, green: 1.0, blue: 0.6,},
-                }
+                Some((1, Point3::new(0.0, 0.0, 1.0))),
+                primitives
+                    .closest_hit(&ray)
+                    .map(|h| (h.index, h.intersection.point))
             );
         }
     }
@@ -1,16 +1,22 @@
-use crate::{Point2, Point3, Ray, Scalar, Vector2};
-use noise::{NoiseFn, OpenSimplex};
+use std::sync::Arc;
+
+use rand::RngCore;
+
+use crate::{Point2, Point3, Ray, Sampler, Scalar, StratifiedSampler};
 
 pub type Perspective3 = nalgebra::Perspective3<Scalar>;
 pub type ScreenPoint = nalgebra::Point2<u32>;
 
-/// A viewport class
+/// A viewport class.
+/// Rays cast through a pixel are spread over it by a sampler,
+/// which is a jittered stratified sampler by default.
 #[derive(Debug, Clone)]
 pub struct Viewport {
     width: Scalar,
     height: Scalar,
     projection: Perspective3,
-    point_offsets: Vec<Vector2>,
+    point_rays_count: usize,
+    sampler: Arc<dyn Sampler>,
 }
 
 impl Viewport {
@@ -22,21 +28,27 @@ impl Viewport {
         zfar: Scalar,
         point_rays_count: usize,
     ) -> Self {
-        let noise = OpenSimplex::new();
-        let coords = (0..point_rays_count)
-            .map(|_| {
-                let pt = [0.0, 0.0];
-                Vector2::new(noise.get(pt) as f32, noise.get(pt) as f32)
-            })
-            .map(|v| v * 0.5);
         Self {
             width: width as Scalar,
             height: height as Scalar,
             projection: Perspective3::new(width as Scalar / height as Scalar, fovy, znear, zfar),
-            point_offsets: coords.collect(),
+            point_rays_count,
+            sampler: Arc::new(StratifiedSampler::default()),
+        }
+    }
+
+    /// Sets sampler spreading rays over pixels
+    pub fn with_sampler(self, sampler: impl Sampler + 'static) -> Self {
+        Self {
+            sampler: Arc::new(sampler),
+            ..self
         }
     }
 
+    pub fn get_sampler(&self) -> &dyn Sampler {
+        self.sampler.as_ref()
+    }
+
     pub fn get_width(&self) -> Scalar {
         self.width
     }
@@ -50,7 +62,7 @@ impl Viewport {
     }
 
     pub fn get_rays_count(&self) -> usize {
-        self.point_offsets.len()
+        self.point_rays_count
     }
 
     pub fn normalize_point(&self, screen_point: Point2) -> Point2 {
@@ -60,12 +72,19 @@ impl Viewport {
         )
     }
 
-    pub fn cast_ray<'a>(&'a self, screen_point: ScreenPoint) -> impl Iterator<Item = Ray> + 'a {
-        self.point_offsets
-            .iter()
+    /// Casts rays through points of the pixel chosen by sampler.
+    /// Randomized samplers draw random numbers from `rng`.
+    pub fn cast_ray<'a>(
+        &'a self,
+        screen_point: ScreenPoint,
+        rng: &mut dyn RngCore,
+    ) -> impl Iterator<Item = Ray> + 'a {
+        self.sampler
+            .get_samples(self.point_rays_count, rng)
+            .into_iter()
             .map(move |off| {
                 self.normalize_point(
-                    Point2::new(screen_point.x as Scalar, screen_point.y as Scalar) + off,
+                    Point2::new(screen_point.x as Scalar, screen_point.y as Scalar) + off.coords,
                 )
             })
             .map(|pt| {
@@ -87,19 +106,23 @@ impl Viewport {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::{HaltonSampler, SobolSampler, UniformSampler};
+    use rand::{rngs::StdRng, SeedableRng};
 
     #[test]
     fn viewport_creation() {
         let vp = Viewport::new(640, 480, 2.0, 1.0, 100.0, 2);
-        assert!(vp.get_width() - 640.0 <= std::f32::EPSILON);
-        assert!(vp.get_height() - 480.0 <= std::f32::EPSILON);
+        assert!(vp.get_width() - 640.0 <= f32::EPSILON);
+        assert!(vp.get_height() - 480.0 <= f32::EPSILON);
         assert_eq!(vp.get_rays_count(), 2);
     }
 
     #[test]
     fn ray_casting() {
         let vp = Viewport::new(640, 480, 2.0, 1.0, 100.0, 25);
-        let rays: Vec<Ray> = vp.cast_ray(ScreenPoint::new(320, 240)).collect();
+        let rays: Vec<Ray> = vp
+            .cast_ray(ScreenPoint::new(320, 240), &mut StdRng::seed_from_u64(0))
+            .collect();
         assert_eq!(rays.len(), 25);
         for ray in rays {
             assert!((ray.origin - Point3::new(0.0, 0.0, -1.0)).norm() <= 0.5_f32.sqrt());
@@ -131,4 +154,45 @@ mod tests {
             Point2::new(0.0, 0.0)
         );
     }
+
+    #[test]
+    fn rays_are_spread_across_pixel() {
+        let samplers: Vec<Viewport> = vec![
+            Viewport::new(64, 48, 1.0, 1.0, 100.0, 16),
+            Viewport::new(64, 48, 1.0, 1.0, 100.0, 16).with_sampler(UniformSampler),
+            Viewport::new(64, 48, 1.0, 1.0, 100.0, 16).with_sampler(HaltonSampler),
+            Viewport::new(64, 48, 1.0, 1.0, 100.0, 16).with_sampler(SobolSampler),
+        ];
+        let pixel = ScreenPoint::new(10, 20);
+        // Corners of the pixel on the near plane
+        let corner = |x: u32, y: u32| {
+            let pt = vp_point(&samplers[0], x, y);
+            samplers[0]
+                .get_projection()
+                .unproject_point(&Point3::new(pt.x, pt.y, -1.0))
+        };
+        let (top_left, bottom_right) = (corner(10, 20), corner(11, 21));
+        for vp in samplers {
+            let rays: Vec<Ray> = vp.cast_ray(pixel, &mut StdRng::seed_from_u64(1)).collect();
+            assert_eq!(rays.len(), 16);
+            let inside = |p: &Point3| {
+                (top_left.x..bottom_right.x).contains(&p.x)
+                    && (bottom_right.y..top_left.y).contains(&p.y)
+            };
+            assert!(rays.iter().all(|r| inside(&r.origin)), "{:?}", vp);
+            // Rays reach every quarter of the pixel
+            let centre = nalgebra::center(&top_left, &bottom_right);
+            let mut quarters = [false; 4];
+            for ray in &rays {
+                let quarter =
+                    (ray.origin.x > centre.x) as usize * 2 + (ray.origin.y > centre.y) as usize;
+                quarters[quarter] = true;
+            }
+            assert_eq!(quarters, [true; 4], "{:?}", vp);
+        }
+    }
+
+    fn vp_point(vp: &Viewport, x: u32, y: u32) -> Point2 {
+        vp.normalize_point(Point2::new(x as Scalar, y as Scalar))
+    }
 }